@@ -0,0 +1,55 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+/// Cache directory used as root. Checkouts are kept here rather than in the
+/// temp directory, where any local user could plant one in advance.
+pub const ROOT_CACHE_DIR: &str = "/var/cache/autopkg";
+
+/// Directory for `name` (e.g. `config-repo`) in the cache directory:
+/// [`ROOT_CACHE_DIR`] as root, else `$XDG_CACHE_HOME/autopkg` or
+/// `~/.cache/autopkg`. The cache directory is created with mode 0700 and
+/// refused unless it is owned by the current user with mode 0700, so nothing
+/// in it can have been put there by another user.
+pub fn dir(name: &str) -> Result<PathBuf> {
+    let root = root()?;
+    if !root.exists() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&root)
+            .with_context(|| format!("Failed to create {}", root.display()))?;
+    }
+    check_private(&root)?;
+    Ok(root.join(name))
+}
+
+fn root() -> Result<PathBuf> {
+    if nix::unistd::geteuid().is_root() {
+        return Ok(PathBuf::from(ROOT_CACHE_DIR));
+    }
+    if let Some(cache_home) = std::env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(cache_home).join("autopkg"));
+    }
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(|home| PathBuf::from(home).join(".cache/autopkg"))
+        .ok_or_else(|| anyhow!("HOME is not set; can't locate the cache directory"))
+}
+
+/// Refuse `dir` unless it is a directory (not a symlink) owned by the current
+/// user that no one else can access.
+pub fn check_private(dir: &Path) -> Result<()> {
+    let metadata =
+        fs::symlink_metadata(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    let uid = nix::unistd::geteuid().as_raw();
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o777 != 0o700 {
+        return Err(anyhow!(
+            "Refusing to use {}: it must be a directory owned by uid {} with mode 0700",
+            dir.display(),
+            uid
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use crate::config::Collector;
+use crate::fetcher::{send, HttpClient};
+use crate::report::RunReport;
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use serde::Serialize;
+
+/// Body POSTed to the collector: the run's JSON report with the host it ran on.
+#[derive(Debug, Serialize)]
+struct Summary<'a> {
+    hostname: String,
+    /// Unix time the summary was sent
+    timestamp: u64,
+    autopkg_version: &'static str,
+    #[serde(flatten)]
+    report: &'a RunReport,
+}
+
+/// POST the run summary to the collector endpoint, authenticated with the
+/// bearer token from `token_env` if set.
+pub fn post(collector: &Collector, http: &HttpClient, report: &RunReport) -> Result<()> {
+    let summary = Summary {
+        hostname: crate::host::hostname(),
+        timestamp: crate::state::now(),
+        autopkg_version: env!("CARGO_PKG_VERSION"),
+        report,
+    };
+
+    let mut request = http.client().post(&collector.url).json(&summary);
+    if let Some(var) = &collector.token_env {
+        let token = std::env::var(var).map_err(|_| {
+            anyhow!(
+                "Environment variable {} (collector.token_env) is not set",
+                var
+            )
+        })?;
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .context("Invalid collector token")?;
+        value.set_sensitive(true);
+        request = request.header(AUTHORIZATION, value);
+    }
+
+    let resp = send(request)?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("Collector returned status {}", resp.status()));
+    }
+    info!("Sent run summary to {}", collector.url);
+    Ok(())
+}
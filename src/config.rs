@@ -1,10 +1,891 @@
-use serde::de::Deserializer;
+use anyhow::{anyhow, Context, Result};
+use serde::de::{self, Deserializer, MapAccess, Visitor};
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 /// Top-level configuration file structure.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub applications: Vec<ApplicationConfig>,
+
+    /// Global settings
+    #[serde(default)]
+    pub settings: Settings,
+
+    /// Where to report applications that were updated (or failed)
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+
+    /// HTTP client shared by fetchers, notifications and the collector
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Additional YAML files (relative to this config) whose `applications` are merged in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// Named profiles that override application settings when selected with `--profile`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+/// Global settings that apply to the whole run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    /// Run `needrestart` after deb packages were installed
+    #[serde(default)]
+    pub needrestart: Option<NeedrestartMode>,
+
+    /// Command used to run installers as root when autopkg isn't root, e.g.
+    /// "doas" or "run0" (defaults to "sudo")
+    #[serde(default)]
+    pub privilege_command: Option<String>,
+
+    /// Schedule a reboot at this time (`shutdown -r` syntax, e.g. "03:00") after
+    /// updates that need one; without it a needed reboot is only reported
+    #[serde(default)]
+    pub reboot_at: Option<String>,
+
+    /// Maximum number of asset downloads running at once (unlimited if unset)
+    #[serde(default)]
+    pub max_concurrent_downloads: Option<usize>,
+
+    /// API token used by GitHub fetchers that don't set their own
+    #[serde(default, skip_serializing)]
+    pub github_token: Option<String>,
+
+    /// Path of the file persisting state between runs (defaults to
+    /// autopkg-state.json next to the config file)
+    #[serde(default)]
+    pub state_file: Option<String>,
+
+    /// Path of the audit history file (defaults to autopkg-history.jsonl next
+    /// to the config file)
+    #[serde(default)]
+    pub history_file: Option<String>,
+
+    /// File holding the key used to sign history records
+    #[serde(default)]
+    pub history_key_file: Option<String>,
+
+    /// Environment variable holding the key used to sign history records
+    #[serde(default)]
+    pub history_key_env: Option<String>,
+
+    /// Git repository holding the real config file, synced before each run
+    #[serde(default)]
+    pub config_repo: Option<ConfigRepo>,
+
+    /// Endpoint receiving each run's summary, for a fleet-wide dashboard
+    #[serde(default)]
+    pub collector: Option<Collector>,
+
+    /// Distro codename substituted for `${codename}` in file patterns
+    /// (defaults to VERSION_CODENAME from /etc/os-release)
+    #[serde(default)]
+    pub codename: Option<String>,
+
+    /// Architecture substituted for `${arch}` in file patterns (defaults to the
+    /// host's, named as its package format does: amd64 on Debian, x86_64 on RPM
+    /// distros)
+    #[serde(default)]
+    pub arch: Option<String>,
+
+    /// OS family (`debian`, `rhel` or `suse`) used for `${os_family}` / `${pkg_ext}`
+    /// in file patterns and by `installer: auto` (defaults to the one detected
+    /// from /etc/os-release)
+    #[serde(default)]
+    pub os_family: Option<String>,
+
+    /// Directory receiving downloads that fail verification or the pre-install
+    /// scan, with a sidecar explaining why (they are deleted if unset)
+    #[serde(default)]
+    pub quarantine_dir: Option<String>,
+
+    /// Cleanup of old downloads (`autopkg gc` and the optional end-of-run sweep)
+    #[serde(default)]
+    pub gc: GcSettings,
+}
+
+/// Which downloaded files `gc` removes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GcSettings {
+    /// Remove downloads older than this (e.g. "7d"; defaults to 7 days)
+    #[serde(default)]
+    pub download_ttl: Option<String>,
+
+    /// Keep at most this many downloads per application (unlimited if unset)
+    #[serde(default)]
+    pub keep_versions: Option<usize>,
+
+    /// Also sweep at the end of every `run`
+    #[serde(default)]
+    pub after_run: bool,
+}
+
+/// Notifications sent after an application is updated.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// URL POSTed a JSON body with the application and its old and new versions
+    #[serde(default)]
+    pub webhook: Option<String>,
+
+    /// Also show a desktop notification with `notify-send` (defaults to false)
+    #[serde(default)]
+    pub desktop: Option<bool>,
+
+    /// Also notify when an application fails (defaults to false)
+    #[serde(default)]
+    pub notify_on_failure: Option<bool>,
+}
+
+/// Settings of the HTTP client shared by all HTTP requests.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// Time a request may take, from connecting until the body is read
+    /// (e.g. "30s", "10m"; defaults to 30s). Raise it for large assets.
+    #[serde(default)]
+    pub timeout: Option<String>,
+
+    /// Time establishing a connection may take (e.g. "10s"; defaults to no
+    /// limit other than `timeout`)
+    #[serde(default)]
+    pub connect_timeout: Option<String>,
+
+    /// User-Agent header sent with requests (defaults to "autopkg-rust/0.1")
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+/// Location of a config file kept in a git repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigRepo {
+    /// Clone URL (https or ssh)
+    pub url: String,
+
+    /// Branch to track (defaults to the remote's default branch)
+    #[serde(default)]
+    pub branch: Option<String>,
+
+    /// Path of the config file inside the repository (defaults to autopkg.yml)
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Environment variable holding a token for https URLs
+    #[serde(default)]
+    pub token_env: Option<String>,
+}
+
+/// Endpoint the run summary is POSTed to after every run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collector {
+    /// URL receiving the JSON summary
+    pub url: String,
+
+    /// Environment variable holding a bearer token for the endpoint
+    #[serde(default)]
+    pub token_env: Option<String>,
+}
+
+/// How to use `needrestart` after deb installs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NeedrestartMode {
+    /// Only report services that need a restart
+    Report,
+    /// Restart services automatically (`needrestart -r a`)
+    Restart,
+}
+
+/// A named set of per-application overrides.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileConfig {
+    /// Overrides keyed by application name, merged over that application's settings
+    #[serde(default)]
+    pub applications: BTreeMap<String, Value>,
+}
+
+impl Config {
+    /// Load a configuration file, merging in the applications of any `include`d
+    /// files, then `defaults` under each application, and then the named profile
+    /// (if any) over the base application settings.
+    pub fn load(path: &Path, profile: Option<&str>) -> Result<Config> {
+        Self::load_with_overrides(path, profile, &[])
+    }
+
+    /// Like [`Config::load`], then apply `--set` overrides (`path=value`, e.g.
+    /// `app.firefox.pinned=true` or `settings.max_concurrent_downloads=1`).
+    /// Paths that don't name a config field are an error.
+    pub fn load_with_overrides(
+        path: &Path,
+        profile: Option<&str>,
+        overrides: &[String],
+    ) -> Result<Config> {
+        let mut value = if path.is_dir() {
+            read_config_dir(path)?
+        } else {
+            let mut value = read_yaml(path)?;
+            let mut included = Vec::new();
+            collect_includes(path, &value, &mut vec![canonical(path)], &mut included)?;
+            if !included.is_empty() {
+                merge_included_applications(path, &mut value, included)?;
+            }
+            value
+        };
+
+        apply_defaults(&mut value)?;
+
+        if let Some(profile) = profile {
+            apply_profile(&mut value, profile)?;
+        }
+
+        let mut overridden = Vec::new();
+        for spec in overrides {
+            let segments = apply_override(&mut value, spec)?;
+            overridden.push((spec, schema_path(&value, &segments)));
+        }
+
+        expand_env_vars(&mut value)
+            .with_context(|| format!("Failed to expand config {}", path.display()))?;
+
+        // Fields serde doesn't know are silently dropped; an override of one
+        // (or of anything below one) is a typo, so catch those here
+        let mut ignored = Vec::new();
+        let config: Config =
+            serde_ignored::deserialize(value, |field| ignored.push(field.to_string()))
+                .with_context(|| format!("Failed to parse config {}", path.display()))?;
+        for (spec, target) in &overridden {
+            let nested = |outer: &str, inner: &str| {
+                inner == outer
+                    || inner
+                        .strip_prefix(outer)
+                        .is_some_and(|rest| rest.starts_with('.'))
+            };
+            if ignored
+                .iter()
+                .any(|field| nested(field, target) || nested(target, field))
+            {
+                return Err(anyhow!("Unknown config path in --set {}", spec));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Distinct application schedules, in config order.
+    pub fn schedules(&self) -> Vec<String> {
+        let mut schedules: Vec<String> = Vec::new();
+        for schedule in self.applications.iter().filter_map(|a| a.schedule.as_ref()) {
+            if !schedules.contains(schedule) {
+                schedules.push(schedule.clone());
+            }
+        }
+        schedules
+    }
+
+    /// Check the configuration for likely mistakes, returning a warning message for each.
+    pub fn validate(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        for (i, a) in self.applications.iter().enumerate() {
+            if !a.fetcher.prefer.is_empty() && !a.fetcher.file_pattern.is_empty() {
+                warnings.push(format!(
+                    "Application '{}' sets both `prefer` and `file_pattern`; `file_pattern` is ignored",
+                    a.name
+                ));
+            }
+            if a.fetcher.download_url.is_some()
+                && !(a.fetcher.file_pattern.is_empty() && a.fetcher.prefer.is_empty())
+            {
+                warnings.push(format!(
+                    "Application '{}' sets `download_url`; its `file_pattern`/`prefer` are ignored",
+                    a.name
+                ));
+            }
+            if let Err(e) = crate::fetcher::check_fields(&a.fetcher, a) {
+                warnings.push(e.to_string());
+            }
+            if a.stream == Some(true) && a.pre_install_scan.is_some() {
+                warnings.push(format!(
+                    "Application '{}' sets `stream`, but `pre_install_scan` needs the downloaded file; downloads are saved to a file",
+                    a.name
+                ));
+            }
+            if a.run_as.is_some() && a.installer.r#type != "cargo" {
+                warnings.push(format!(
+                    "Application '{}' sets `run_as`, but the {} installer always installs as root (only hooks run as that user)",
+                    a.name, a.installer.r#type
+                ));
+            }
+            for b in &self.applications[i + 1..] {
+                if a.installer.r#type == b.installer.r#type
+                    && a.effective_package_name() == b.effective_package_name()
+                {
+                    warnings.push(format!(
+                        "Applications '{}' and '{}' both install package '{}'",
+                        a.name,
+                        b.name,
+                        a.effective_package_name()
+                    ));
+                } else if a.fetcher.overlaps(&b.fetcher) {
+                    warnings.push(format!(
+                        "Applications '{}' and '{}' track the same source with overlapping file patterns ({:?} / {:?})",
+                        a.name,
+                        b.name,
+                        a.fetcher.file_patterns(),
+                        b.fetcher.file_patterns()
+                    ));
+                }
+            }
+        }
+
+        warnings
+    }
+}
+
+impl ApplicationConfig {
+    /// Package name used by installers: `package_name`, or `name` if not set.
+    pub fn effective_package_name(&self) -> &str {
+        self.package_name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Parsed `check_interval`, if set.
+    pub fn check_interval(&self) -> Result<Option<std::time::Duration>> {
+        self.check_interval
+            .as_deref()
+            .map(|interval| {
+                humantime::parse_duration(interval).map_err(|e| {
+                    anyhow!(
+                        "Invalid check_interval '{}' for application '{}': {}",
+                        interval,
+                        self.name,
+                        e
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// Whether the application is selected by `run --group` / `--tag` (a filter
+    /// that isn't given selects everything).
+    pub fn is_selected(&self, group: Option<&str>, tag: Option<&str>) -> bool {
+        group.is_none_or(|g| self.group.as_deref() == Some(g))
+            && tag.is_none_or(|t| self.tags.iter().flatten().any(|own| own == t))
+    }
+}
+
+impl FetcherConfig {
+    /// Whether two fetchers read from the same source and could select the same asset.
+    ///
+    /// Patterns overlap when they are equal or when one pattern matches the other
+    /// pattern's text (e.g. `*.deb` and `*amd64.deb`).
+    fn overlaps(&self, other: &FetcherConfig) -> bool {
+        let source = |f: &FetcherConfig| f.repo.clone().or_else(|| f.url.clone());
+        if self.r#type != other.r#type || source(self).is_none() || source(self) != source(other) {
+            return false;
+        }
+
+        let matches = |pattern: &str, text: &str| {
+            glob::Pattern::new(pattern)
+                .map(|p| p.matches(text))
+                .unwrap_or(false)
+        };
+        self.file_patterns().iter().any(|a| {
+            other
+                .file_patterns()
+                .iter()
+                .any(|b| a == b || matches(a, b) || matches(b, a))
+        })
+    }
+
+    /// Configured file patterns in the order they are tried: the `prefer` patterns,
+    /// else `file_pattern`, else `*`. Host placeholders such as `${codename}` are
+    /// not yet substituted.
+    pub fn file_patterns(&self) -> Vec<&str> {
+        if !self.prefer.is_empty() {
+            self.prefer.iter().map(|p| p.pattern.as_str()).collect()
+        } else if self.file_pattern.is_empty() {
+            vec!["*"]
+        } else {
+            self.file_pattern.iter().map(String::as_str).collect()
+        }
+    }
+
+    /// Installer type for assets selected by the `index`-th of `file_patterns()`,
+    /// if a `prefer` entry sets one.
+    pub fn preferred_installer(&self, index: usize) -> Option<&str> {
+        self.prefer.get(index)?.installer.as_deref()
+    }
+}
+
+fn read_yaml(path: &Path) -> Result<Value> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    serde_yaml::from_str(&contents)
+        .with_context(|| format!("Failed to parse config YAML {}", path.display()))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Read a directory of config fragments: every `*.yml` / `*.yaml` file in it, in
+/// name order. Their `applications` (and those of the files they include) are
+/// concatenated; any other top-level keys are merged, later files winning.
+fn read_config_dir(dir: &Path) -> Result<Value> {
+    let mut fragments = Vec::new();
+    for entry in fs::read_dir(dir)
+        .with_context(|| format!("Failed to read config directory {}", dir.display()))?
+    {
+        let path = entry?.path();
+        let is_yaml = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("yml" | "yaml")
+        );
+        let hidden = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if is_yaml && !hidden && path.is_file() {
+            fragments.push(path);
+        }
+    }
+    fragments.sort();
+    if fragments.is_empty() {
+        return Err(anyhow!(
+            "Config directory {} has no *.yml or *.yaml files",
+            dir.display()
+        ));
+    }
+
+    let mut root = Value::Mapping(Mapping::new());
+    let mut files = Vec::new();
+    for fragment in fragments {
+        let value = read_yaml(&fragment)?;
+        let Value::Mapping(map) = &value else {
+            if value.is_null() {
+                continue;
+            }
+            return Err(anyhow!("Config {} must be a YAML map", fragment.display()));
+        };
+        let mut rest = map.clone();
+        rest.remove("applications");
+        rest.remove("include");
+        merge_values(&mut root, Value::Mapping(rest));
+
+        files.push((fragment.clone(), value.clone()));
+        collect_includes(
+            &fragment,
+            &value,
+            &mut vec![canonical(&fragment)],
+            &mut files,
+        )?;
+    }
+    merge_included_applications(dir, &mut root, files)?;
+    Ok(root)
+}
+
+/// Recursively read the files listed in `include`, relative to the including file.
+/// `stack` holds the chain of files currently being read, to detect cycles.
+fn collect_includes(
+    path: &Path,
+    value: &Value,
+    stack: &mut Vec<PathBuf>,
+    included: &mut Vec<(PathBuf, Value)>,
+) -> Result<()> {
+    let includes: Vec<String> = match value.get("include") {
+        Some(include) => serde_yaml::from_value(include.clone())
+            .with_context(|| format!("`include` in {} must be a list of paths", path.display()))?,
+        None => return Ok(()),
+    };
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    for include in includes {
+        let include_path = base_dir.join(&include);
+        let key = canonical(&include_path);
+        if stack.contains(&key) {
+            return Err(anyhow!(
+                "Include cycle: {} includes {}",
+                path.display(),
+                include_path.display()
+            ));
+        }
+        if included.iter().any(|(p, _)| canonical(p) == key) {
+            return Err(anyhow!(
+                "{} is included more than once",
+                include_path.display()
+            ));
+        }
+
+        let include_value = read_yaml(&include_path)?;
+        included.push((include_path.clone(), include_value.clone()));
+        stack.push(key);
+        collect_includes(&include_path, &include_value, stack, included)?;
+        stack.pop();
+    }
+    Ok(())
+}
+
+/// Append the `applications` of each included file to the root config, rejecting
+/// application names defined in more than one file.
+fn merge_included_applications(
+    root_path: &Path,
+    root: &mut Value,
+    included: Vec<(PathBuf, Value)>,
+) -> Result<()> {
+    let mut sources: HashMap<String, PathBuf> = HashMap::new();
+    let mut merged = Vec::new();
+
+    let root_apps = root
+        .get("applications")
+        .and_then(Value::as_sequence)
+        .cloned()
+        .unwrap_or_default();
+    let included_apps = included.into_iter().map(|(path, mut value)| {
+        let apps = value
+            .get_mut("applications")
+            .and_then(Value::as_sequence_mut)
+            .map(std::mem::take)
+            .unwrap_or_default();
+        (path, apps)
+    });
+
+    for (path, apps) in std::iter::once((root_path.to_path_buf(), root_apps)).chain(included_apps) {
+        for app in apps {
+            if let Some(name) = app.get("name").and_then(Value::as_str) {
+                if let Some(first) = sources.insert(name.to_string(), path.clone()) {
+                    return Err(anyhow!(
+                        "Application '{}' is defined in both {} and {}",
+                        name,
+                        first.display(),
+                        path.display()
+                    ));
+                }
+            }
+            merged.push(app);
+        }
+    }
+
+    match root {
+        Value::Mapping(map) => {
+            map.insert("applications".into(), Value::Sequence(merged));
+            Ok(())
+        }
+        _ => Err(anyhow!("Config {} must be a YAML map", root_path.display())),
+    }
+}
+
+/// Fill in each application's `fetcher` and `installer` from the top-level
+/// `defaults` block, which is then dropped. Values the application sets win; a
+/// fetcher of another `type` than the default one inherits nothing, since the
+/// default fields wouldn't apply to it.
+fn apply_defaults(config: &mut Value) -> Result<()> {
+    let Some(defaults) = config.as_mapping_mut().and_then(|m| m.remove("defaults")) else {
+        return Ok(());
+    };
+    if defaults.is_null() {
+        return Ok(());
+    }
+    if !defaults.is_mapping() {
+        return Err(anyhow!("`defaults` must be a map"));
+    }
+    let fetcher = defaults.get("fetcher").filter(|f| !f.is_null());
+    if fetcher.is_some_and(|f| !f.is_mapping()) {
+        return Err(anyhow!("`defaults.fetcher` must be a map"));
+    }
+    let installer = defaults.get("installer").filter(|i| !i.is_null());
+
+    let Some(applications) = config
+        .get_mut("applications")
+        .and_then(Value::as_sequence_mut)
+    else {
+        return Ok(());
+    };
+    for app in applications.iter_mut().filter_map(Value::as_mapping_mut) {
+        if let Some(default_fetcher) = fetcher {
+            let default_type = default_fetcher.get("type");
+            match app.get_mut("fetcher") {
+                None | Some(Value::Null) => {
+                    app.insert("fetcher".into(), default_fetcher.clone());
+                }
+                Some(own) => {
+                    let own_type = own.get("type");
+                    if own_type.is_none() || own_type == default_type {
+                        let mut merged = default_fetcher.clone();
+                        merge_values(&mut merged, own.clone());
+                        *own = merged;
+                    }
+                }
+            }
+        }
+        if let Some(default_installer) = installer {
+            if app.get("installer").is_none_or(Value::is_null) {
+                app.insert("installer".into(), default_installer.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Merge the overrides of profile `name` into the matching entries of `applications`.
+fn apply_profile(config: &mut Value, name: &str) -> Result<()> {
+    let profile = config
+        .get("profiles")
+        .and_then(|profiles| profiles.get(name))
+        .cloned()
+        .ok_or_else(|| anyhow!("Unknown profile: {}", name))?;
+
+    let overrides = match profile.get("applications") {
+        Some(Value::Mapping(overrides)) => overrides.clone(),
+        Some(Value::Null) | None => Mapping::new(),
+        Some(_) => {
+            return Err(anyhow!(
+                "Profile '{}': `applications` must be a map of application name to overrides",
+                name
+            ))
+        }
+    };
+
+    let applications = config
+        .get_mut("applications")
+        .and_then(Value::as_sequence_mut)
+        .ok_or_else(|| anyhow!("Config is missing the `applications` list"))?;
+
+    for (app_name, app_override) in overrides {
+        let app = applications
+            .iter_mut()
+            .find(|app| app.get("name") == Some(&app_name))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Profile '{}' overrides unknown application {:?}",
+                    name,
+                    app_name.as_str().unwrap_or_default()
+                )
+            })?;
+        merge_values(app, app_override);
+    }
+
+    Ok(())
+}
+
+/// Apply one `path=value` override to the raw config, returning the path's
+/// segments. The value is parsed as YAML, so `true` and `1` are a bool and a number.
+fn apply_override(config: &mut Value, spec: &str) -> Result<Vec<String>> {
+    let (path, raw) = spec
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid --set {} (expected path=value)", spec))?;
+    let new_value: Value =
+        serde_yaml::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+    let segments: Vec<String> = path.split('.').map(str::to_string).collect();
+
+    match override_target(config, &segments, true)? {
+        Some(target) => *target = new_value,
+        None => return Err(anyhow!("Unknown config path in --set {}", spec)),
+    }
+    Ok(segments)
+}
+
+/// An override path as the deserializer names it: `app.<name>.` becomes
+/// `applications.<index>.`.
+fn schema_path(config: &Value, segments: &[String]) -> String {
+    if let [root, name, fields @ ..] = segments {
+        if matches!(root.as_str(), "app" | "apps" | "applications") {
+            let index = config
+                .get("applications")
+                .and_then(Value::as_sequence)
+                .and_then(|apps| {
+                    apps.iter()
+                        .position(|a| a.get("name").and_then(Value::as_str) == Some(name.as_str()))
+                });
+            if let Some(index) = index {
+                return std::iter::once(format!("applications.{}", index))
+                    .chain(fields.iter().cloned())
+                    .collect::<Vec<_>>()
+                    .join(".");
+            }
+        }
+    }
+    segments.join(".")
+}
+
+/// The value at an override path: `settings.<field>...`, `notifications.<field>`,
+/// `http.<field>` or `app.<name>.<field>...`.
+/// With `create`, missing mappings along the way are added.
+fn override_target<'a>(
+    config: &'a mut Value,
+    segments: &[String],
+    create: bool,
+) -> Result<Option<&'a mut Value>> {
+    let path = segments.join(".");
+    let (mut current, fields) = match segments {
+        [root, fields @ ..] if matches!(root.as_str(), "settings" | "notifications" | "http") => {
+            let Some(config) = config.as_mapping_mut() else {
+                return Ok(None);
+            };
+            if create && !config.contains_key(root.as_str()) {
+                config.insert(root.as_str().into(), Value::Mapping(Mapping::new()));
+            }
+            match config.get_mut(root.as_str()) {
+                Some(settings) => (settings, fields),
+                None => return Ok(None),
+            }
+        }
+        [root, name, fields @ ..] if matches!(root.as_str(), "app" | "apps" | "applications") => {
+            let app = config
+                .get_mut("applications")
+                .and_then(Value::as_sequence_mut)
+                .and_then(|apps| {
+                    apps.iter_mut()
+                        .find(|a| a.get("name").and_then(Value::as_str) == Some(name.as_str()))
+                });
+            match app {
+                Some(app) => (app, fields),
+                None => return Err(anyhow!("--set {}: no application named '{}'", path, name)),
+            }
+        }
+        _ => {
+            return Err(anyhow!(
+            "--set {}: paths start with `settings.`, `notifications.`, `http.` or `app.<name>.`",
+            path
+        ))
+        }
+    };
+    if fields.is_empty() {
+        return Err(anyhow!("--set {}: missing field name", path));
+    }
+
+    for (i, field) in fields.iter().enumerate() {
+        let Some(mapping) = current.as_mapping_mut() else {
+            return Ok(None);
+        };
+        if create && !mapping.contains_key(field.as_str()) {
+            mapping.insert(field.as_str().into(), Value::Null);
+        }
+        current = match mapping.get_mut(field.as_str()) {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        if create && current.is_null() && i + 1 < fields.len() {
+            *current = Value::Mapping(Mapping::new());
+        }
+    }
+    Ok(Some(current))
+}
+
+/// Placeholders the fetchers fill in with host values (see [`crate::host::substitute`]);
+/// never read from the environment.
+const HOST_PLACEHOLDERS: &[&str] = &["codename", "arch", "os_family", "pkg_ext"];
+
+/// Fields whose values are shell commands or regexes, where `$` already means
+/// something; these are left as written.
+const UNEXPANDED_FIELDS: &[&str] = &[
+    "pre_install",
+    "post_install",
+    "pre_install_scan",
+    "build_command",
+    "tag_pattern",
+    "regex",
+    "asset_version_regex",
+    "version_regex",
+];
+
+/// Expand `${VAR}` and `$VAR` in the string values of `settings` and
+/// `applications` from the process environment. A variable that isn't set is an
+/// error naming it and the field it is used in.
+fn expand_env_vars(config: &mut Value) -> Result<()> {
+    for key in ["settings", "notifications", "http", "applications"] {
+        if let Some(value) = config.get_mut(key) {
+            expand_value(value, key)?;
+        }
+    }
+    Ok(())
+}
+
+fn expand_value(value: &mut Value, path: &str) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            *s = expand_env(s).with_context(|| format!("In {}", path))?;
+        }
+        Value::Sequence(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                expand_value(item, &format!("{}[{}]", path, i))?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, item) in map.iter_mut() {
+                let Some(key) = key.as_str() else { continue };
+                if UNEXPANDED_FIELDS.contains(&key) {
+                    continue;
+                }
+                expand_value(item, &format!("{}.{}", path, key))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expand environment variables in `s`. `$$` is a literal `$`, and a `$` not
+/// followed by a variable name is kept as is.
+fn expand_env(s: &str) -> Result<String> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, braced, len) = if let Some(inner) = after.strip_prefix('{') {
+            match inner.find('}') {
+                Some(end) => (&inner[..end], true, end + 2),
+                None => return Err(anyhow!("Unterminated ${{ in '{}'", s)),
+            }
+        } else if let Some(after) = after.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+            continue;
+        } else {
+            let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+            (&after[..end], false, end)
+        };
+
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(is_name_char);
+        if braced && !valid {
+            return Err(anyhow!("Invalid variable name '${{{}}}' in '{}'", name, s));
+        }
+        if !valid || (braced && HOST_PLACEHOLDERS.contains(&name)) {
+            // Not a variable (e.g. a trailing `$`) or a host placeholder: keep as is
+            out.push('$');
+            rest = after;
+            continue;
+        }
+        let value =
+            std::env::var(name).map_err(|_| anyhow!("Environment variable {} is not set", name))?;
+        out.push_str(&value);
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Recursively merge `overlay` into `base`; mappings are merged key by key and
+/// any other value replaces the base value.
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
 }
 
 /// Application configuration entry.
@@ -27,6 +908,198 @@ pub struct ApplicationConfig {
     /// Optional flag to pin this application (no update checks)
     #[serde(default)]
     pub pinned: Option<bool>,
+
+    /// Optional semver requirement (e.g. `=1.4.3`, `~2.1`) the versions this
+    /// application is updated to must satisfy
+    #[serde(default)]
+    pub version: Option<String>,
+
+    /// Optional debsig-verify signature check for deb packages; packages that
+    /// don't verify against the trusted keyrings are refused
+    #[serde(default)]
+    pub debsig: Option<DebsigConfig>,
+
+    /// Optional flag to let the deb installer reinstall a package at the
+    /// installed version, or downgrade it (defaults to false)
+    #[serde(default)]
+    pub allow_reinstall: Option<bool>,
+
+    /// Optional flag to verify a downloaded package's architecture matches the host (defaults to true)
+    #[serde(default)]
+    pub check_architecture: Option<bool>,
+
+    /// Optional command run on each downloaded file before install; `{file}` is
+    /// replaced with its path and a non-zero exit rejects (and deletes) the file
+    #[serde(default)]
+    pub pre_install_scan: Option<String>,
+
+    /// Optional shell command(s) run right before installing an update; a
+    /// non-zero exit aborts the install of this application
+    #[serde(
+        default,
+        deserialize_with = "deserialize_string_or_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub pre_install: Vec<String>,
+
+    /// Optional shell command(s) run right after an update was installed (e.g.
+    /// restarting a service); a non-zero exit is logged as an error
+    #[serde(
+        default,
+        deserialize_with = "deserialize_string_or_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub post_install: Vec<String>,
+
+    /// Optional flag to pipe the download straight into the installer instead of
+    /// saving it to a temporary file first, when the fetcher and installer
+    /// support it (defaults to false)
+    #[serde(default)]
+    pub stream: Option<bool>,
+
+    /// Optional minimum available memory (e.g. "256M") required to install; the
+    /// install is deferred to a later run otherwise
+    #[serde(default)]
+    pub min_free_mem: Option<String>,
+
+    /// Optional maximum one-minute load average allowed to install; the install
+    /// is deferred to a later run otherwise
+    #[serde(default)]
+    pub max_load: Option<f64>,
+
+    /// Optional user to run the installer and hooks as, with that user's login
+    /// environment, when autopkg runs as root (for user-scoped installers such as cargo)
+    #[serde(default)]
+    pub run_as: Option<String>,
+
+    /// Optional file to read the installed version from instead of asking the
+    /// installer (e.g. `/opt/app/VERSION`), optionally with a regex
+    #[serde(default)]
+    pub version_file: Option<VersionFile>,
+
+    /// Optional flag marking updates of this application as needing a reboot
+    /// (e.g. kernel, glibc, systemd)
+    #[serde(default)]
+    pub reboot_required: Option<bool>,
+
+    /// Optional group (e.g. "security") for running related applications
+    /// together with `run --group`
+    #[serde(default)]
+    pub group: Option<String>,
+
+    /// Optional tags for selecting applications with `run --tag`
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+
+    /// Optional processing priority; higher values are processed first (defaults to 0)
+    #[serde(default)]
+    pub priority: Option<i32>,
+
+    /// Optional systemd `OnCalendar` schedule (e.g. "hourly") for a dedicated timer
+    #[serde(default)]
+    pub schedule: Option<String>,
+
+    /// Optional minimum time between update checks (e.g. "24h"); `run` skips the
+    /// application if it was checked more recently (defaults to every run)
+    #[serde(default)]
+    pub check_interval: Option<String>,
+
+    /// Optional largest version bump installed automatically; bigger updates are
+    /// only reported as needing approval (defaults to any bump)
+    #[serde(default)]
+    pub auto_update_level: Option<UpdateLevel>,
+
+    /// Optional re-check of the installed version after an install, and what to
+    /// do if it still reports the old version
+    #[serde(default)]
+    pub post_install_mismatch: Option<PostInstallMismatch>,
+
+    /// After an install, fail unless the installed version is the version the
+    /// fetcher resolved
+    #[serde(default)]
+    pub verify_installed_version: Option<bool>,
+
+    /// When `verify_installed_version` fails, reinstall the previous version
+    /// (installers that support it)
+    #[serde(default)]
+    pub rollback_on_mismatch: Option<bool>,
+
+    /// Save the installed deb package before an upgrade and reinstall it if the
+    /// upgrade fails (defaults to false)
+    #[serde(default)]
+    pub rollback_on_failure: Option<bool>,
+}
+
+/// Where to read an application's installed version from.
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionFile {
+    pub path: String,
+
+    /// Regex applied to the file's contents; the version is its `version` named
+    /// group, else its first group, else the whole match (defaults to the trimmed contents)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+}
+
+/// `version_file` may be just a path or a map with `path` and `regex`.
+#[derive(Deserialize)]
+struct VersionFileFields {
+    path: String,
+    #[serde(default)]
+    regex: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for VersionFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = string_or_map(deserializer, |path| VersionFileFields { path, regex: None })?;
+        Ok(VersionFile {
+            path: fields.path,
+            regex: fields.regex,
+        })
+    }
+}
+
+/// Size of a version bump, by the first semver component that changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateLevel {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl UpdateLevel {
+    pub fn label(self) -> &'static str {
+        match self {
+            UpdateLevel::Patch => "patch",
+            UpdateLevel::Minor => "minor",
+            UpdateLevel::Major => "major",
+        }
+    }
+}
+
+/// What to do when the installed version is unchanged after a successful install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PostInstallMismatch {
+    /// Install once more, then fail if the version is still unchanged
+    Retry,
+    /// Fail the application
+    Error,
+    /// Log a warning and carry on
+    Warn,
+}
+
+/// Signature verification of deb packages with `debsig-verify`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DebsigConfig {
+    /// Keyrings directory (defaults to debsig-verify's `/usr/share/debsig/keyrings`)
+    #[serde(default)]
+    pub keyrings_dir: Option<String>,
+
+    /// Policies directory (defaults to debsig-verify's `/etc/debsig/policies`)
+    #[serde(default)]
+    pub policies_dir: Option<String>,
 }
 
 /// Configuration for different fetchers.
@@ -38,9 +1111,148 @@ pub struct FetcherConfig {
     #[serde(default)]
     pub repo: Option<String>,
 
-    /// File pattern (glob) to match assets
+    /// File pattern (glob) to match assets; a list is tried in order until one matches
+    #[serde(
+        default,
+        deserialize_with = "deserialize_string_or_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub file_pattern: Vec<String>,
+
+    /// Ranked asset preferences, for GitHub fetcher; used instead of `file_pattern`
+    /// when set. Each entry may name the installer for the assets it selects.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefer: Vec<Preference>,
+
+    /// Instance URL, for GitLab fetcher (defaults to https://gitlab.com)
+    #[serde(default)]
+    pub base_url: Option<String>,
+
+    /// Download URL template, for GitHub fetcher: `{version}` and `{tag}` are
+    /// replaced from the latest release and the file is downloaded from there
+    /// instead of from a release asset
+    #[serde(default)]
+    pub download_url: Option<String>,
+
+    /// Release channel, for GitHub fetcher: "stable" (default) or "prerelease"
+    /// to also consider pre-releases
+    #[serde(default)]
+    pub channel: Option<String>,
+
+    /// Release tag to install, for GitHub fetcher: the release with this exact
+    /// tag is used instead of the latest one
+    #[serde(default)]
+    pub tag: Option<String>,
+
+    /// Release asset (glob) holding SHA256 checksums, for GitHub fetcher; the
+    /// downloaded file is verified against its entry
+    #[serde(default)]
+    pub checksum_pattern: Option<String>,
+
+    /// Release asset (glob) holding the detached GPG signature (`.asc`/`.sig`)
+    /// of the downloaded file, for GitHub fetcher; requires `gpg_key`
+    #[serde(default)]
+    pub signature_pattern: Option<String>,
+
+    /// Armored public key file that `signature_pattern` signatures must verify
+    /// against, for GitHub fetcher
+    #[serde(default)]
+    pub gpg_key: Option<String>,
+
+    /// Asset field matched by `file_pattern`: "name" (default) or "label", for GitHub fetcher
+    #[serde(default)]
+    pub match_field: Option<String>,
+
+    /// Where a release's version comes from: "tag" (default) or "asset" for the
+    /// selected asset's file name, for GitHub fetcher
+    #[serde(default)]
+    pub version_from: Option<String>,
+
+    /// Regex with a named `version` group applied to the asset name with
+    /// `version_from: asset` (defaults to the first dotted number)
+    #[serde(default)]
+    pub asset_version_regex: Option<String>,
+
+    /// Literal prefix stripped from release tags before version comparison (e.g. "release/")
+    #[serde(default)]
+    pub version_prefix: Option<String>,
+
+    /// Literal suffix stripped from release tags before version comparison (e.g. "_linux")
+    #[serde(default)]
+    pub version_suffix: Option<String>,
+
+    /// Workflow file name or ID, for GitHub Actions fetcher
+    #[serde(default)]
+    pub workflow: Option<String>,
+
+    /// Only consider workflow runs on this branch, for GitHub Actions fetcher
+    #[serde(default)]
+    pub branch: Option<String>,
+
+    /// API token, for GitHub fetchers; overrides `token_env` and `settings.github_token`
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+
+    /// Environment variable holding the API token, for GitHub fetchers
+    #[serde(default)]
+    pub token_env: Option<String>,
+
+    /// Repository URL, for git fetcher; file URL, for url fetcher
+    #[serde(default)]
+    pub url: Option<String>,
+
+    /// URL returning the latest version as plain text, for url fetcher
+    #[serde(default)]
+    pub version_url: Option<String>,
+
+    /// URL returning the version to install as plain text, for apt fetcher
+    /// (defaults to apt's candidate version)
+    #[serde(default)]
+    pub stable_version_url: Option<String>,
+
+    /// Request timeout (e.g. "30s", "2m"), for url fetcher; defaults to `http.timeout`
     #[serde(default)]
-    pub file_pattern: Option<String>,
+    pub timeout: Option<String>,
+
+    /// Times a request failing with a connection error, timeout, 5xx or 429
+    /// is retried, for HTTP fetchers; defaults to 3
+    #[serde(default)]
+    pub retries: Option<u32>,
+
+    /// Delay before the first retry, doubled for each further one, in
+    /// milliseconds; defaults to 500
+    #[serde(default)]
+    pub retry_base_delay_ms: Option<u64>,
+
+    /// PEM file with extra root certificates to trust (e.g. of an intercepting
+    /// proxy or a private CA), for HTTP fetchers
+    #[serde(default)]
+    pub ca_cert: Option<String>,
+
+    /// Tag pattern (glob) to select release tags, for git fetcher
+    #[serde(default)]
+    pub tag_pattern: Option<String>,
+
+    /// Shell command that builds the checked-out tag, for git fetcher
+    #[serde(default)]
+    pub build_command: Option<String>,
+
+    /// Path (glob, relative to the checkout) of the built artifact, for git fetcher;
+    /// name (glob) of the file inside the downloaded artifact, for GitHub Actions fetcher
+    #[serde(default)]
+    pub artifact: Option<String>,
+
+    /// Require a valid GPG signature on the selected tag (`git verify-tag`), for git fetcher
+    #[serde(default)]
+    pub verify_tag: Option<bool>,
+
+    /// GnuPG home directory holding the trusted keyring used for tag verification
+    #[serde(default)]
+    pub gpg_home: Option<String>,
+
+    /// Key fingerprints allowed to sign tags; if unset, any key trusted by the keyring is accepted
+    #[serde(default)]
+    pub trusted_signers: Option<Vec<String>>,
 }
 
 /// Installer configuration.
@@ -60,23 +1272,196 @@ pub struct FetcherConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InstallerConfig {
     pub r#type: String,
+
+    /// How the deb installer installs packages (defaults to `dpkg`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_method: Option<DebInstallMethod>,
+
+    /// Executable to take from the download, for the binary installer, or
+    /// name of the symlink to the AppImage, for the appimage installer
+    /// (defaults to the application name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_name: Option<String>,
+
+    /// Where the binary installer puts the executable: a file path, or a
+    /// directory to put `binary_name` in (defaults to `/usr/local/bin`); the
+    /// directory the appimage installer puts AppImages in (defaults to
+    /// `~/.local/bin`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_path: Option<String>,
+
+    /// Regex finding the version in `<install_path> --version` output, for the
+    /// binary installer, or in `--appimage-version` output and file names, for
+    /// the appimage installer (defaults to the first dotted version number)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_regex: Option<String>,
+
+    /// Desktop entry to write for the AppImage, for the appimage installer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desktop_entry: Option<DesktopEntry>,
+}
+
+impl InstallerConfig {
+    /// Installer of `type` with no further options.
+    pub fn of_type(r#type: impl Into<String>) -> Self {
+        Self {
+            r#type: r#type.into(),
+            install_method: None,
+            binary_name: None,
+            install_path: None,
+            version_regex: None,
+            desktop_entry: None,
+        }
+    }
 }
 
-/// Helper enum used for custom deserialization to support shorthand installer syntax.
+/// `.desktop` file the appimage installer writes to
+/// `~/.local/share/applications`, so the AppImage shows up in menus.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
-enum InstallerConfigIntermediate {
-    String(String),
-    Full { r#type: String },
+pub struct DesktopEntry {
+    /// Name shown in menus
+    pub name: String,
+
+    /// Icon theme name or path to an icon file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
 }
 
+/// Command the deb installer installs packages with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DebInstallMethod {
+    /// `dpkg -i`, which fails on missing dependencies
+    Dpkg,
+    /// `apt-get install -y ./<file>`, which installs missing dependencies
+    Apt,
+}
+
+/// Supports the shorthand installer syntax (`installer: deb`).
 fn deserialize_installer_config<'de, D>(deserializer: D) -> Result<InstallerConfig, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let intermediate = InstallerConfigIntermediate::deserialize(deserializer)?;
-    match intermediate {
-        InstallerConfigIntermediate::String(s) => Ok(InstallerConfig { r#type: s }),
-        InstallerConfigIntermediate::Full { r#type } => Ok(InstallerConfig { r#type }),
+    string_or_map(deserializer, InstallerConfig::of_type)
+}
+
+/// Deserialize a section that may be written as a plain string (turned into
+/// `T` by `from_string`) or as a map of `T`'s fields. Unlike an untagged enum,
+/// the map is read in place, so its unknown fields are still seen by the
+/// `--set` check in [`Config::load_with_overrides`].
+fn string_or_map<'de, D, T>(deserializer: D, from_string: fn(String) -> T) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct StringOrMap<T>(fn(String) -> T);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for StringOrMap<T> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a string or a map")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+            Ok((self.0)(value.to_string()))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<T, A::Error> {
+            T::deserialize(de::value::MapAccessDeserializer::new(map))
+        }
+    }
+
+    deserializer.deserialize_any(StringOrMap(from_string))
+}
+
+/// One entry of a fetcher's `prefer` list.
+#[derive(Debug, Clone, Serialize)]
+pub struct Preference {
+    /// Glob pattern matched against assets
+    pub pattern: String,
+
+    /// Installer type for assets selected by this entry (defaults to the application's)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installer: Option<String>,
+}
+
+/// A `prefer` entry may be just a pattern or a map with `pattern` and `installer`.
+#[derive(Deserialize)]
+struct PreferenceFields {
+    pattern: String,
+    #[serde(default)]
+    installer: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for Preference {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = string_or_map(deserializer, |pattern| PreferenceFields {
+            pattern,
+            installer: None,
+        })?;
+        Ok(Preference {
+            pattern: fields.pattern,
+            installer: fields.installer,
+        })
+    }
+}
+
+/// A single string or a list, such as `file_pattern` (an ordered list of
+/// fallbacks) or the install hooks.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrList {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn deserialize_string_or_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match StringOrList::deserialize(deserializer)? {
+        StringOrList::One(s) => Ok(vec![s]),
+        StringOrList::Many(v) => Ok(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regexes_are_not_env_expanded() {
+        let path = std::env::temp_dir().join(format!(
+            "autopkg-config-test-regex-{}.yaml",
+            std::process::id()
+        ));
+        fs::write(
+            &path,
+            r#"applications:
+  - name: tool
+    fetcher:
+      type: github
+      repo: owner/tool
+      version_from: asset
+      asset_version_regex: '^tool-(?P<version>[0-9.]+)$|^${AUTOPKG_TEST_UNSET}'
+    installer:
+      type: binary
+      version_regex: 'tool (?P<version>\S+)$AUTOPKG_TEST_UNSET'
+"#,
+        )
+        .unwrap();
+        let config = Config::load(&path, None).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let app = &config.applications[0];
+        assert_eq!(
+            app.fetcher.asset_version_regex.as_deref(),
+            Some("^tool-(?P<version>[0-9.]+)$|^${AUTOPKG_TEST_UNSET}")
+        );
+        assert_eq!(
+            app.installer.version_regex.as_deref(),
+            Some("tool (?P<version>\\S+)$AUTOPKG_TEST_UNSET")
+        );
     }
 }
@@ -0,0 +1,116 @@
+use crate::cache;
+use crate::config::ConfigRepo;
+
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::info;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use which::which;
+
+/// Clone or update the config repository in the private cache directory and
+/// return the path of the config file in it.
+///
+/// A failed update is an error: a stale checkout is never used, since the
+/// config it holds may no longer be the one the repository publishes.
+pub fn sync(repo: &ConfigRepo) -> Result<PathBuf> {
+    if which("git").is_err() {
+        return Err(anyhow!(
+            "git not found in PATH; required by settings.config_repo"
+        ));
+    }
+
+    let checkout_dir = cache::dir("config-repo")?;
+    let auth = auth_header(repo)?;
+
+    if checkout_dir.join(".git").exists() && origin_url(&checkout_dir).as_deref() == Some(&repo.url)
+    {
+        info!(
+            "ConfigRepo: updating {} from {}",
+            checkout_dir.display(),
+            repo.url
+        );
+        let branch = repo.branch.as_deref().unwrap_or("HEAD");
+        git(
+            &checkout_dir,
+            &["fetch", "--depth", "1", "origin", branch],
+            &auth,
+        )
+        .and_then(|_| git(&checkout_dir, &["reset", "--hard", "FETCH_HEAD"], &auth))
+        .with_context(|| format!("Failed to update config repository {}", repo.url))?;
+    } else {
+        if checkout_dir.exists() {
+            std::fs::remove_dir_all(&checkout_dir)
+                .with_context(|| format!("Failed to remove {}", checkout_dir.display()))?;
+        }
+        info!(
+            "ConfigRepo: cloning {} into {}",
+            repo.url,
+            checkout_dir.display()
+        );
+        let mut args = vec!["clone", "--depth", "1"];
+        if let Some(branch) = &repo.branch {
+            args.extend(["--branch", branch]);
+        }
+        let dir = checkout_dir.display().to_string();
+        args.extend([repo.url.as_str(), dir.as_str()]);
+        git(Path::new("."), &args, &auth)?;
+    }
+
+    let config_path = checkout_dir.join(repo.path.as_deref().unwrap_or("autopkg.yml"));
+    if !config_path.exists() {
+        return Err(anyhow!(
+            "Config file {} not found in {}",
+            config_path.display(),
+            repo.url
+        ));
+    }
+    Ok(config_path)
+}
+
+/// `Authorization` header for https remotes, if `token_env` is set.
+fn auth_header(repo: &ConfigRepo) -> Result<Option<String>> {
+    let Some(var) = &repo.token_env else {
+        return Ok(None);
+    };
+    let token = std::env::var(var).map_err(|_| {
+        anyhow!(
+            "Environment variable {} (config_repo.token_env) is not set",
+            var
+        )
+    })?;
+    let credentials = STANDARD.encode(format!("x-access-token:{}", token));
+    Ok(Some(format!("Authorization: Basic {}", credentials)))
+}
+
+fn origin_url(dir: &Path) -> Option<String> {
+    git(dir, &["remote", "get-url", "origin"], &None)
+        .ok()
+        .map(|url| url.trim().to_string())
+}
+
+/// Run git in `dir`. The auth header is passed through the environment so it
+/// never shows up in the process list or the checkout's `.git/config`.
+fn git(dir: &Path, args: &[&str], auth: &Option<String>) -> Result<String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir).args(args);
+    if let Some(header) = auth {
+        cmd.env("GIT_CONFIG_COUNT", "1")
+            .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+            .env("GIT_CONFIG_VALUE_0", header);
+    }
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run git {}", args[0]))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed with status {}: {}",
+            args[0],
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
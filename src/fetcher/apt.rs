@@ -0,0 +1,170 @@
+use crate::config::{ApplicationConfig, FetcherConfig};
+use crate::fetcher::{Fetcher, HttpClient, RetryPolicy};
+use crate::host;
+use crate::types::{FetchResult, Resolved};
+use crate::version::{Scheme, Version, VersionRequirement};
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use reqwest::blocking::Client;
+use std::process::Command;
+use which::which;
+
+/// Fetcher that reports the version of a package to install from the host's
+/// apt repositories: the one published at `stable_version_url`, else apt's
+/// candidate version (`apt-cache policy`).
+///
+/// It does not download anything; it is meant to be paired with the `apt`
+/// installer, which installs that exact version.
+pub struct AptFetcher {
+    package_name: String,
+    stable_version_url: Option<String>,
+    /// The application's `version` requirement the version must satisfy
+    requirement: Option<VersionRequirement>,
+    client: Client,
+    retry: RetryPolicy,
+}
+
+impl AptFetcher {
+    pub fn new(config: &FetcherConfig, app: &ApplicationConfig, http: &HttpClient) -> Result<Self> {
+        Ok(Self {
+            package_name: app.effective_package_name().to_string(),
+            stable_version_url: config
+                .stable_version_url
+                .as_deref()
+                .map(host::substitute)
+                .transpose()?,
+            requirement: VersionRequirement::from_app(app)?,
+            client: http.for_fetcher(config, None)?,
+            retry: RetryPolicy::from_config(config),
+        })
+    }
+
+    /// The version published at `url`: its first non-empty line, as apt names
+    /// it (e.g. `1.24.0-2ubuntu7`).
+    fn stable_version(&self, url: &str) -> Result<String> {
+        info!("AptFetcher: querying {}", url);
+        let resp = self.retry.send(self.client.get(url))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Stable version endpoint {} returned status {}",
+                url,
+                resp.status()
+            ));
+        }
+        resp.text()?
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Stable version endpoint {} returned no version", url))
+    }
+
+    /// apt's candidate version of the package; `None` if no repository has it.
+    fn candidate_version(&self) -> Result<Option<String>> {
+        if which("apt-cache").is_err() {
+            return Err(anyhow!(
+                "apt-cache not found in PATH; required by the apt fetcher without stable_version_url"
+            ));
+        }
+        info!("AptFetcher: running apt-cache policy {}", self.package_name);
+        let output = Command::new("apt-cache")
+            .arg("policy")
+            .arg(&self.package_name)
+            .output()
+            .context("Failed to run apt-cache policy")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "apt-cache policy {} failed with status {}: {}",
+                self.package_name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(parse_candidate(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// The `Candidate:` version of `apt-cache policy` output, unless it is `(none)`.
+fn parse_candidate(policy: &str) -> Option<String> {
+    policy
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Candidate:"))
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && *v != "(none)")
+        .map(str::to_string)
+}
+
+impl Fetcher for AptFetcher {
+    fn fetch_if_newer(&self, _current_version: &Version) -> FetchResult {
+        Err(anyhow!(
+            "apt fetcher only reports versions; pair it with `installer: apt`"
+        ))
+    }
+
+    fn resolve_latest(&self) -> Result<Option<Resolved>> {
+        let version = match &self.stable_version_url {
+            Some(url) => self.stable_version(url)?,
+            None => match self.candidate_version()? {
+                Some(version) => version,
+                None => {
+                    warn!("AptFetcher: no apt repository offers {}", self.package_name);
+                    return Ok(None);
+                }
+            },
+        };
+        if let Some(requirement) = &self.requirement {
+            if !requirement.matches(&Version::new(version.as_str(), Scheme::Debian).upstream_part())
+            {
+                warn!(
+                    "AptFetcher: version {} doesn't satisfy version {}",
+                    version, requirement
+                );
+                return Ok(None);
+            }
+        }
+        info!("AptFetcher: {} version {}", self.package_name, version);
+        Ok(Some(Resolved { version, url: None }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+
+    #[test]
+    fn reads_candidate_from_apt_cache_policy() {
+        let policy = "nginx:\n  Installed: 1.24.0-2ubuntu1\n  Candidate: 1.24.0-2ubuntu7\n  Version table:\n";
+        assert_eq!(parse_candidate(policy).as_deref(), Some("1.24.0-2ubuntu7"));
+        assert_eq!(
+            parse_candidate("tool:\n  Installed: (none)\n  Candidate: (none)\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn stable_version_url_decides_the_version() {
+        let mut server = Server::new();
+        let stable = server
+            .mock("GET", "/nginx-stable")
+            .with_body("\n1.24.0-2ubuntu7\n")
+            .create();
+        let app: ApplicationConfig = serde_yaml::from_str(&format!(
+            "name: nginx\nfetcher:\n  type: apt\n  stable_version_url: {}/nginx-stable\ninstaller: apt\n",
+            server.url()
+        ))
+        .unwrap();
+        let fetcher = AptFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap(),
+        )
+        .unwrap();
+
+        let resolved = fetcher.resolve_latest().unwrap().unwrap();
+        assert_eq!(resolved.version, "1.24.0-2ubuntu7");
+        assert_eq!(resolved.url, None);
+        stable.assert();
+    }
+}
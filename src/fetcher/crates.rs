@@ -0,0 +1,102 @@
+use crate::config::{ApplicationConfig, FetcherConfig};
+use crate::fetcher::{Fetcher, HttpClient, RetryPolicy};
+use crate::types::{FetchResult, Resolved};
+use crate::version::{compare, Version, VersionRequirement};
+
+use anyhow::{anyhow, Result};
+use log::info;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+/// crates.io API response subset.
+#[derive(Debug, Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateInfo,
+    #[serde(default)]
+    versions: Vec<CrateVersion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CrateInfo {
+    max_stable_version: Option<String>,
+    max_version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CrateVersion {
+    num: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+/// Fetcher that reports the latest version of a crate published on crates.io.
+///
+/// It does not download anything; it is meant to be paired with an installer
+/// that fetches by itself, such as the `cargo` installer.
+pub struct CratesFetcher {
+    crate_name: String,
+    /// The application's `version` requirement the version must satisfy
+    requirement: Option<VersionRequirement>,
+    client: Client,
+    retry: RetryPolicy,
+}
+
+impl CratesFetcher {
+    pub fn new(config: &FetcherConfig, app: &ApplicationConfig, http: &HttpClient) -> Result<Self> {
+        let client = http.for_fetcher(config, None)?;
+
+        Ok(Self {
+            crate_name: app.effective_package_name().to_string(),
+            requirement: VersionRequirement::from_app(app)?,
+            client,
+            retry: RetryPolicy::from_config(config),
+        })
+    }
+}
+
+impl Fetcher for CratesFetcher {
+    fn fetch_if_newer(&self, _current_version: &Version) -> FetchResult {
+        Err(anyhow!(
+            "crates fetcher only reports versions; pair it with an installer that fetches by itself (e.g. `installer: cargo`)"
+        ))
+    }
+
+    fn resolve_latest(&self) -> Result<Option<Resolved>> {
+        let url = format!("https://crates.io/api/v1/crates/{}", self.crate_name);
+        info!("CratesFetcher: querying {}", url);
+
+        let resp = self.retry.send(self.client.get(&url))?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(anyhow!(
+                "Crate '{}' not found on crates.io",
+                self.crate_name
+            ));
+        }
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "crates.io API returned non-success status: {}",
+                resp.status()
+            ));
+        }
+
+        let info: CrateResponse = resp.json()?;
+        if let Some(requirement) = &self.requirement {
+            // Newest published version satisfying it
+            let version = info
+                .versions
+                .into_iter()
+                .filter(|v| !v.yanked && requirement.matches(&v.num))
+                .map(|v| v.num)
+                .max_by(|a, b| compare(a, b));
+            return Ok(version.map(|version| Resolved { version, url: None }));
+        }
+        Ok(Some(Resolved {
+            version: info
+                .krate
+                .max_stable_version
+                .unwrap_or(info.krate.max_version),
+            url: None,
+        }))
+    }
+}
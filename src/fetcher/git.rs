@@ -0,0 +1,500 @@
+use crate::cache;
+use crate::config::{ApplicationConfig, FetcherConfig};
+use crate::fetcher::{file_name, Fetcher};
+use crate::types::{Download, FetchResult, Resolved};
+use crate::version::{is_newer, should_replace, TagFormat, Version, VersionRequirement};
+
+use anyhow::{anyhow, Context, Result};
+use glob::Pattern;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use which::which;
+
+/// Fetcher that builds an application from the newest matching tag of a git repository.
+pub struct GitFetcher {
+    url: String,
+    tag_pattern: Pattern,
+    /// The application's `version` requirement tags must satisfy
+    requirement: Option<VersionRequirement>,
+    build_command: String,
+    artifact: String,
+    checkout_dir: PathBuf,
+    tag_format: TagFormat,
+    verify_tag: bool,
+    gpg_home: Option<PathBuf>,
+    trusted_signers: Vec<String>,
+}
+
+impl GitFetcher {
+    pub fn new(config: &FetcherConfig, app: &ApplicationConfig) -> Result<Self> {
+        let url = config
+            .url
+            .clone()
+            .ok_or_else(|| anyhow!("Git fetcher requires `url` field"))?;
+        let build_command = config
+            .build_command
+            .clone()
+            .ok_or_else(|| anyhow!("Git fetcher requires `build_command` field"))?;
+        let artifact = config
+            .artifact
+            .clone()
+            .ok_or_else(|| anyhow!("Git fetcher requires `artifact` field"))?;
+
+        let pattern_str = config.tag_pattern.as_deref().unwrap_or("*");
+        let tag_pattern = Pattern::new(pattern_str)
+            .with_context(|| format!("Invalid glob pattern: {}", pattern_str))?;
+
+        let checkout_dir = cache::dir("git")?.join(&app.name);
+
+        let trusted_signers = config
+            .trusted_signers
+            .iter()
+            .flatten()
+            .map(|fp| normalize_fingerprint(fp))
+            .collect();
+
+        Ok(Self {
+            url,
+            tag_pattern,
+            requirement: VersionRequirement::from_app(app)?,
+            build_command,
+            artifact,
+            checkout_dir,
+            tag_format: TagFormat::from_config(config),
+            verify_tag: config.verify_tag.unwrap_or(false),
+            gpg_home: config.gpg_home.as_ref().map(PathBuf::from),
+            trusted_signers,
+        })
+    }
+
+    /// Run a git command inside the checkout directory.
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.checkout_dir)
+            .args(args)
+            .output()
+            .with_context(|| format!("Failed to run git {}", args.join(" ")))?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git {} failed with status {}: {}",
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Clone the repository on first use, otherwise fetch new tags. The clone
+    /// goes to a temporary directory next to the checkout that is renamed into
+    /// place once complete, so a failed clone never leaves a partial checkout.
+    fn sync(&self) -> Result<()> {
+        if which("git").is_err() {
+            return Err(anyhow!(
+                "git not found in PATH; required by the git fetcher"
+            ));
+        }
+
+        if self.checkout_dir.join(".git").exists() {
+            info!("GitFetcher: fetching tags for {}", self.url);
+            self.git(&["fetch", "--tags", "--force", "--prune", "origin"])?;
+            return Ok(());
+        }
+
+        info!(
+            "GitFetcher: cloning {} into {}",
+            self.url,
+            self.checkout_dir.display()
+        );
+        let parent = self.checkout_dir.parent().ok_or_else(|| {
+            anyhow!(
+                "Invalid checkout directory: {}",
+                self.checkout_dir.display()
+            )
+        })?;
+        std::fs::create_dir_all(parent).with_context(|| {
+            format!("Failed to create checkout directory: {}", parent.display())
+        })?;
+        let mut partial = self.checkout_dir.clone().into_os_string();
+        partial.push(format!(".partial-{}", std::process::id()));
+        let partial = PathBuf::from(partial);
+        let _ = std::fs::remove_dir_all(&partial);
+
+        let status = Command::new("git")
+            .arg("clone")
+            .arg("--no-checkout")
+            .arg(&self.url)
+            .arg(&partial)
+            .status()
+            .context("Failed to run git clone");
+        let cloned = match status {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(anyhow!(
+                "git clone {} failed with status {}",
+                self.url,
+                status
+            )),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = cloned {
+            let _ = std::fs::remove_dir_all(&partial);
+            return Err(e);
+        }
+
+        // A directory without `.git` can only be left over from an older autopkg
+        if self.checkout_dir.exists() {
+            std::fs::remove_dir_all(&self.checkout_dir)
+                .with_context(|| format!("Failed to remove {}", self.checkout_dir.display()))?;
+        }
+        std::fs::rename(&partial, &self.checkout_dir).with_context(|| {
+            format!(
+                "Failed to move the clone into {}",
+                self.checkout_dir.display()
+            )
+        })
+    }
+
+    /// Newest tag matching `tag_pattern`, using the shared version comparison.
+    fn latest_tag(&self) -> Result<Option<String>> {
+        let tags = self.git(&["tag", "--list"])?;
+        Ok(self.newest_tag(tags.lines().map(str::trim)))
+    }
+
+    /// Newest of `tags` matching `tag_pattern` (and the `version` requirement).
+    fn newest_tag<'a>(&self, tags: impl Iterator<Item = &'a str>) -> Option<String> {
+        let mut latest: Option<String> = None;
+        let allowed = |tag: &str| {
+            self.tag_pattern.matches(tag)
+                && self
+                    .requirement
+                    .as_ref()
+                    .is_none_or(|r| r.matches(&self.tag_format.version(tag)))
+        };
+        for tag in tags.filter(|t| allowed(t)) {
+            let newer = match &latest {
+                None => true,
+                Some(current) => is_newer(
+                    &self.tag_format.version(current),
+                    &self.tag_format.version(tag),
+                ),
+            };
+            if newer {
+                latest = Some(tag.to_string());
+            }
+        }
+        latest
+    }
+
+    /// Verify the tag's GPG signature with `git verify-tag --raw`, refusing
+    /// unsigned tags, untrusted keys, and signers whose primary key isn't
+    /// listed in `trusted_signers`.
+    fn verify_tag_signature(&self, tag: &str) -> Result<()> {
+        info!("GitFetcher: verifying signature of tag {}", tag);
+
+        let mut cmd = Command::new("git");
+        cmd.arg("-C")
+            .arg(&self.checkout_dir)
+            .args(["verify-tag", "--raw", tag]);
+        if let Some(gpg_home) = &self.gpg_home {
+            cmd.env("GNUPGHOME", gpg_home);
+        }
+        let output = cmd
+            .output()
+            .with_context(|| format!("Failed to run git verify-tag {}", tag))?;
+
+        // gpg's machine-readable status lines are printed on stderr
+        let status = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Tag {} failed signature verification: {}",
+                tag,
+                status.trim()
+            ));
+        }
+        check_signer(tag, &status, &self.trusted_signers)?;
+
+        info!("GitFetcher: tag {} has a valid trusted signature", tag);
+        Ok(())
+    }
+
+    fn build(&self, tag: &str) -> Result<PathBuf> {
+        if self.verify_tag {
+            self.verify_tag_signature(tag)?;
+        }
+
+        info!("GitFetcher: checking out {}", tag);
+        self.git(&[
+            "checkout",
+            "--force",
+            "--detach",
+            &format!("refs/tags/{}", tag),
+        ])?;
+
+        info!("GitFetcher: running build command: {}", self.build_command);
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&self.build_command)
+            .current_dir(&self.checkout_dir)
+            .status()
+            .with_context(|| format!("Failed to run build command: {}", self.build_command))?;
+        if !status.success() {
+            return Err(anyhow!("Build command failed with status {}", status));
+        }
+
+        self.find_artifact()
+    }
+
+    fn find_artifact(&self) -> Result<PathBuf> {
+        let pattern = self.checkout_dir.join(&self.artifact);
+        let pattern = pattern.to_string_lossy();
+        let path = glob::glob(&pattern)
+            .with_context(|| format!("Invalid artifact pattern: {}", self.artifact))?
+            .filter_map(|entry| entry.ok())
+            .find(|p| Path::is_file(p))
+            .ok_or_else(|| anyhow!("Build produced no artifact matching '{}'", self.artifact))?;
+
+        info!("GitFetcher: built artifact {}", path.display());
+        Ok(path)
+    }
+}
+
+/// Fingerprint of the primary key that made a valid signature, from the
+/// `[GNUPG:] VALIDSIG` line of gpg's status output. The primary key's
+/// fingerprint is the line's tenth field; gpg versions that omit it sign with
+/// the primary key, whose fingerprint is then the first field.
+fn validsig_fingerprint(status: &str) -> Option<String> {
+    status.lines().find_map(|line| {
+        let fields: Vec<&str> = line
+            .trim()
+            .strip_prefix("[GNUPG:] VALIDSIG ")?
+            .split_whitespace()
+            .collect();
+        fields
+            .get(9)
+            .or_else(|| fields.first())
+            .map(|fp| normalize_fingerprint(fp))
+    })
+}
+
+/// Check gpg's status output for a valid signature by one of
+/// `trusted_signers` (any key when empty). Only the `VALIDSIG` fingerprint
+/// counts, never text elsewhere in the output such as the signer's user ID.
+fn check_signer(tag: &str, status: &str, trusted_signers: &[String]) -> Result<()> {
+    let signed_by = validsig_fingerprint(status)
+        .ok_or_else(|| anyhow!("Tag {} has no valid signature: {}", tag, status.trim()))?;
+    if !trusted_signers.is_empty() && !trusted_signers.contains(&signed_by) {
+        return Err(anyhow!(
+            "Tag {} is signed by {}, which is not listed in trusted_signers",
+            tag,
+            signed_by
+        ));
+    }
+    Ok(())
+}
+
+/// Uppercase with whitespace removed, so fingerprints compare regardless of spacing.
+fn normalize_fingerprint(s: &str) -> String {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+impl GitFetcher {
+    /// Select the newest tag in the local checkout and build it if it is newer.
+    fn build_if_newer(&self, current_version: &Version) -> FetchResult {
+        let tag = match self.latest_tag()? {
+            Some(tag) => tag,
+            None => {
+                match &self.requirement {
+                    Some(requirement) => warn!(
+                        "GitFetcher: no tag matching pattern '{}' satisfies version {}",
+                        self.tag_pattern, requirement
+                    ),
+                    None => warn!(
+                        "GitFetcher: no tag matching pattern '{}' found",
+                        self.tag_pattern
+                    ),
+                }
+                return Ok(None);
+            }
+        };
+
+        let latest_version = self.tag_format.version(&tag);
+        info!(
+            "GitFetcher: latest tag={}, normalized={}, current={}",
+            tag, latest_version, current_version
+        );
+
+        if !should_replace(current_version, &latest_version, self.requirement.as_ref()) {
+            info!("GitFetcher: no newer version available");
+            return Ok(None);
+        }
+
+        info!(
+            "GitFetcher: version {} available to replace {}",
+            latest_version, current_version
+        );
+        let path = self.build(&tag)?;
+        Ok(Some(Download {
+            asset: file_name(&path),
+            path,
+            version: Some(latest_version),
+            installer: None,
+        }))
+    }
+}
+
+impl Fetcher for GitFetcher {
+    /// The newest tag, from the remote's tags listed with `git ls-remote`
+    /// without cloning or fetching into the checkout. There is no URL: what it
+    /// builds is only known after building it.
+    fn resolve_latest(&self) -> Result<Option<Resolved>> {
+        if which("git").is_err() {
+            return Err(anyhow!(
+                "git not found in PATH; required by the git fetcher"
+            ));
+        }
+        let output = Command::new("git")
+            .args(["ls-remote", "--tags", "--refs"])
+            .arg(&self.url)
+            .output()
+            .context("Failed to run git ls-remote")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git ls-remote {} failed with status {}: {}",
+                self.url,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let refs = String::from_utf8_lossy(&output.stdout);
+        let tags = refs
+            .lines()
+            .filter_map(|line| line.split_once("refs/tags/").map(|(_, tag)| tag.trim()));
+        Ok(self.newest_tag(tags).map(|tag| Resolved {
+            version: self.tag_format.version(&tag),
+            url: None,
+        }))
+    }
+
+    fn fetch_if_newer(&self, current_version: &Version) -> FetchResult {
+        self.sync()?;
+        self.build_if_newer(current_version)
+    }
+
+    fn fetch_cached(&self, current_version: &Version) -> Option<FetchResult> {
+        if !self.checkout_dir.join(".git").exists() {
+            return None;
+        }
+        info!("GitFetcher: offline; using tags already in the local checkout");
+        Some(self.build_if_newer(current_version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::DirBuilderExt;
+
+    const TRUSTED: &str = "0123456789ABCDEF0123456789ABCDEF01234567";
+    const OTHER: &str = "FEDCBA9876543210FEDCBA9876543210FEDCBA98";
+
+    #[test]
+    fn signer_is_taken_from_validsig_only() {
+        let status = format!(
+            "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 76543210FEDCBA98 Mallory <{}>\n[GNUPG:] VALIDSIG 1111111111111111111111111111111111111111 2024-01-01 1704067200 0 4 0 22 10 00 {}\n",
+            TRUSTED, OTHER
+        );
+        assert_eq!(validsig_fingerprint(&status).as_deref(), Some(OTHER));
+        let err = check_signer("v1.0.0", &status, &[TRUSTED.to_string()]).unwrap_err();
+        assert!(err.to_string().contains("not listed"), "{}", err);
+        assert!(check_signer("v1.0.0", &status, &[OTHER.to_string()]).is_ok());
+        assert!(check_signer("v1.0.0", "[GNUPG:] ERRSIG ...", &[]).is_err());
+    }
+
+    fn run(cmd: &mut Command) -> String {
+        let output = cmd.output().unwrap();
+        assert!(output.status.success(), "{:?}: {:?}", cmd, output);
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Generate a signing key with `uid` and return its fingerprint.
+    fn gen_key(gnupg: &Path, uid: &str) -> String {
+        run(Command::new("gpg")
+            .env("GNUPGHOME", gnupg)
+            .args(["--batch", "--passphrase", "", "--quick-gen-key", uid])
+            .args(["ed25519", "sign", "never"]));
+        let keys = run(Command::new("gpg").env("GNUPGHOME", gnupg).args([
+            "--with-colons",
+            "--list-keys",
+            "--",
+            uid,
+        ]));
+        keys.lines()
+            .find_map(|l| l.strip_prefix("fpr:"))
+            .map(|l| l.trim_matches(':').to_string())
+            .unwrap()
+    }
+
+    #[test]
+    fn rejects_signer_whose_uid_contains_trusted_fingerprint() {
+        if which("gpg").is_err() || which("git").is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("autopkg-git-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let gnupg = dir.join("gnupg");
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&gnupg)
+            .unwrap();
+        let trusted = gen_key(&gnupg, "Trusted <trusted@example.com>");
+        let mallory = gen_key(
+            &gnupg,
+            &format!("Mallory ({}) <mallory@example.com>", trusted),
+        );
+
+        let repo = dir.join("repo");
+        let git = |args: &[&str]| {
+            run(Command::new("git")
+                .env("GNUPGHOME", &gnupg)
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+                .args(args))
+        };
+        std::fs::create_dir_all(&repo).unwrap();
+        git(&["init", "-q"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "init"]);
+        git(&["tag", "-u", &trusted, "-m", "good", "v1.0.0"]);
+        git(&["tag", "-u", &mallory, "-m", "evil", "v1.1.0"]);
+
+        let app: ApplicationConfig = serde_yaml::from_str(&format!(
+            "name: signed\nfetcher:\n  type: git\n  url: {}\n  build_command: \"true\"\n  artifact: out\n  verify_tag: true\n  gpg_home: {}\n  trusted_signers: [\"{}\"]\ninstaller: deb\n",
+            repo.display(),
+            gnupg.display(),
+            trusted
+        ))
+        .unwrap();
+        let mut fetcher = GitFetcher::new(&app.fetcher, &app).unwrap();
+        fetcher.checkout_dir = repo.clone();
+
+        let good = fetcher.verify_tag_signature("v1.0.0");
+        let evil = fetcher.verify_tag_signature("v1.1.0");
+        let _ = Command::new("gpgconf")
+            .env("GNUPGHOME", &gnupg)
+            .args(["--kill", "gpg-agent"])
+            .status();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        good.unwrap();
+        let err = evil.unwrap_err();
+        assert!(err.to_string().contains("not listed"), "{}", err);
+    }
+}
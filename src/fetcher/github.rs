@@ -1,28 +1,90 @@
 use crate::config::{ApplicationConfig, FetcherConfig};
-use crate::fetcher::Fetcher;
-use crate::types::FetchResult;
+use crate::fetcher::{
+    acquire_download_permit, download_dir, find_checksum, find_named_checksum, github_auth,
+    github_status_error, save_response, url_file_name, verify_signature, Fetcher, HttpClient,
+    RejectedDownload, ResponseCache, RetryPolicy, StreamedResponse,
+};
+use crate::host;
+use crate::types::{AssetInfo, Download, FetchResult, Resolved};
+use crate::version::{
+    compare, normalize_version, parse_semver, should_replace, TagFormat, Version,
+    VersionRequirement,
+};
 
 use anyhow::{anyhow, Context, Result};
 use glob::Pattern;
 use log::{info, warn};
 use regex::Regex;
-use reqwest::blocking::Client;
-use serde::Deserialize;
-use std::fs::File;
-use std::io::copy;
-use std::path::PathBuf;
-use std::time::Duration;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::{HeaderValue, AUTHORIZATION, ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Default GitHub REST API endpoint.
+const GITHUB_API: &str = "https://api.github.com";
+
+/// Version found in asset names with `version_from: asset` when
+/// `asset_version_regex` isn't set: the first dotted number.
+const DEFAULT_ASSET_VERSION_REGEX: &str = r"(?P<version>\d+(?:\.\d+)+)";
 
 /// GitHub releases API response subset.
 #[derive(Debug, Deserialize)]
 struct GitHubRelease {
     tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    published_at: Option<String>,
     assets: Vec<GitHubAsset>,
 }
 
+/// GitHub tags API response subset.
+#[derive(Debug, Deserialize)]
+struct GitHubTag {
+    name: String,
+}
+
+/// File name of the release cache, next to the state file.
+pub const RELEASE_CACHE_FILE: &str = "autopkg-github-cache.json";
+
+/// Where `/releases/latest` responses are cached, if anywhere.
+static RELEASE_CACHE: Mutex<Option<ResponseCache>> = Mutex::new(None);
+
+/// Cache `/releases/latest` responses in `path` and revalidate them with their
+/// ETag, so unchanged releases cost a `304` that doesn't count against the
+/// rate limit. With `refresh`, cached responses are ignored (and replaced).
+pub fn set_release_cache(path: &Path, refresh: bool) {
+    *RELEASE_CACHE.lock().unwrap() = Some(ResponseCache::new(path, refresh));
+}
+
+/// A cached `/releases/latest` response.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedRelease {
+    etag: String,
+    release: serde_json::Value,
+}
+
+/// GitHub repository API response subset.
+#[derive(Debug, Deserialize)]
+struct GitHubRepo {
+    #[serde(default)]
+    archived: bool,
+}
+
 #[derive(Debug, Deserialize)]
 struct GitHubAsset {
     name: String,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    content_type: String,
     browser_download_url: String,
 }
 
@@ -30,13 +92,37 @@ struct GitHubAsset {
 pub struct GitHubFetcher {
     owner: String,
     repo: String,
-    file_pattern: Pattern,
+    file_patterns: Vec<Pattern>,
+    /// Installer override for each of `file_patterns`, from `prefer`
+    pattern_installers: Vec<Option<String>>,
+    match_label: bool,
+    /// With `version_from: asset`, the regex whose `version` group is the version
+    /// in the selected asset's name
+    asset_version: Option<Regex>,
+    /// Whether pre-releases are considered (`channel: prerelease`)
+    prerelease: bool,
+    /// The application's `version` requirement releases must satisfy
+    requirement: Option<VersionRequirement>,
+    /// Release tag used instead of the latest release (`tag`)
+    tag: Option<String>,
+    /// `download_url` template, used instead of release assets
+    download_url: Option<String>,
+    /// Release asset holding the checksums downloads are verified against
+    checksum_pattern: Option<Pattern>,
+    /// Release asset holding the detached signature of downloads, and the
+    /// public key it must verify against
+    signature: Option<(Pattern, PathBuf)>,
+    tag_format: TagFormat,
     client: Client,
+    retry: RetryPolicy,
+    auth: Option<HeaderValue>,
+    api_base: String,
+    release_cache: Option<ResponseCache>,
     _app_name: String,
 }
 
 impl GitHubFetcher {
-    pub fn new(config: &FetcherConfig, app: &ApplicationConfig) -> Result<Self> {
+    pub fn new(config: &FetcherConfig, app: &ApplicationConfig, http: &HttpClient) -> Result<Self> {
         let repo_str = config
             .repo
             .as_ref()
@@ -45,46 +131,577 @@ impl GitHubFetcher {
             .split_once('/')
             .ok_or_else(|| anyhow!("GitHub repo must be in form `owner/repo`"))?;
 
-        let pattern_str = config.file_pattern.as_deref().unwrap_or("*");
+        let file_patterns = config
+            .file_patterns()
+            .into_iter()
+            .map(|p| {
+                let p = host::substitute(p)?;
+                Pattern::new(&p).with_context(|| format!("Invalid glob pattern: {}", p))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        let file_pattern = Pattern::new(pattern_str)
-            .with_context(|| format!("Invalid glob pattern: {}", pattern_str))?;
+        let pattern_installers = (0..file_patterns.len())
+            .map(|i| config.preferred_installer(i).map(str::to_string))
+            .collect();
 
-        let client = Client::builder()
-            .user_agent("autopkg-rust/0.1")
-            .timeout(Duration::from_secs(30))
-            .build()?;
+        let match_label = match config.match_field.as_deref() {
+            None | Some("name") => false,
+            Some("label") => true,
+            Some(other) => {
+                return Err(anyhow!(
+                    "Invalid match_field: {} (expected `name` or `label`)",
+                    other
+                ))
+            }
+        };
+
+        let asset_version = match config.version_from.as_deref() {
+            None | Some("tag") => None,
+            Some("asset") => {
+                if config.download_url.is_some() {
+                    return Err(anyhow!(
+                        "version_from: asset can't be combined with download_url, which has no asset to read the version from"
+                    ));
+                }
+                let pattern = config
+                    .asset_version_regex
+                    .as_deref()
+                    .unwrap_or(DEFAULT_ASSET_VERSION_REGEX);
+                let regex = Regex::new(pattern)
+                    .with_context(|| format!("Invalid asset_version_regex: {}", pattern))?;
+                if !regex.capture_names().any(|name| name == Some("version")) {
+                    return Err(anyhow!(
+                        "asset_version_regex '{}' has no named `version` group, e.g. `_(?P<version>[0-9.]+)_`",
+                        pattern
+                    ));
+                }
+                Some(regex)
+            }
+            Some(other) => {
+                return Err(anyhow!(
+                    "Invalid version_from: {} (expected `tag` or `asset`)",
+                    other
+                ))
+            }
+        };
+        if asset_version.is_none() && config.asset_version_regex.is_some() {
+            return Err(anyhow!(
+                "asset_version_regex only applies with `version_from: asset`"
+            ));
+        }
+
+        let prerelease = match config.channel.as_deref() {
+            None | Some("stable") => false,
+            Some("prerelease") => true,
+            Some(other) => {
+                return Err(anyhow!(
+                    "Invalid channel: {} (expected `stable` or `prerelease`)",
+                    other
+                ))
+            }
+        };
+
+        if config.tag.is_some() && config.channel.is_some() {
+            return Err(anyhow!(
+                "tag and channel can't be combined; the release with the tag is used whatever its channel"
+            ));
+        }
+
+        let checksum_pattern = config
+            .checksum_pattern
+            .as_deref()
+            .map(|p| {
+                let p = host::substitute(p)?;
+                Pattern::new(&p).with_context(|| format!("Invalid glob pattern: {}", p))
+            })
+            .transpose()?;
+
+        let signature = match (&config.signature_pattern, &config.gpg_key) {
+            (Some(pattern), Some(key)) => {
+                let pattern = host::substitute(pattern)?;
+                let pattern = Pattern::new(&pattern)
+                    .with_context(|| format!("Invalid glob pattern: {}", pattern))?;
+                if !Path::new(key).is_file() {
+                    return Err(anyhow!("gpg_key {} does not exist", key));
+                }
+                Some((pattern, PathBuf::from(key)))
+            }
+            (None, None) => None,
+            _ => {
+                return Err(anyhow!(
+                    "signature_pattern and gpg_key must be set together"
+                ))
+            }
+        };
+
+        let client = http.for_fetcher(config, None)?;
 
         Ok(Self {
             owner: owner.to_string(),
             repo: repo.to_string(),
-            file_pattern,
+            file_patterns,
+            pattern_installers,
+            match_label,
+            asset_version,
+            prerelease,
+            requirement: VersionRequirement::from_app(app)?,
+            tag: config.tag.clone(),
+            download_url: config
+                .download_url
+                .as_deref()
+                .map(host::substitute)
+                .transpose()?,
+            checksum_pattern,
+            signature,
+            tag_format: TagFormat::from_config(config),
             client,
+            retry: RetryPolicy::from_config(config),
+            auth: github_auth(config)?,
+            api_base: GITHUB_API.to_string(),
+            release_cache: RELEASE_CACHE.lock().unwrap().clone(),
             _app_name: app.name.clone(),
         })
     }
 
-    fn latest_release(&self) -> Result<GitHubRelease> {
+    /// Use a different API endpoint, e.g. a mock server in tests.
+    #[cfg(test)]
+    fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Cache releases in `path`, without going through the global setting.
+    #[cfg(test)]
+    fn with_release_cache(mut self, path: &Path) -> Self {
+        self.release_cache = Some(ResponseCache::new(path, false));
+        self
+    }
+
+    /// GET `url`, authenticated if a token is configured.
+    fn get(&self, url: &str) -> RequestBuilder {
+        let request = self.client.get(url);
+        match &self.auth {
+            Some(auth) => request.header(AUTHORIZATION, auth.clone()),
+            None => request,
+        }
+    }
+
+    /// GET a download `url`. The token is only sent with release asset downloads,
+    /// never to the site a `download_url` points at.
+    fn download_request(&self, url: &str) -> RequestBuilder {
+        if self.download_url.is_some() {
+            self.client.get(url)
+        } else {
+            self.get(url)
+        }
+    }
+
+    /// The `download_url` template filled in for a release, if one is configured.
+    fn templated_url(&self, tag: &str, version: &str) -> Option<String> {
+        self.download_url
+            .as_ref()
+            .map(|t| t.replace("{version}", version).replace("{tag}", tag))
+    }
+
+    /// The latest release of the configured channel: `/releases/latest` for
+    /// stable, otherwise the most recently published non-draft release, which
+    /// may be a pre-release. With a `version` requirement, the newest release
+    /// of the channel satisfying it among the 100 most recent ones. `None` if
+    /// the repository has no such release. With a `tag`, always that release.
+    /// With `download_url`, a repository without any release falls back to its
+    /// newest tag.
+    fn latest_release(&self) -> Result<Option<GitHubRelease>> {
+        if let Some(tag) = &self.tag {
+            return self.tagged_release(tag).map(Some);
+        }
+
+        if let Some(requirement) = &self.requirement {
+            let releases = self.list_releases("100")?;
+            if releases.is_empty() && self.download_url.is_some() {
+                return self.latest_tag();
+            }
+            let version = |r: &GitHubRelease| self.release_version(r);
+            return Ok(releases
+                .into_iter()
+                .filter(|r| self.prerelease || !r.prerelease)
+                .filter(|r| requirement.matches(&version(r)))
+                .max_by(|a, b| compare(&version(a), &version(b))));
+        }
+
+        if !self.prerelease {
+            let url = format!(
+                "{}/repos/{}/{}/releases/latest",
+                self.api_base, self.owner, self.repo
+            );
+            info!("GitHubFetcher: querying {}", url);
+            return match self.get_latest_release(&url)? {
+                Some(release) => Ok(Some(release)),
+                None => self.latest_tag(),
+            };
+        }
+
+        let releases = self.list_releases("30")?;
+        if releases.is_empty() && self.download_url.is_some() {
+            return self.latest_tag();
+        }
+        // RFC 3339 timestamps in UTC sort chronologically as strings.
+        Ok(releases
+            .into_iter()
+            .max_by(|a, b| a.published_at.cmp(&b.published_at)))
+    }
+
+    /// The newest of the 100 most recent tags, as a release without assets, for
+    /// repositories that tag versions but publish no releases (`download_url`).
+    /// Tags of pre-releases are only considered on the `prerelease` channel, and
+    /// with a `version` requirement only tags satisfying it. `None` if no tag
+    /// qualifies.
+    fn latest_tag(&self) -> Result<Option<GitHubRelease>> {
+        let url = format!("{}/repos/{}/{}/tags", self.api_base, self.owner, self.repo);
+        info!("GitHubFetcher: no releases; querying {}", url);
+
+        let resp = self
+            .retry
+            .send(self.get(&url).query(&[("per_page", "100")]))?;
+        if !resp.status().is_success() {
+            return Err(github_status_error(&resp));
+        }
+
+        let tags: Vec<GitHubTag> = resp.json()?;
+        let is_prerelease = |v: &str| parse_semver(v).is_some_and(|semver| !semver.pre.is_empty());
+        Ok(tags
+            .into_iter()
+            .map(|t| (self.tag_format.version(&t.name), t.name))
+            .filter(|(version, _)| self.prerelease || !is_prerelease(version))
+            .filter(|(version, _)| {
+                self.requirement
+                    .as_ref()
+                    .is_none_or(|requirement| requirement.matches(version))
+            })
+            .max_by(|(a, _), (b, _)| compare(a, b))
+            .map(|(_, tag_name)| GitHubRelease {
+                tag_name,
+                draft: false,
+                prerelease: false,
+                published_at: None,
+                assets: Vec::new(),
+            }))
+    }
+
+    /// GET `/releases/tags/<tag>`. A missing tag is an error rather than a
+    /// fallback to the latest release, so a pinned fleet never drifts.
+    fn tagged_release(&self, tag: &str) -> Result<GitHubRelease> {
+        let mut url = reqwest::Url::parse(&self.api_base)
+            .with_context(|| format!("Invalid GitHub API URL: {}", self.api_base))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid GitHub API URL: {}", self.api_base))?
+            .pop_if_empty()
+            .extend(["repos", &self.owner, &self.repo, "releases", "tags", tag]);
+        info!("GitHubFetcher: querying {}", url);
+
+        let resp = self.retry.send(self.get(url.as_str()))?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Err(anyhow!(
+                "No release with tag {} in {}/{}",
+                tag,
+                self.owner,
+                self.repo
+            ));
+        }
+        if !resp.status().is_success() {
+            return Err(github_status_error(&resp));
+        }
+        let release: GitHubRelease = resp.json()?;
+
+        if let Some(requirement) = &self.requirement {
+            let version = self.release_version(&release);
+            if !requirement.matches(&version) {
+                return Err(anyhow!(
+                    "Release {} (version {}) doesn't satisfy version {}",
+                    tag,
+                    version,
+                    requirement
+                ));
+            }
+        }
+        Ok(release)
+    }
+
+    /// GET `/releases/latest`, revalidating the cached response with its ETag
+    /// if there is one. A `304` reuses the cached release; a cached release that
+    /// no longer parses is fetched again in full (and replaced). `None` for a
+    /// repository without releases when `download_url` is set, so its tags are
+    /// used instead.
+    fn get_latest_release(&self, url: &str) -> Result<Option<GitHubRelease>> {
+        let key = format!("{}/{}", self.owner, self.repo);
+        let cached = self
+            .release_cache
+            .as_ref()
+            .and_then(|c| c.get::<CachedRelease>(&key));
+
+        let mut request = self.get(url);
+        if let Some(cached) = &cached {
+            request = request.header(IF_NONE_MATCH, &cached.etag);
+        }
+        let mut resp = self.retry.send(request)?;
+
+        if let (StatusCode::NOT_MODIFIED, Some(cached)) = (resp.status(), cached) {
+            match GitHubRelease::deserialize(&cached.release) {
+                Ok(release) => {
+                    info!(
+                        "GitHubFetcher: latest release of {} not modified; using cached response",
+                        key
+                    );
+                    return Ok(Some(release));
+                }
+                Err(e) => {
+                    warn!(
+                        "GitHubFetcher: ignoring invalid cached release of {}: {}",
+                        key, e
+                    );
+                    resp = self.retry.send(self.get(url))?;
+                }
+            }
+        }
+        if resp.status() == StatusCode::NOT_FOUND && self.download_url.is_some() {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            return Err(github_status_error(&resp));
+        }
+
+        let etag = resp
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body: serde_json::Value = resp.json()?;
+        let release = GitHubRelease::deserialize(&body)?;
+        if let (Some(cache), Some(etag)) = (&self.release_cache, etag) {
+            cache.set(
+                &key,
+                CachedRelease {
+                    etag,
+                    release: body,
+                },
+            );
+        }
+        Ok(Some(release))
+    }
+
+    /// The most recent `per_page` releases, without drafts.
+    fn list_releases(&self, per_page: &str) -> Result<Vec<GitHubRelease>> {
         let url = format!(
-            "https://api.github.com/repos/{}/{}/releases/latest",
-            self.owner, self.repo
+            "{}/repos/{}/{}/releases",
+            self.api_base, self.owner, self.repo
         );
-        info!("GitHubFetcher: querying {}", url);
+        info!("GitHubFetcher: querying {} (including pre-releases)", url);
+
+        let resp = self
+            .retry
+            .send(self.get(&url).query(&[("per_page", per_page)]))?;
+        if !resp.status().is_success() {
+            return Err(github_status_error(&resp));
+        }
+
+        let releases: Vec<GitHubRelease> = resp.json()?;
+        Ok(releases.into_iter().filter(|r| !r.draft).collect())
+    }
+
+    /// Version of `release`: from its tag, or with `version_from: asset` from the
+    /// name of the asset that would be downloaded. Falls back to the tag when
+    /// no asset matches the pattern or the regex doesn't match its name.
+    fn release_version(&self, release: &GitHubRelease) -> String {
+        let tag_version = || self.tag_format.version(&release.tag_name);
+        let Some(regex) = &self.asset_version else {
+            return tag_version();
+        };
+        let Some((asset, _)) = self.select_asset(&release.assets) else {
+            return tag_version();
+        };
+        match regex.captures(&asset.name).and_then(|c| c.name("version")) {
+            Some(version) => normalize_version(version.as_str()),
+            None => {
+                warn!(
+                    "GitHubFetcher: asset_version_regex '{}' does not match asset '{}'; using tag {}",
+                    regex, asset.name, release.tag_name
+                );
+                tag_version()
+            }
+        }
+    }
+
+    fn asset_matches(&self, pattern: &Pattern, asset: &GitHubAsset) -> bool {
+        if self.match_label {
+            asset
+                .label
+                .as_deref()
+                .is_some_and(|label| pattern.matches(label))
+        } else {
+            pattern.matches(&asset.name)
+        }
+    }
 
-        let resp = self.client.get(&url).send()?;
+    /// Pick the first asset matching the first pattern (in configured order) that
+    /// matches anything, with the index of that pattern.
+    fn select_asset<'a>(&self, assets: &'a [GitHubAsset]) -> Option<(&'a GitHubAsset, usize)> {
+        for (rank, pattern) in self.file_patterns.iter().enumerate() {
+            if let Some(asset) = assets.iter().find(|a| self.asset_matches(pattern, a)) {
+                info!("GitHubFetcher: pattern '{}' matched", pattern);
+                return Some((asset, rank));
+            }
+        }
+        None
+    }
+
+    /// Expected SHA256 of the asset `name`, if `checksum_pattern` is set: from the
+    /// matching asset named after it (e.g. `<name>.sha256`) or the only matching
+    /// one, else from whichever matching asset has an entry for `name`.
+    fn expected_checksum(&self, assets: &[GitHubAsset], name: &str) -> Result<Option<String>> {
+        let Some(pattern) = &self.checksum_pattern else {
+            return Ok(None);
+        };
+        let candidates: Vec<&GitHubAsset> =
+            assets.iter().filter(|a| pattern.matches(&a.name)).collect();
+        let own = candidates.iter().find(|a| a.name.starts_with(name));
+        match (own, candidates.as_slice()) {
+            (_, []) => Err(anyhow!(
+                "No checksum asset matching '{}' in release",
+                pattern
+            )),
+            // The asset named after the download (e.g. `<name>.sha256`), or the
+            // only one, may hold just the hash
+            (Some(asset), _) | (None, [asset]) => {
+                let contents = self.checksum_contents(asset)?;
+                find_checksum(&contents, name)
+                    .map(Some)
+                    .ok_or_else(|| anyhow!("No checksum for '{}' in '{}'", name, asset.name))
+            }
+            // Otherwise look for an entry naming it in any of them
+            (None, _) => {
+                for asset in &candidates {
+                    let contents = self.checksum_contents(asset)?;
+                    if let Some(checksum) = find_named_checksum(&contents, name) {
+                        return Ok(Some(checksum));
+                    }
+                }
+                Err(anyhow!(
+                    "No checksum for '{}' in the assets matching '{}'",
+                    name,
+                    pattern
+                ))
+            }
+        }
+    }
+
+    fn checksum_contents(&self, asset: &GitHubAsset) -> Result<String> {
+        info!("GitHubFetcher: reading checksums from '{}'", asset.name);
+        let resp = self.retry.send(self.get(&asset.browser_download_url))?;
         if !resp.status().is_success() {
             return Err(anyhow!(
-                "GitHub API returned non-success status: {}",
+                "Failed to download checksums from {}: status {}",
+                asset.browser_download_url,
                 resp.status()
             ));
         }
+        Ok(resp.text()?)
+    }
 
-        let release: GitHubRelease = resp.json()?;
-        Ok(release)
+    /// The release asset holding the signature of the asset `name`, if
+    /// `signature_pattern` is set: the matching asset named after it (e.g.
+    /// `<name>.asc`), or the only matching one.
+    fn signature_asset<'a>(
+        &self,
+        assets: &'a [GitHubAsset],
+        name: &str,
+    ) -> Result<Option<&'a GitHubAsset>> {
+        let Some((pattern, _)) = &self.signature else {
+            return Ok(None);
+        };
+        let candidates: Vec<&GitHubAsset> =
+            assets.iter().filter(|a| pattern.matches(&a.name)).collect();
+        match candidates.as_slice() {
+            [] => Err(anyhow!(
+                "No signature asset matching '{}' in release",
+                pattern
+            )),
+            [only] => Ok(Some(only)),
+            _ => candidates
+                .iter()
+                .find(|a| a.name.starts_with(name))
+                .copied()
+                .map(Some)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Several assets match signature_pattern '{}', none named after '{}'",
+                        pattern,
+                        name
+                    )
+                }),
+        }
     }
 
-    fn download_asset(&self, url: &str, name: &str) -> Result<PathBuf> {
-        let mut resp = self.client.get(url).send()?;
+    /// Verify the downloaded asset `name` at `path` against its detached
+    /// signature. A download that doesn't verify is left for the caller to
+    /// quarantine, see [`RejectedDownload`].
+    fn verify_download_signature(
+        &self,
+        path: &Path,
+        name: &str,
+        signature: &GitHubAsset,
+    ) -> Result<()> {
+        let Some((_, key)) = &self.signature else {
+            return Ok(());
+        };
+        info!(
+            "GitHubFetcher: verifying {} with '{}'",
+            name, signature.name
+        );
+
+        let sig_path = download_dir()?.join(format!("autopkg-{}-{}", self.repo, signature.name));
+        let result = (|| {
+            let mut resp = self.retry.send(self.get(&signature.browser_download_url))?;
+            if !resp.status().is_success() {
+                return Err(anyhow!(
+                    "Failed to download signature from {}: status {}",
+                    signature.browser_download_url,
+                    resp.status()
+                ));
+            }
+            save_response(&mut resp, &sig_path)?;
+            verify_signature(path, &sig_path, key)
+        })();
+        let _ = std::fs::remove_file(&sig_path);
+
+        if let Err(e) = result {
+            return Err(e
+                .context(format!(
+                    "Signature does not verify against gpg_key {}",
+                    key.display()
+                ))
+                .context(RejectedDownload {
+                    name: name.to_string(),
+                    path: path.to_path_buf(),
+                }));
+        }
+        info!("GitHubFetcher: signature of {} verified", name);
+        Ok(())
+    }
+
+    /// Download the asset `name` from `url`, verified against the release's
+    /// checksums and signature assets when those are configured.
+    fn download_verified(&self, assets: &[GitHubAsset], url: &str, name: &str) -> Result<PathBuf> {
+        let checksum = self.expected_checksum(assets, name)?;
+        let signature = self.signature_asset(assets, name)?;
+        let path = self.download_asset(url, name, checksum.as_deref())?;
+        if let Some(signature) = signature {
+            self.verify_download_signature(&path, name, signature)?;
+        }
+        Ok(path)
+    }
+
+    fn download_asset(&self, url: &str, name: &str, checksum: Option<&str>) -> Result<PathBuf> {
+        let _permit = acquire_download_permit();
+        let mut resp = self.retry.send(self.download_request(url))?;
         if !resp.status().is_success() {
             return Err(anyhow!(
                 "Failed to download asset from {}: status {}",
@@ -93,87 +710,144 @@ impl GitHubFetcher {
             ));
         }
 
-        let tmp_dir = std::env::temp_dir();
-        let filename = format!("autopkg-{}-{}", self.repo, name);
-        let path = tmp_dir.join(filename);
+        let path = download_dir()?.join(format!("autopkg-{}-{}", self.repo, name));
 
-        let mut out = File::create(&path)?;
-        copy(&mut resp, &mut out)?;
+        let actual = save_response(&mut resp, &path)?;
+        if let Some(expected) = checksum {
+            if actual != expected {
+                return Err(anyhow!(
+                    "Checksum mismatch for {}: expected sha256 {}, got {}",
+                    name,
+                    expected,
+                    actual
+                )
+                .context(RejectedDownload {
+                    name: name.to_string(),
+                    path,
+                }));
+            }
+            info!("GitHubFetcher: sha256 of {} verified", name);
+        }
 
         info!("Downloaded asset to {}", path.display());
         Ok(path)
     }
+}
 
-    /// Naive version extraction from a tag like "v1.2.3" or "1.2.3".
-    fn normalize_version(tag: &str) -> String {
-        let re = Regex::new(r"v?(?P<version>[0-9][0-9A-Za-z\.\-\+]*)").unwrap();
-        if let Some(caps) = re.captures(tag) {
-            caps["version"].to_string()
-        } else {
-            tag.to_string()
+impl Fetcher for GitHubFetcher {
+    fn source_archived(&self) -> Result<bool> {
+        let url = format!("{}/repos/{}/{}", self.api_base, self.owner, self.repo);
+        let resp = self.retry.send(self.get(&url))?;
+        if !resp.status().is_success() {
+            return Err(github_status_error(&resp));
         }
+
+        let repo: GitHubRepo = resp.json()?;
+        Ok(repo.archived)
     }
 
-    /// Very simple semantic version comparison: "1.2.3" style.
-    /// Returns true if `remote` is newer than `local`.
-    fn is_newer(local: &str, remote: &str) -> bool {
-        fn parse(v: &str) -> Vec<u64> {
-            v.split('.').filter_map(|s| s.parse::<u64>().ok()).collect()
-        }
+    fn list_assets(&self) -> Result<Vec<AssetInfo>> {
+        let Some(release) = self.latest_release()? else {
+            return Ok(Vec::new());
+        };
+        info!("GitHubFetcher: latest release tag={}", release.tag_name);
 
-        let mut local_parts = parse(local);
-        let mut remote_parts = parse(remote);
+        Ok(release
+            .assets
+            .iter()
+            .map(|a| AssetInfo {
+                name: a.name.clone(),
+                size: a.size,
+                content_type: a.content_type.clone(),
+                matches: self.file_patterns.iter().any(|p| self.asset_matches(p, a)),
+            })
+            .collect())
+    }
+
+    fn resolve_latest(&self) -> Result<Option<Resolved>> {
+        let Some(release) = self.latest_release()? else {
+            return Ok(None);
+        };
+        let version = self.release_version(&release);
+        let url = match self.templated_url(&release.tag_name, &version) {
+            Some(url) => Some(url),
+            None => self
+                .select_asset(&release.assets)
+                .map(|(a, _)| a.browser_download_url.clone()),
+        };
+        Ok(Some(Resolved { version, url }))
+    }
 
-        let max_len = local_parts.len().max(remote_parts.len());
-        local_parts.resize(max_len, 0);
-        remote_parts.resize(max_len, 0);
+    fn supports_stream(&self) -> bool {
+        // Streamed files are installed before they could be verified.
+        self.checksum_pattern.is_none() && self.signature.is_none()
+    }
 
-        for (l, r) in local_parts.iter().zip(remote_parts.iter()) {
-            if r > l {
-                return true;
-            } else if r < l {
-                return false;
-            }
+    fn open_stream(&self, url: &str) -> Result<Box<dyn Read>> {
+        let permit = acquire_download_permit();
+        let resp = self.retry.send(self.download_request(url))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Failed to download asset from {}: status {}",
+                url,
+                resp.status()
+            ));
         }
-        false
+        info!("GitHubFetcher: streaming {}", url);
+        Ok(Box::new(StreamedResponse::new(resp, permit)))
     }
-}
 
-impl Fetcher for GitHubFetcher {
-    fn fetch_if_newer(&self, current_version: &str) -> FetchResult {
-        let release = self.latest_release()?;
+    fn fetch_if_newer(&self, current_version: &Version) -> FetchResult {
+        let Some(release) = self.latest_release()? else {
+            match &self.requirement {
+                Some(requirement) => warn!(
+                    "GitHubFetcher: no release satisfies version {}",
+                    requirement
+                ),
+                None => warn!("GitHubFetcher: no releases found"),
+            }
+            return Ok(None);
+        };
 
         let latest_tag = release.tag_name.clone();
-        let latest_version = Self::normalize_version(&latest_tag);
-        let current_normalized = Self::normalize_version(current_version);
+        let latest_version = self.release_version(&release);
 
         info!(
             "GitHubFetcher: latest tag={}, normalized={}, current={}",
-            latest_tag, latest_version, current_normalized
+            latest_tag, latest_version, current_version
         );
 
-        if !Self::is_newer(&current_normalized, &latest_version) {
+        if !should_replace(current_version, &latest_version, self.requirement.as_ref()) {
             info!("GitHubFetcher: no newer version available");
             return Ok(None);
         }
 
         info!(
-            "GitHubFetcher: newer version available: {} > {}",
-            latest_version, current_normalized
+            "GitHubFetcher: version {} available to replace {}",
+            latest_version, current_version
         );
 
-        // Find asset matching the file_pattern
-        let asset = release
-            .assets
-            .into_iter()
-            .find(|a| self.file_pattern.matches(&a.name));
+        if let Some(url) = self.templated_url(&latest_tag, &latest_version) {
+            info!("GitHubFetcher: downloading from {}", url);
+            let name = url_file_name(&url).unwrap_or("download").to_string();
+            let path = self.download_verified(&release.assets, &url, &name)?;
+            return Ok(Some(Download {
+                path,
+                version: Some(latest_version),
+                asset: name,
+                installer: None,
+            }));
+        }
 
-        let asset = match asset {
-            Some(a) => a,
+        let (asset, rank) = match self.select_asset(&release.assets) {
+            Some(selected) => selected,
             None => {
+                let patterns: Vec<String> =
+                    self.file_patterns.iter().map(|p| p.to_string()).collect();
                 warn!(
-                    "GitHubFetcher: no asset matching pattern '{}' found",
-                    self.file_pattern
+                    "GitHubFetcher: no asset {} matching pattern '{}' found",
+                    if self.match_label { "label" } else { "name" },
+                    patterns.join("', '")
                 );
                 return Ok(None);
             }
@@ -184,7 +858,1000 @@ impl Fetcher for GitHubFetcher {
             asset.name, asset.browser_download_url
         );
 
-        let path = self.download_asset(&asset.browser_download_url, &asset.name)?;
-        Ok(Some(path))
+        let path =
+            self.download_verified(&release.assets, &asset.browser_download_url, &asset.name)?;
+        Ok(Some(Download {
+            path,
+            version: Some(latest_version),
+            asset: asset.name.clone(),
+            installer: self.pattern_installers[rank].clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::Scheme;
+    use mockito::{Matcher, Server, ServerGuard};
+
+    /// Build a fetcher for `owner/<repo>` from the YAML `fetcher` fields, pointed at `server`.
+    fn fetcher(server: &ServerGuard, repo: &str, extra: &str) -> GitHubFetcher {
+        let app: ApplicationConfig = serde_yaml::from_str(&format!(
+            "name: test\nfetcher:\n  type: github\n  repo: owner/{}\n{}\ninstaller: deb\n",
+            repo, extra
+        ))
+        .unwrap();
+        GitHubFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap(),
+        )
+        .unwrap()
+        .with_api_base(&server.url())
+    }
+
+    fn asset(server: &ServerGuard, name: &str, label: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "label": label,
+            "size": 4,
+            "content_type": "application/octet-stream",
+            "browser_download_url": format!("{}/download/{}", server.url(), name),
+        })
+    }
+
+    fn mock_release(
+        server: &mut ServerGuard,
+        repo: &str,
+        tag: &str,
+        assets: Vec<serde_json::Value>,
+    ) -> mockito::Mock {
+        let body = serde_json::json!({ "tag_name": tag, "assets": assets });
+        server
+            .mock(
+                "GET",
+                format!("/repos/owner/{}/releases/latest", repo).as_str(),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .create()
+    }
+
+    #[test]
+    fn downloads_matching_asset_when_newer() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool_1.2.0_arm64.deb", None),
+            asset(&server, "tool_1.2.0_amd64.deb", None),
+        ];
+        let release = mock_release(&mut server, "newer", "v1.2.0", assets);
+        let download = server
+            .mock("GET", "/download/tool_1.2.0_amd64.deb")
+            .with_body("data")
+            .create();
+
+        let fetcher = fetcher(&server, "newer", "  file_pattern: \"*_amd64.deb\"");
+        let fetched = fetcher
+            .fetch_if_newer(&Version::upstream("1.1.9"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.version.as_deref(), Some("1.2.0"));
+        assert_eq!(fetched.asset, "tool_1.2.0_amd64.deb");
+        let path = fetched.path;
+
+        release.assert();
+        download.assert();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+        assert!(path.ends_with("autopkg-newer-tool_1.2.0_amd64.deb"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn version_from_asset_reads_version_from_asset_name() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "mytool_3.14.2_amd64.deb", None),
+            asset(&server, "notes.txt", None),
+        ];
+        mock_release(&mut server, "generic", "latest", assets);
+        let download = server
+            .mock("GET", "/download/mytool_3.14.2_amd64.deb")
+            .with_body("data")
+            .create();
+
+        let fetcher = fetcher(
+            &server,
+            "generic",
+            "  file_pattern: \"*_amd64.deb\"\n  version_from: asset",
+        );
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("3.14.2"))
+            .unwrap()
+            .is_none());
+        let fetched = fetcher
+            .fetch_if_newer(&Version::upstream("3.9.0"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.version.as_deref(), Some("3.14.2"));
+        download.assert();
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn skips_download_when_up_to_date() {
+        let mut server = Server::new();
+        let assets = vec![asset(&server, "tool.deb", None)];
+        mock_release(&mut server, "current", "v1.2.0", assets);
+        let download = server
+            .mock("GET", Matcher::Regex("^/download/".into()))
+            .expect(0)
+            .create();
+
+        let fetcher = fetcher(&server, "current", "");
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.2.0"))
+            .unwrap()
+            .is_none());
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.10.0"))
+            .unwrap()
+            .is_none());
+        download.assert();
+    }
+
+    #[test]
+    fn revalidates_cached_release_with_etag() {
+        let mut server = Server::new();
+        let path = "/repos/owner/cached/releases/latest";
+        let body = serde_json::json!({ "tag_name": "v3.1.0", "assets": [] });
+        let full = server
+            .mock("GET", path)
+            .match_header("if-none-match", Matcher::Missing)
+            .with_header("etag", "\"abc\"")
+            .with_body(body.to_string())
+            .expect(2)
+            .create();
+        let not_modified = server
+            .mock("GET", path)
+            .match_header("if-none-match", "\"abc\"")
+            .with_status(304)
+            .expect(1)
+            .create();
+
+        let cache = std::env::temp_dir().join("autopkg-test-github-cache.json");
+        let _ = std::fs::remove_file(&cache);
+        let fetcher = fetcher(&server, "cached", "").with_release_cache(&cache);
+        for _ in 0..2 {
+            let resolved = fetcher.resolve_latest().unwrap().unwrap();
+            assert_eq!(resolved.version, "3.1.0");
+        }
+
+        // A corrupt cache falls back to a full request
+        std::fs::write(&cache, "not json").unwrap();
+        let resolved = fetcher.resolve_latest().unwrap().unwrap();
+        assert_eq!(resolved.version, "3.1.0");
+        full.assert();
+        not_modified.assert();
+        std::fs::remove_file(&cache).unwrap();
+        let _ = std::fs::remove_file(format!("{}.lock", cache.display()));
+    }
+
+    #[test]
+    fn compares_debian_versions_by_upstream_part() {
+        let mut server = Server::new();
+        let assets = vec![asset(&server, "tool.deb", None)];
+        mock_release(&mut server, "debian", "v1.2.0", assets);
+        let download = server
+            .mock("GET", Matcher::Regex("^/download/".into()))
+            .expect(0)
+            .create();
+
+        let fetcher = fetcher(&server, "debian", "");
+        let installed = Version::new("1:1.2.0+dfsg-1ubuntu1", Scheme::Debian);
+        assert!(fetcher.fetch_if_newer(&installed).unwrap().is_none());
+        download.assert();
+    }
+
+    #[test]
+    fn release_candidate_sorts_below_its_release() {
+        let mut server = Server::new();
+        let assets = vec![asset(&server, "tool.deb", None)];
+        mock_release(&mut server, "rc", "v1.2.0-rc1", assets);
+        let download = server
+            .mock("GET", Matcher::Regex("^/download/".into()))
+            .expect(0)
+            .create();
+
+        let fetcher = fetcher(&server, "rc", "");
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.2.0"))
+            .unwrap()
+            .is_none());
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.2.0-rc1+build.7"))
+            .unwrap()
+            .is_none());
+        download.assert();
+    }
+
+    #[test]
+    fn prerelease_channel_picks_newest_published_non_draft() {
+        let mut server = Server::new();
+        let body = serde_json::json!([
+            {
+                "tag_name": "v2.0.0",
+                "draft": true,
+                "published_at": null,
+                "assets": [asset(&server, "draft.deb", None)],
+            },
+            {
+                "tag_name": "v1.9.0",
+                "prerelease": false,
+                "published_at": "2024-03-01T10:00:00Z",
+                "assets": [asset(&server, "stable.deb", None)],
+            },
+            {
+                "tag_name": "v2.0.0-beta.1",
+                "prerelease": true,
+                "published_at": "2024-04-01T10:00:00Z",
+                "assets": [asset(&server, "beta.deb", None)],
+            },
+        ]);
+        server
+            .mock("GET", "/repos/owner/beta/releases")
+            .match_query(Matcher::UrlEncoded("per_page".into(), "30".into()))
+            .with_body(body.to_string())
+            .create();
+        server
+            .mock("GET", "/download/beta.deb")
+            .with_body("beta")
+            .create();
+
+        let fetched = fetcher(&server, "beta", "  channel: prerelease")
+            .fetch_if_newer(&Version::upstream("1.9.0"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.version.as_deref(), Some("2.0.0-beta.1"));
+        assert_eq!(fetched.asset, "beta.deb");
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn version_requirement_picks_newest_satisfying_release() {
+        let mut server = Server::new();
+        let body = serde_json::json!([
+            {"tag_name": "v3.0.0", "assets": [asset(&server, "tool-3.deb", None)]},
+            {"tag_name": "v2.2.0-rc.1", "prerelease": true, "assets": []},
+            {"tag_name": "v2.1.5", "assets": [asset(&server, "tool-2.1.5.deb", None)]},
+            {"tag_name": "v2.0.9", "assets": [asset(&server, "tool-2.0.9.deb", None)]},
+            {"tag_name": "v1.4.3", "assets": [asset(&server, "tool-1.4.3.deb", None)]},
+        ]);
+        server
+            .mock("GET", "/repos/owner/pinned/releases")
+            .match_query(Matcher::UrlEncoded("per_page".into(), "100".into()))
+            .with_body(body.to_string())
+            .create();
+        server
+            .mock("GET", Matcher::Regex("^/download/".into()))
+            .with_body("deb")
+            .create();
+        let pinned = |requirement: &str| {
+            fetcher(&server, "pinned", &format!("version: \"{}\"", requirement))
+        };
+
+        let fetched = pinned(">=2, <3")
+            .fetch_if_newer(&Version::upstream("2.0.9"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.version.as_deref(), Some("2.1.5"));
+        std::fs::remove_file(fetched.path).unwrap();
+        assert!(pinned(">=2, <3")
+            .fetch_if_newer(&Version::upstream("2.1.5"))
+            .unwrap()
+            .is_none());
+
+        // An exact pin moves a newer installed version back
+        let fetched = pinned("=1.4.3")
+            .fetch_if_newer(&Version::new("1:2.1.5-1", Scheme::Debian))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.asset, "tool-1.4.3.deb");
+        std::fs::remove_file(fetched.path).unwrap();
+        assert!(pinned("~5.0")
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn prerelease_channel_without_releases_is_not_an_error() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/repos/owner/empty/releases")
+            .match_query(Matcher::Any)
+            .with_body("[]")
+            .create();
+
+        let fetcher = fetcher(&server, "empty", "  channel: prerelease");
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .is_none());
+        assert!(fetcher.resolve_latest().unwrap().is_none());
+    }
+
+    #[test]
+    fn strips_tag_prefix_and_suffix() {
+        let mut server = Server::new();
+        let assets = vec![asset(&server, "tool.deb", None)];
+        mock_release(&mut server, "affixed", "release/1.2.0_linux", assets);
+
+        let fetcher = fetcher(
+            &server,
+            "affixed",
+            "  version_prefix: release/\n  version_suffix: _linux",
+        );
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.2.0"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn no_matching_asset_returns_none() {
+        let mut server = Server::new();
+        let assets = vec![asset(&server, "tool.tar.gz", None)];
+        mock_release(&mut server, "nomatch", "v2.0.0", assets);
+
+        let fetcher = fetcher(&server, "nomatch", "  file_pattern: \"*.deb\"");
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn file_pattern_fallbacks_are_tried_in_order() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool-x86_64.deb", None),
+            asset(&server, "tool-x86_64.rpm", None),
+        ];
+        mock_release(&mut server, "fallback", "v2.0.0", assets);
+        server
+            .mock("GET", "/download/tool-x86_64.deb")
+            .with_body("deb")
+            .create();
+
+        let fetcher = fetcher(
+            &server,
+            "fallback",
+            "  file_pattern: [\"*_amd64.deb\", \"*-x86_64.deb\", \"*x86_64*\"]",
+        );
+        let path = fetcher
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .unwrap()
+            .path;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "deb");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn prefer_picks_highest_ranked_asset_and_its_installer() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool-x86_64.AppImage", None),
+            asset(&server, "tool_amd64.deb", None),
+        ];
+        mock_release(&mut server, "prefer", "v2.0.0", assets);
+        server
+            .mock("GET", "/download/tool_amd64.deb")
+            .with_body("deb")
+            .create();
+
+        let preferring = fetcher(
+            &server,
+            "prefer",
+            "  prefer:\n    - \"*.deb\"\n    - pattern: \"*.AppImage\"\n      installer: appimage",
+        );
+        let fetched = preferring
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&fetched.path).unwrap(), "deb");
+        assert_eq!(fetched.installer, None);
+        std::fs::remove_file(fetched.path).unwrap();
+
+        let assets = vec![asset(&server, "tool-x86_64.AppImage", None)];
+        mock_release(&mut server, "prefer-fallback", "v2.0.0", assets);
+        server
+            .mock("GET", "/download/tool-x86_64.AppImage")
+            .with_body("appimage")
+            .create();
+
+        let falling_back = fetcher(
+            &server,
+            "prefer-fallback",
+            "  prefer:\n    - \"*.deb\"\n    - pattern: \"*.AppImage\"\n      installer: appimage",
+        );
+        let fetched = falling_back
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.installer.as_deref(), Some("appimage"));
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn resolves_latest_without_downloading() {
+        let mut server = Server::new();
+        let assets = vec![asset(&server, "tool.deb", None)];
+        mock_release(&mut server, "resolve", "v3.1.0", assets);
+        let download = server
+            .mock("GET", Matcher::Regex("^/download/".into()))
+            .expect(0)
+            .create();
+
+        let resolved = fetcher(&server, "resolve", "")
+            .resolve_latest()
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved.version, "3.1.0");
+        assert_eq!(
+            resolved.url,
+            Some(format!("{}/download/tool.deb", server.url()))
+        );
+        download.assert();
+    }
+
+    #[test]
+    fn streams_asset_without_saving_it() {
+        let mut server = Server::new();
+        let download = server
+            .mock("GET", "/download/tool.rpm")
+            .with_body("data")
+            .create();
+
+        let fetcher = fetcher(&server, "stream", "");
+        let mut body = String::new();
+        fetcher
+            .open_stream(&format!("{}/download/tool.rpm", server.url()))
+            .unwrap()
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(body, "data");
+        download.assert();
+        assert!(!download_dir()
+            .unwrap()
+            .join("autopkg-stream-tool.rpm")
+            .exists());
+    }
+
+    #[test]
+    fn matches_on_label_when_configured() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "a.bin", Some("Linux arm64")),
+            asset(&server, "b.bin", Some("Linux x86_64")),
+        ];
+        mock_release(&mut server, "label", "v2.0.0", assets);
+        server
+            .mock("GET", "/download/b.bin")
+            .with_body("b")
+            .create();
+
+        let fetcher = fetcher(
+            &server,
+            "label",
+            "  file_pattern: \"*x86_64\"\n  match_field: label",
+        );
+        let path = fetcher
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .unwrap()
+            .path;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "b");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn list_assets_reports_matches() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool.deb", None),
+            asset(&server, "tool.rpm", None),
+        ];
+        mock_release(&mut server, "list", "v1.0.0", assets);
+
+        let fetcher = fetcher(&server, "list", "  file_pattern: \"*.deb\"");
+        let listed = fetcher.list_assets().unwrap();
+        let matches: Vec<_> = listed
+            .iter()
+            .map(|a| (a.name.as_str(), a.matches))
+            .collect();
+        assert_eq!(matches, [("tool.deb", true), ("tool.rpm", false)]);
+        assert_eq!(listed[0].size, 4);
+    }
+
+    #[test]
+    fn reports_archived_repo() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/repos/owner/archived")
+            .with_status(200)
+            .with_body(r#"{"full_name": "owner/archived", "archived": true}"#)
+            .create();
+        server
+            .mock("GET", "/repos/owner/active")
+            .with_status(200)
+            .with_body(r#"{"full_name": "owner/active", "archived": false}"#)
+            .create();
+
+        assert!(fetcher(&server, "archived", "").source_archived().unwrap());
+        assert!(!fetcher(&server, "active", "").source_archived().unwrap());
+    }
+
+    #[test]
+    fn sends_configured_token() {
+        let mut server = Server::new();
+        let release = server
+            .mock("GET", "/repos/owner/token/releases/latest")
+            .match_header("authorization", "Bearer secret")
+            .with_status(200)
+            .with_body(r#"{"tag_name": "v1.0.0", "assets": []}"#)
+            .create();
+
+        let fetcher = fetcher(&server, "token", "  token: secret");
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .is_none());
+        release.assert();
+    }
+
+    #[test]
+    fn downloads_from_url_template_without_token() {
+        let mut server = Server::new();
+        mock_release(&mut server, "template", "v2.0.1", vec![]);
+        let download = server
+            .mock("GET", "/dl/2.0.1/tool_v2.0.1_amd64.deb")
+            .match_header("authorization", Matcher::Missing)
+            .with_body("data")
+            .create();
+
+        let fetcher = fetcher(
+            &server,
+            "template",
+            &format!(
+                "  token: secret\n  download_url: \"{}/dl/{{version}}/tool_{{tag}}_amd64.deb\"",
+                server.url()
+            ),
+        );
+        let fetched = fetcher
+            .fetch_if_newer(&Version::upstream("2.0.0"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.version.as_deref(), Some("2.0.1"));
+        download.assert();
+        assert!(fetched
+            .path
+            .ends_with("autopkg-template-tool_v2.0.1_amd64.deb"));
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn url_template_falls_back_to_newest_tag_without_releases() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/repos/owner/tagsonly/releases/latest")
+            .with_status(404)
+            .create();
+        let tags = serde_json::json!([
+            {"name": "v2.0.0-rc.1"},
+            {"name": "v1.10.0"},
+            {"name": "v1.9.2"},
+        ]);
+        let listed = server
+            .mock("GET", "/repos/owner/tagsonly/tags")
+            .match_query(Matcher::UrlEncoded("per_page".into(), "100".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(tags.to_string())
+            .create();
+        let download = server
+            .mock("GET", "/dl/1.10.0/tool_amd64.deb")
+            .with_body("data")
+            .create();
+
+        let fetcher = fetcher(
+            &server,
+            "tagsonly",
+            &format!(
+                "  download_url: \"{}/dl/{{version}}/tool_amd64.deb\"",
+                server.url()
+            ),
+        );
+        let fetched = fetcher
+            .fetch_if_newer(&Version::upstream("1.9.2"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.version.as_deref(), Some("1.10.0"));
+        listed.assert();
+        download.assert();
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn tag_picks_that_release_instead_of_latest() {
+        let mut server = Server::new();
+        let latest = mock_release(&mut server, "tagged", "v2.0.0", Vec::new()).expect(0);
+        let body = serde_json::json!({
+            "tag_name": "release/1.4.3",
+            "assets": [asset(&server, "tool_1.4.3_amd64.deb", None)],
+        });
+        let tagged = server
+            .mock("GET", "/repos/owner/tagged/releases/tags/release%2F1.4.3")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .create();
+        let download = server
+            .mock("GET", "/download/tool_1.4.3_amd64.deb")
+            .with_body("data")
+            .create();
+
+        let fetcher = fetcher(
+            &server,
+            "tagged",
+            "  tag: release/1.4.3\n  version_prefix: release/",
+        );
+        let fetched = fetcher
+            .fetch_if_newer(&Version::upstream("1.4.0"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.version.as_deref(), Some("1.4.3"));
+        latest.assert();
+        tagged.assert();
+        download.assert();
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn missing_tag_is_an_error() {
+        let mut server = Server::new();
+        let latest = mock_release(&mut server, "notag", "v2.0.0", Vec::new()).expect(0);
+        server
+            .mock("GET", "/repos/owner/notag/releases/tags/v9.9.9")
+            .with_status(404)
+            .create();
+
+        let err = fetcher(&server, "notag", "  tag: v9.9.9")
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "No release with tag v9.9.9 in owner/notag");
+        latest.assert();
+    }
+
+    #[test]
+    fn api_error_is_reported() {
+        let mut server = Server::new();
+        let latest = server
+            .mock("GET", "/repos/owner/missing/releases/latest")
+            .with_status(404)
+            .expect(1)
+            .create();
+
+        let err = fetcher(&server, "missing", "")
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap_err();
+        assert!(err.to_string().contains("404"), "{}", err);
+        // Not retried
+        latest.assert();
+    }
+
+    #[test]
+    fn rate_limit_is_a_typed_error() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/repos/owner/limited/releases/latest")
+            .with_status(403)
+            .with_header("x-ratelimit-remaining", "0")
+            .with_header("x-ratelimit-reset", "1700000000")
+            .create();
+
+        let err = fetcher(&server, "limited", "")
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap_err();
+        let limit = err.downcast_ref::<crate::fetcher::RateLimited>().unwrap();
+        assert_eq!(limit.reset, 1700000000);
+    }
+
+    #[test]
+    fn malformed_release_is_an_error() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/repos/owner/malformed/releases/latest")
+            .with_status(200)
+            .with_body("<html>rate limited</html>")
+            .create();
+
+        assert!(fetcher(&server, "malformed", "")
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .is_err());
+    }
+
+    #[test]
+    fn failed_download_is_an_error() {
+        let mut server = Server::new();
+        let assets = vec![asset(&server, "tool.deb", None)];
+        mock_release(&mut server, "dlfail", "v2.0.0", assets);
+        let download = server
+            .mock("GET", "/download/tool.deb")
+            .with_status(500)
+            .expect(3)
+            .create();
+
+        let extra = "  retries: 2\n  retry_base_delay_ms: 1";
+        let err = fetcher(&server, "dlfail", extra)
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap_err();
+        assert!(err.to_string().contains("500"), "{}", err);
+        download.assert();
+    }
+
+    #[test]
+    fn verifies_download_against_checksums_asset() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool_amd64.deb", None),
+            asset(&server, "SHA256SUMS", None),
+        ];
+        mock_release(&mut server, "checksum", "v2.0.0", assets);
+        server
+            .mock("GET", "/download/SHA256SUMS")
+            .with_body("0000000000000000000000000000000000000000000000000000000000000000  tool_arm64.deb\n3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7 *tool_amd64.deb\n")
+            .create();
+        server
+            .mock("GET", "/download/tool_amd64.deb")
+            .with_body("data")
+            .create();
+
+        let fetched = fetcher(&server, "checksum", "  checksum_pattern: SHA256SUMS")
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&fetched.path).unwrap(), "data");
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn picks_checksum_asset_of_the_download() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool_arm64.deb", None),
+            asset(&server, "tool_amd64.deb", None),
+            asset(&server, "tool_arm64.deb.sha256", None),
+            asset(&server, "tool_amd64.deb.sha256", None),
+        ];
+        mock_release(&mut server, "persum", "v2.0.0", assets);
+        let other = server
+            .mock("GET", "/download/tool_arm64.deb.sha256")
+            .with_body("0000000000000000000000000000000000000000000000000000000000000000\n")
+            .expect(0)
+            .create();
+        server
+            .mock("GET", "/download/tool_amd64.deb.sha256")
+            .with_body("3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7\n")
+            .create();
+        server
+            .mock("GET", "/download/tool_amd64.deb")
+            .with_body("data")
+            .create();
+
+        let fetched = fetcher(
+            &server,
+            "persum",
+            "  file_pattern: \"*amd64.deb\"\n  checksum_pattern: \"*.sha256\"",
+        )
+        .fetch_if_newer(&Version::upstream("1.0.0"))
+        .unwrap()
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&fetched.path).unwrap(), "data");
+        std::fs::remove_file(fetched.path).unwrap();
+        other.assert();
+    }
+
+    #[test]
+    fn searches_every_checksum_asset_for_the_download() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool_amd64.deb", None),
+            asset(&server, "darwin.sha256sums", None),
+            asset(&server, "linux.sha256sums", None),
+        ];
+        mock_release(&mut server, "sumfiles", "v2.0.0", assets);
+        server
+            .mock("GET", "/download/darwin.sha256sums")
+            .with_body(
+                "0000000000000000000000000000000000000000000000000000000000000000  tool.pkg\n",
+            )
+            .create();
+        server
+            .mock("GET", "/download/linux.sha256sums")
+            .with_body("3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7  tool_amd64.deb\n")
+            .create();
+        server
+            .mock("GET", "/download/tool_amd64.deb")
+            .with_body("data")
+            .create();
+
+        let fetched = fetcher(
+            &server,
+            "sumfiles",
+            "  file_pattern: \"*.deb\"\n  checksum_pattern: \"*.sha256sums\"",
+        )
+        .fetch_if_newer(&Version::upstream("1.0.0"))
+        .unwrap()
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&fetched.path).unwrap(), "data");
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn checksum_mismatch_is_quarantined() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool_amd64.deb", None),
+            asset(&server, "tool_amd64.deb.sha256", None),
+        ];
+        mock_release(&mut server, "badsum", "v2.0.0", assets);
+        server
+            .mock("GET", "/download/tool_amd64.deb.sha256")
+            .with_body("3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7\n")
+            .create();
+        server
+            .mock("GET", "/download/tool_amd64.deb")
+            .with_body("tampered")
+            .create();
+
+        let err = fetcher(
+            &server,
+            "badsum",
+            "  file_pattern: \"*.deb\"\n  checksum_pattern: \"*.sha256\"",
+        )
+        .fetch_if_newer(&Version::upstream("1.0.0"))
+        .unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains(
+                "expected sha256 3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7"
+            ),
+            "{}",
+            message
+        );
+        assert!(message.contains("got "), "{}", message);
+
+        let download = download_dir()
+            .unwrap()
+            .join("autopkg-badsum-tool_amd64.deb");
+        assert_eq!(
+            err.downcast_ref::<RejectedDownload>().unwrap().path,
+            download
+        );
+        let quarantine =
+            std::env::temp_dir().join(format!("autopkg-test-quarantine-{}", std::process::id()));
+        let err = crate::quarantine::reject_fetched("tool", err, Some(&quarantine));
+        assert!(
+            format!("{:#}", err).contains("file quarantined as"),
+            "{:#}",
+            err
+        );
+        assert!(!download.exists());
+        let reasons: Vec<String> = std::fs::read_dir(&quarantine)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".reason.json"))
+            .collect();
+        assert_eq!(reasons.len(), 1, "{:?}", reasons);
+        let reason = std::fs::read_to_string(quarantine.join(&reasons[0])).unwrap();
+        assert!(reason.contains("Checksum mismatch"), "{}", reason);
+        std::fs::remove_dir_all(quarantine).unwrap();
+    }
+
+    #[test]
+    fn bad_signature_rejects_download() {
+        let mut server = Server::new();
+        let assets = vec![
+            asset(&server, "tool_amd64.deb", None),
+            asset(&server, "tool_amd64.deb.asc", None),
+        ];
+        mock_release(&mut server, "badsig", "v2.0.0", assets);
+        server
+            .mock("GET", "/download/tool_amd64.deb.asc")
+            .with_body("not a signature")
+            .create();
+        server
+            .mock("GET", "/download/tool_amd64.deb")
+            .with_body("data")
+            .create();
+        let key = std::env::temp_dir().join(format!("autopkg-test-key-{}.asc", std::process::id()));
+        std::fs::write(&key, "not a key").unwrap();
+
+        let err = fetcher(
+            &server,
+            "badsig",
+            &format!(
+                "  file_pattern: \"*.deb\"\n  signature_pattern: \"*.asc\"\n  gpg_key: {}",
+                key.display()
+            ),
+        )
+        .fetch_if_newer(&Version::upstream("1.0.0"))
+        .unwrap_err();
+        std::fs::remove_file(&key).unwrap();
+        assert!(
+            format!("{:#}", err).contains("Signature does not verify"),
+            "{:#}",
+            err
+        );
+
+        let download = download_dir()
+            .unwrap()
+            .join("autopkg-badsig-tool_amd64.deb");
+        assert!(download.exists());
+        let err = crate::quarantine::reject_fetched("tool", err, None);
+        assert!(format!("{:#}", err).contains("deleted"), "{:#}", err);
+        assert!(!download.exists());
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let app: ApplicationConfig = serde_yaml::from_str(
+            "name: test\nfetcher:\n  type: github\n  repo: no-slash\ninstaller: deb\n",
+        )
+        .unwrap();
+        assert!(GitHubFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap()
+        )
+        .is_err());
+
+        let app: ApplicationConfig = serde_yaml::from_str(
+            "name: test\nfetcher:\n  type: github\n  repo: o/r\n  match_field: size\ninstaller: deb\n",
+        )
+        .unwrap();
+        assert!(GitHubFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap()
+        )
+        .is_err());
+
+        let app: ApplicationConfig = serde_yaml::from_str(
+            "name: test\nfetcher:\n  type: github\n  repo: o/r\n  signature_pattern: \"*.asc\"\ninstaller: deb\n",
+        )
+        .unwrap();
+        let err = GitHubFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap(),
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("gpg_key"), "{}", err);
+
+        let app: ApplicationConfig = serde_yaml::from_str(
+            "name: test\nfetcher:\n  type: github\n  repo: o/r\n  tag: v1.0.0\n  channel: prerelease\ninstaller: deb\n",
+        )
+        .unwrap();
+        assert!(GitHubFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap()
+        )
+        .is_err());
     }
 }
@@ -0,0 +1,295 @@
+use crate::config::{ApplicationConfig, FetcherConfig};
+use crate::fetcher::{
+    acquire_download_permit, download_dir, file_name, github_auth, github_status_error,
+    save_response, Fetcher, HttpClient, RetryPolicy,
+};
+use crate::host;
+use crate::types::{Download, FetchResult, Resolved};
+use crate::version::Version;
+
+use anyhow::{anyhow, Context, Result};
+use glob::Pattern;
+use log::{info, warn};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use which::which;
+
+/// GitHub Actions workflow runs API response subset.
+#[derive(Debug, Deserialize)]
+struct WorkflowRuns {
+    workflow_runs: Vec<WorkflowRun>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkflowRun {
+    id: u64,
+    run_number: u64,
+    head_sha: String,
+}
+
+/// GitHub Actions run artifacts API response subset.
+#[derive(Debug, Deserialize)]
+struct Artifacts {
+    artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    name: String,
+    archive_download_url: String,
+    #[serde(default)]
+    expired: bool,
+}
+
+/// Fetcher for build artifacts of the latest successful GitHub Actions workflow run.
+///
+/// The run number is used as the version. Artifacts are always zip archives, so
+/// the file handed to the installer is extracted from it.
+pub struct GitHubActionsFetcher {
+    owner: String,
+    repo: String,
+    workflow: String,
+    branch: Option<String>,
+    artifact_patterns: Vec<Pattern>,
+    file_pattern: Option<Pattern>,
+    auth: HeaderValue,
+    client: Client,
+    retry: RetryPolicy,
+}
+
+impl GitHubActionsFetcher {
+    pub fn new(config: &FetcherConfig, app: &ApplicationConfig, http: &HttpClient) -> Result<Self> {
+        if app.version.is_some() {
+            return Err(anyhow!(
+                "GitHub Actions fetcher versions are run numbers; `version` requirements are not supported"
+            ));
+        }
+        let repo_str = config
+            .repo
+            .as_ref()
+            .ok_or_else(|| anyhow!("GitHub Actions fetcher requires `repo` field"))?;
+        let (owner, repo) = repo_str
+            .split_once('/')
+            .ok_or_else(|| anyhow!("GitHub repo must be in form `owner/repo`"))?;
+        let workflow = config
+            .workflow
+            .clone()
+            .ok_or_else(|| anyhow!("GitHub Actions fetcher requires `workflow` field"))?;
+
+        let artifact_patterns = config
+            .file_patterns()
+            .into_iter()
+            .map(|p| {
+                let p = host::substitute(p)?;
+                Pattern::new(&p).with_context(|| format!("Invalid glob pattern: {}", p))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let file_pattern = config
+            .artifact
+            .as_deref()
+            .map(|p| Pattern::new(p).with_context(|| format!("Invalid glob pattern: {}", p)))
+            .transpose()?;
+
+        // Artifact downloads always require authentication
+        let auth = github_auth(config)?.ok_or_else(|| {
+            anyhow!("GitHub Actions fetcher requires a token (`token`, `token_env`, `settings.github_token` or GITHUB_TOKEN)")
+        })?;
+
+        let client = http.for_fetcher(config, None)?;
+
+        Ok(Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            workflow,
+            branch: config.branch.clone(),
+            artifact_patterns,
+            file_pattern,
+            auth,
+            client,
+            retry: RetryPolicy::from_config(config),
+        })
+    }
+
+    fn get<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<T> {
+        info!("GitHubActionsFetcher: querying {}", url);
+        let resp = self.retry.send(
+            self.client
+                .get(url)
+                .header(AUTHORIZATION, self.auth.clone()),
+        )?;
+        if !resp.status().is_success() {
+            return Err(github_status_error(&resp));
+        }
+        Ok(resp.json()?)
+    }
+
+    fn latest_successful_run(&self) -> Result<Option<WorkflowRun>> {
+        let mut url = format!(
+            "https://api.github.com/repos/{}/{}/actions/workflows/{}/runs?status=success&per_page=1",
+            self.owner, self.repo, self.workflow
+        );
+        if let Some(branch) = &self.branch {
+            url.push_str(&format!("&branch={}", branch));
+        }
+        let runs: WorkflowRuns = self.get(&url)?;
+        Ok(runs.workflow_runs.into_iter().next())
+    }
+
+    fn download_artifact(&self, run: &WorkflowRun, artifact: &Artifact) -> Result<PathBuf> {
+        let _permit = acquire_download_permit();
+        let mut resp = self.retry.send(
+            self.client
+                .get(&artifact.archive_download_url)
+                .header(AUTHORIZATION, self.auth.clone()),
+        )?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Failed to download artifact from {}: status {}",
+                artifact.archive_download_url,
+                resp.status()
+            ));
+        }
+
+        let path = download_dir()?.join(format!(
+            "autopkg-{}-{}-{}.zip",
+            self.repo, artifact.name, run.run_number
+        ));
+        save_response(&mut resp, &path)?;
+
+        info!("Downloaded artifact to {}", path.display());
+        Ok(path)
+    }
+
+    /// Extract the zip and return the file matching `artifact` (or the only file).
+    fn extract(&self, zip_path: &Path, run: &WorkflowRun) -> Result<PathBuf> {
+        if which("unzip").is_err() {
+            return Err(anyhow!(
+                "unzip not found in PATH; required to extract GitHub Actions artifacts"
+            ));
+        }
+
+        let dest = download_dir()?.join(format!("autopkg-{}-run-{}", self.repo, run.run_number));
+        if dest.exists() {
+            fs::remove_dir_all(&dest)
+                .with_context(|| format!("Failed to clean {}", dest.display()))?;
+        }
+        let status = Command::new("unzip")
+            .arg("-q")
+            .arg("-o")
+            .arg(zip_path)
+            .arg("-d")
+            .arg(&dest)
+            .status()
+            .context("Failed to run unzip")?;
+        if !status.success() {
+            return Err(anyhow!(
+                "unzip {} failed with status {}",
+                zip_path.display(),
+                status
+            ));
+        }
+
+        let mut files: Vec<PathBuf> = fs::read_dir(&dest)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .filter(|p| match &self.file_pattern {
+                Some(pattern) => p
+                    .file_name()
+                    .is_some_and(|n| pattern.matches(&n.to_string_lossy())),
+                None => true,
+            })
+            .collect();
+        files.sort();
+
+        match (files.len(), &self.file_pattern) {
+            (0, _) => Err(anyhow!(
+                "Artifact {} contains no matching file",
+                zip_path.display()
+            )),
+            (1, _) | (_, Some(_)) => Ok(files.remove(0)),
+            (_, None) => Err(anyhow!(
+                "Artifact {} contains several files; set `artifact` to choose one",
+                zip_path.display()
+            )),
+        }
+    }
+}
+
+impl Fetcher for GitHubActionsFetcher {
+    /// The run number of the latest successful run, which serves as the
+    /// version. There is no URL: artifacts need the API's download redirect.
+    fn resolve_latest(&self) -> Result<Option<Resolved>> {
+        Ok(self.latest_successful_run()?.map(|run| Resolved {
+            version: run.run_number.to_string(),
+            url: None,
+        }))
+    }
+
+    fn fetch_if_newer(&self, current_version: &Version) -> FetchResult {
+        let run = match self.latest_successful_run()? {
+            Some(run) => run,
+            None => {
+                warn!(
+                    "GitHubActionsFetcher: no successful run of workflow {}",
+                    self.workflow
+                );
+                return Ok(None);
+            }
+        };
+
+        let latest_version = run.run_number.to_string();
+        info!(
+            "GitHubActionsFetcher: latest successful run #{} (commit {}), current={}",
+            run.run_number, run.head_sha, current_version
+        );
+
+        if !current_version.is_older_than(&Version::upstream(&latest_version)) {
+            info!("GitHubActionsFetcher: no newer run available");
+            return Ok(None);
+        }
+
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/actions/runs/{}/artifacts",
+            self.owner, self.repo, run.id
+        );
+        let artifacts: Artifacts = self.get(&url)?;
+        let artifact = self.artifact_patterns.iter().find_map(|pattern| {
+            artifacts
+                .artifacts
+                .iter()
+                .find(|a| !a.expired && pattern.matches(&a.name))
+        });
+        let artifact = match artifact {
+            Some(a) => a,
+            None => {
+                let patterns: Vec<String> = self
+                    .artifact_patterns
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect();
+                warn!(
+                    "GitHubActionsFetcher: no artifact matching pattern '{}' found",
+                    patterns.join("', '")
+                );
+                return Ok(None);
+            }
+        };
+
+        info!(
+            "GitHubActionsFetcher: selected artifact '{}'",
+            artifact.name
+        );
+        let zip_path = self.download_artifact(&run, artifact)?;
+        let path = self.extract(&zip_path, &run)?;
+        Ok(Some(Download {
+            asset: file_name(&path),
+            path,
+            version: Some(latest_version),
+            installer: None,
+        }))
+    }
+}
@@ -0,0 +1,497 @@
+use crate::config::{ApplicationConfig, FetcherConfig};
+use crate::fetcher::{
+    acquire_download_permit, download_dir, save_response, Fetcher, HttpClient, RetryPolicy,
+};
+use crate::host;
+use crate::types::{AssetInfo, Download, FetchResult, Resolved};
+use crate::version::{compare, should_replace, TagFormat, Version, VersionRequirement};
+
+use anyhow::{anyhow, Context, Result};
+use glob::Pattern;
+use log::{info, warn};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Default GitLab instance.
+const GITLAB_URL: &str = "https://gitlab.com";
+
+/// GitLab releases API response subset.
+#[derive(Debug, Deserialize)]
+struct GitLabRelease {
+    tag_name: String,
+    #[serde(default)]
+    assets: GitLabAssets,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct GitLabAssets {
+    #[serde(default)]
+    links: Vec<GitLabLink>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabLink {
+    name: String,
+    url: String,
+    #[serde(default)]
+    direct_asset_url: Option<String>,
+    #[serde(default)]
+    link_type: Option<String>,
+}
+
+impl GitLabLink {
+    /// The permanent `/-/releases/<tag>/downloads/...` URL if there is one, else the link's target.
+    fn download_url(&self) -> &str {
+        self.direct_asset_url.as_deref().unwrap_or(&self.url)
+    }
+}
+
+/// GitLab-based fetcher: release asset links of a project on gitlab.com or a
+/// self-hosted instance.
+pub struct GitLabFetcher {
+    /// Project path (`group/project`) or numeric ID
+    project: String,
+    file_patterns: Vec<Pattern>,
+    /// The application's `version` requirement releases must satisfy
+    requirement: Option<VersionRequirement>,
+    tag_format: TagFormat,
+    client: Client,
+    retry: RetryPolicy,
+    token: Option<HeaderValue>,
+    base_url: Url,
+}
+
+impl GitLabFetcher {
+    pub fn new(config: &FetcherConfig, app: &ApplicationConfig, http: &HttpClient) -> Result<Self> {
+        let project = config
+            .repo
+            .clone()
+            .ok_or_else(|| anyhow!("GitLab fetcher requires `repo` field"))?;
+        if !project.contains('/') && !project.chars().all(|c| c.is_ascii_digit()) {
+            return Err(anyhow!(
+                "GitLab repo must be in form `group/project` (or a numeric project ID)"
+            ));
+        }
+
+        let base_str = config.base_url.as_deref().unwrap_or(GITLAB_URL);
+        let base_url = Url::parse(base_str)
+            .ok()
+            .filter(|u| matches!(u.scheme(), "http" | "https") && u.has_host())
+            .ok_or_else(|| {
+                anyhow!(
+                    "GitLab base_url must be an http(s) URL such as `https://gitlab.example.com`, got `{}`",
+                    base_str
+                )
+            })?;
+
+        let file_patterns = config
+            .file_patterns()
+            .into_iter()
+            .map(|p| {
+                let p = host::substitute(p)?;
+                Pattern::new(&p).with_context(|| format!("Invalid glob pattern: {}", p))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let client = http.for_fetcher(config, None)?;
+
+        Ok(Self {
+            project,
+            file_patterns,
+            requirement: VersionRequirement::from_app(app)?,
+            tag_format: TagFormat::from_config(config),
+            client,
+            retry: RetryPolicy::from_config(config),
+            token: gitlab_token(config)?,
+            base_url,
+        })
+    }
+
+    /// GET `url`, with the `PRIVATE-TOKEN` header if a token is configured.
+    fn get(&self, url: &str) -> RequestBuilder {
+        let request = self.client.get(url);
+        match &self.token {
+            Some(token) => request.header("PRIVATE-TOKEN", token.clone()),
+            None => request,
+        }
+    }
+
+    /// GET a download `url`. Links may point outside the instance, so the token
+    /// is only sent to the instance's own host.
+    fn download_request(&self, url: &str) -> RequestBuilder {
+        let same_host = Url::parse(url).is_ok_and(|u| {
+            u.host_str() == self.base_url.host_str()
+                && u.port_or_known_default() == self.base_url.port_or_known_default()
+        });
+        if same_host {
+            self.get(url)
+        } else {
+            self.client.get(url)
+        }
+    }
+
+    /// URL of the project's releases endpoint; the project path is encoded as a
+    /// single segment (`group%2Fproject`), as the API requires.
+    fn releases_url(&self) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("base_url is an http(s) URL")
+            .pop_if_empty()
+            .extend(["api", "v4", "projects", &self.project, "releases"]);
+        url
+    }
+
+    /// Newest release; the API lists releases by release date, newest first.
+    /// With a `version` requirement, the newest release satisfying it among the
+    /// 100 most recent ones.
+    fn latest_release(&self) -> Result<GitLabRelease> {
+        let url = self.releases_url();
+        info!("GitLabFetcher: querying {}", url);
+
+        let per_page = if self.requirement.is_some() {
+            "100"
+        } else {
+            "1"
+        };
+        let resp = self
+            .retry
+            .send(self.get(url.as_str()).query(&[("per_page", per_page)]))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "GitLab API returned non-success status: {}",
+                resp.status()
+            ));
+        }
+
+        let releases: Vec<GitLabRelease> = resp.json()?;
+        if let Some(requirement) = &self.requirement {
+            let version = |r: &GitLabRelease| self.tag_format.version(&r.tag_name);
+            return releases
+                .into_iter()
+                .filter(|r| requirement.matches(&version(r)))
+                .max_by(|a, b| compare(&version(a), &version(b)))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "GitLab project {} has no release satisfying version {}",
+                        self.project,
+                        requirement
+                    )
+                });
+        }
+        releases
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("GitLab project {} has no releases", self.project))
+    }
+
+    /// The first link matching the first pattern (in configured order) that
+    /// matches anything.
+    fn select_link(&self, links: Vec<GitLabLink>) -> Option<GitLabLink> {
+        for pattern in &self.file_patterns {
+            if let Some(i) = links.iter().position(|l| pattern.matches(&l.name)) {
+                info!("GitLabFetcher: pattern '{}' matched", pattern);
+                return links.into_iter().nth(i);
+            }
+        }
+        None
+    }
+
+    fn download_link(&self, link: &GitLabLink) -> Result<PathBuf> {
+        let url = link.download_url();
+        let _permit = acquire_download_permit();
+        let mut resp = self.retry.send(self.download_request(url))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Failed to download asset from {}: status {}",
+                url,
+                resp.status()
+            ));
+        }
+
+        let project = self.project.rsplit('/').next().unwrap_or(&self.project);
+        let path = download_dir()?.join(format!("autopkg-{}-{}", project, link.name));
+        save_response(&mut resp, &path)?;
+
+        info!("Downloaded asset to {}", path.display());
+        Ok(path)
+    }
+}
+
+/// Resolve the GitLab API token for a fetcher: its `token`, its `token_env`
+/// variable (which must be set), then the `GITLAB_TOKEN` environment variable.
+fn gitlab_token(config: &FetcherConfig) -> Result<Option<HeaderValue>> {
+    let token = if let Some(token) = &config.token {
+        Some(token.clone())
+    } else if let Some(var) = &config.token_env {
+        Some(
+            std::env::var(var)
+                .map_err(|_| anyhow!("Environment variable {} (token_env) is not set", var))?,
+        )
+    } else {
+        std::env::var("GITLAB_TOKEN").ok()
+    };
+
+    token
+        .map(|token| {
+            let mut value = HeaderValue::from_str(&token).context("Invalid GitLab token")?;
+            value.set_sensitive(true);
+            Ok(value)
+        })
+        .transpose()
+}
+
+impl Fetcher for GitLabFetcher {
+    fn list_assets(&self) -> Result<Vec<AssetInfo>> {
+        let release = self.latest_release()?;
+        info!("GitLabFetcher: latest release tag={}", release.tag_name);
+
+        Ok(release
+            .assets
+            .links
+            .iter()
+            .map(|l| AssetInfo {
+                name: l.name.clone(),
+                size: 0,
+                content_type: l.link_type.clone().unwrap_or_default(),
+                matches: self.file_patterns.iter().any(|p| p.matches(&l.name)),
+            })
+            .collect())
+    }
+
+    fn resolve_latest(&self) -> Result<Option<Resolved>> {
+        let release = self.latest_release()?;
+        let version = self.tag_format.version(&release.tag_name);
+        let url = self
+            .select_link(release.assets.links)
+            .map(|l| l.download_url().to_string());
+        Ok(Some(Resolved { version, url }))
+    }
+
+    fn fetch_if_newer(&self, current_version: &Version) -> FetchResult {
+        let release = self.latest_release()?;
+
+        let latest_tag = release.tag_name.clone();
+        let latest_version = self.tag_format.version(&latest_tag);
+
+        info!(
+            "GitLabFetcher: latest tag={}, normalized={}, current={}",
+            latest_tag, latest_version, current_version
+        );
+
+        if !should_replace(current_version, &latest_version, self.requirement.as_ref()) {
+            info!("GitLabFetcher: no newer version available");
+            return Ok(None);
+        }
+
+        info!(
+            "GitLabFetcher: version {} available to replace {}",
+            latest_version, current_version
+        );
+
+        let link = match self.select_link(release.assets.links) {
+            Some(link) => link,
+            None => {
+                let patterns: Vec<String> =
+                    self.file_patterns.iter().map(|p| p.to_string()).collect();
+                warn!(
+                    "GitLabFetcher: no asset link matching pattern '{}' found",
+                    patterns.join("', '")
+                );
+                return Ok(None);
+            }
+        };
+
+        info!(
+            "GitLabFetcher: selected asset '{}' ({})",
+            link.name,
+            link.download_url()
+        );
+
+        let path = self.download_link(&link)?;
+        Ok(Some(Download {
+            path,
+            version: Some(latest_version),
+            asset: link.name,
+            installer: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server, ServerGuard};
+
+    /// Build a fetcher for `repo` from the YAML `fetcher` fields, pointed at `server`.
+    fn fetcher(server: &ServerGuard, repo: &str, extra: &str) -> GitLabFetcher {
+        let app: ApplicationConfig = serde_yaml::from_str(&format!(
+            "name: test\nfetcher:\n  type: gitlab\n  repo: {}\n  base_url: {}\n{}\ninstaller: deb\n",
+            repo,
+            server.url(),
+            extra
+        ))
+        .unwrap();
+        GitLabFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn mock_releases(
+        server: &mut ServerGuard,
+        path: &str,
+        body: serde_json::Value,
+    ) -> mockito::Mock {
+        server
+            .mock("GET", path)
+            .match_query(Matcher::UrlEncoded("per_page".into(), "1".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .create()
+    }
+
+    #[test]
+    fn downloads_matching_link_when_newer() {
+        let mut server = Server::new();
+        let url = server.url();
+        let releases = mock_releases(
+            &mut server,
+            "/api/v4/projects/group%2Ftool/releases",
+            serde_json::json!([{
+                "tag_name": "v2.1.0",
+                "assets": { "links": [
+                    { "name": "tool_2.1.0_arm64.deb", "url": format!("{}/arm64", url) },
+                    {
+                        "name": "tool_2.1.0_amd64.deb",
+                        "url": format!("{}/other", url),
+                        "direct_asset_url": format!("{}/group/tool/-/releases/v2.1.0/downloads/tool_amd64.deb", url)
+                    }
+                ]}
+            }]),
+        );
+        let download = server
+            .mock(
+                "GET",
+                "/group/tool/-/releases/v2.1.0/downloads/tool_amd64.deb",
+            )
+            .with_body("data")
+            .create();
+
+        let fetched = fetcher(&server, "group/tool", "  file_pattern: \"*_amd64.deb\"")
+            .fetch_if_newer(&Version::upstream("2.0.0"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.version.as_deref(), Some("2.1.0"));
+        assert_eq!(fetched.asset, "tool_2.1.0_amd64.deb");
+        releases.assert();
+        download.assert();
+        assert_eq!(std::fs::read_to_string(&fetched.path).unwrap(), "data");
+        std::fs::remove_file(fetched.path).unwrap();
+    }
+
+    #[test]
+    fn skips_download_when_up_to_date() {
+        let mut server = Server::new();
+        mock_releases(
+            &mut server,
+            "/api/v4/projects/group%2Fcurrent/releases",
+            serde_json::json!([{ "tag_name": "v1.0.0", "assets": { "links": [] } }]),
+        );
+
+        assert!(fetcher(&server, "group/current", "")
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn encodes_nested_project_under_base_path() {
+        let mut server = Server::new();
+        let releases = mock_releases(
+            &mut server,
+            "/gitlab/api/v4/projects/a%2Fb%2Fc/releases",
+            serde_json::json!([{ "tag_name": "3.0", "assets": { "links": [] } }]),
+        );
+        let app: ApplicationConfig = serde_yaml::from_str(&format!(
+            "name: test\nfetcher:\n  type: gitlab\n  repo: a/b/c\n  base_url: {}/gitlab/\ninstaller: deb\n",
+            server.url()
+        ))
+        .unwrap();
+
+        let resolved = GitLabFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap(),
+        )
+        .unwrap()
+        .resolve_latest()
+        .unwrap()
+        .unwrap();
+        assert_eq!(resolved.version, "3.0");
+        assert_eq!(resolved.url, None);
+        releases.assert();
+    }
+
+    #[test]
+    fn sends_token_to_instance_only() {
+        let mut server = Server::new();
+        let releases = server
+            .mock("GET", "/api/v4/projects/group%2Fprivate/releases")
+            .match_query(Matcher::Any)
+            .match_header("private-token", "secret")
+            .with_status(200)
+            .with_body(r#"[{"tag_name": "v1.0.0"}]"#)
+            .create();
+
+        let fetcher = fetcher(&server, "group/private", "  token: secret");
+        assert!(fetcher
+            .fetch_if_newer(&Version::upstream("1.0.0"))
+            .unwrap()
+            .is_none());
+        releases.assert();
+        assert!(fetcher
+            .download_request("https://downloads.example.com/tool.deb")
+            .build()
+            .unwrap()
+            .headers()
+            .get("private-token")
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let parse = |fields: &str| -> ApplicationConfig {
+            serde_yaml::from_str(&format!(
+                "name: test\nfetcher:\n  type: gitlab\n{}\ninstaller: deb\n",
+                fields
+            ))
+            .unwrap()
+        };
+
+        let missing_repo = parse("  file_pattern: \"*\"");
+        let err = GitLabFetcher::new(
+            &missing_repo.fetcher,
+            &missing_repo,
+            &HttpClient::new(&Default::default()).unwrap(),
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("requires `repo`"));
+
+        let bad_base = parse("  repo: group/tool\n  base_url: gitlab.example.com");
+        let err = GitLabFetcher::new(
+            &bad_base.fetcher,
+            &bad_base,
+            &HttpClient::new(&Default::default()).unwrap(),
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("base_url must be an http(s) URL"));
+    }
+}
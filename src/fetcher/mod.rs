@@ -1,20 +1,1027 @@
+pub mod apt;
+pub mod crates;
+pub mod git;
 pub mod github;
+pub mod github_actions;
+pub mod gitlab;
+pub mod url;
 
-use crate::config::{ApplicationConfig, FetcherConfig};
-use crate::types::FetchResult;
-use anyhow::{anyhow, Result};
+use crate::cache;
+use crate::config::{ApplicationConfig, FetcherConfig, HttpConfig};
+use crate::lock;
+use crate::types::{AssetInfo, FetchResult, Resolved};
+use crate::version::Version;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::Certificate;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{copy, IsTerminal, Read, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
 
 /// Trait for fetching updates from a source.
 pub trait Fetcher {
     /// If a newer version than `current_version` is available, downloads it and
-    /// returns the local path. Otherwise, returns `Ok(None)`.
-    fn fetch_if_newer(&self, current_version: &str) -> FetchResult;
+    /// returns the local path with the resolved version. Otherwise, returns `Ok(None)`.
+    fn fetch_if_newer(&self, current_version: &Version) -> FetchResult;
+
+    /// Like `fetch_if_newer`, but using only data already available locally (no
+    /// network access). Returns `None` if this fetcher can't work offline.
+    fn fetch_cached(&self, _current_version: &Version) -> Option<FetchResult> {
+        None
+    }
+
+    /// List the assets of the latest release and whether each matches the
+    /// configured pattern, for diagnostics.
+    fn list_assets(&self) -> Result<Vec<AssetInfo>> {
+        Err(anyhow!("This fetcher does not support listing assets"))
+    }
+
+    /// Whether the upstream source has been archived and will see no further
+    /// releases. Fetchers that can't tell report `false`.
+    fn source_archived(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Report the latest available version (and asset URL, where the fetcher
+    /// can tell) without downloading anything or writing locally. Used with
+    /// installers that fetch by themselves, and by `status`.
+    fn resolve_latest(&self) -> Result<Option<Resolved>> {
+        Err(anyhow!(
+            "This fetcher does not support version-only lookups"
+        ))
+    }
+
+    /// Whether `open_stream` can read the asset URLs reported by `resolve_latest`.
+    fn supports_stream(&self) -> bool {
+        false
+    }
+
+    /// Open the asset at `url` for reading, for installing without saving it to
+    /// a file first.
+    fn open_stream(&self, _url: &str) -> Result<Box<dyn Read>> {
+        Err(anyhow!("This fetcher does not support streaming downloads"))
+    }
 }
 
 /// Factory for fetchers.
-pub fn create_fetcher(config: &FetcherConfig, app: &ApplicationConfig) -> Result<Box<dyn Fetcher>> {
+pub fn create_fetcher(
+    config: &FetcherConfig,
+    app: &ApplicationConfig,
+    http: &HttpClient,
+) -> Result<Box<dyn Fetcher>> {
+    check_fields(config, app)?;
     match config.r#type.as_str() {
-        "github" => Ok(Box::new(github::GitHubFetcher::new(config, app)?)),
+        "github" => Ok(Box::new(github::GitHubFetcher::new(config, app, http)?)),
+        "github-actions" => Ok(Box::new(github_actions::GitHubActionsFetcher::new(
+            config, app, http,
+        )?)),
+        "gitlab" => Ok(Box::new(gitlab::GitLabFetcher::new(config, app, http)?)),
+        "git" => Ok(Box::new(git::GitFetcher::new(config, app)?)),
+        "crates" => Ok(Box::new(crates::CratesFetcher::new(config, app, http)?)),
+        "url" => Ok(Box::new(url::UrlFetcher::new(config, app, http)?)),
+        "apt" => Ok(Box::new(apt::AptFetcher::new(config, app, http)?)),
         other => Err(anyhow!("Unknown fetcher type: {}", other)),
     }
 }
+
+/// Reject fetcher fields that the configured fetcher type ignores, which are
+/// usually copy-paste mistakes (e.g. `repo` on a `git` fetcher).
+pub fn check_fields(config: &FetcherConfig, app: &ApplicationConfig) -> Result<()> {
+    const HOSTED: &[&str] = &["github", "github-actions", "gitlab"];
+    const HTTP: &[&str] = &["github", "github-actions", "gitlab", "crates", "url", "apt"];
+    let fields: [(&str, bool, &[&str]); 32] = [
+        ("repo", config.repo.is_some(), HOSTED),
+        ("file_pattern", !config.file_pattern.is_empty(), HOSTED),
+        ("prefer", !config.prefer.is_empty(), &["github"]),
+        ("download_url", config.download_url.is_some(), &["github"]),
+        ("channel", config.channel.is_some(), &["github"]),
+        ("tag", config.tag.is_some(), &["github"]),
+        (
+            "checksum_pattern",
+            config.checksum_pattern.is_some(),
+            &["github"],
+        ),
+        (
+            "signature_pattern",
+            config.signature_pattern.is_some(),
+            &["github"],
+        ),
+        ("gpg_key", config.gpg_key.is_some(), &["github"]),
+        ("match_field", config.match_field.is_some(), &["github"]),
+        ("version_from", config.version_from.is_some(), &["github"]),
+        (
+            "asset_version_regex",
+            config.asset_version_regex.is_some(),
+            &["github"],
+        ),
+        (
+            "version_prefix",
+            config.version_prefix.is_some(),
+            &["github", "gitlab", "git", "url"],
+        ),
+        (
+            "version_suffix",
+            config.version_suffix.is_some(),
+            &["github", "gitlab", "git", "url"],
+        ),
+        ("workflow", config.workflow.is_some(), &["github-actions"]),
+        ("branch", config.branch.is_some(), &["github-actions"]),
+        ("token", config.token.is_some(), HOSTED),
+        ("token_env", config.token_env.is_some(), HOSTED),
+        ("base_url", config.base_url.is_some(), &["gitlab"]),
+        ("url", config.url.is_some(), &["git", "url"]),
+        ("version_url", config.version_url.is_some(), &["url"]),
+        (
+            "stable_version_url",
+            config.stable_version_url.is_some(),
+            &["apt"],
+        ),
+        ("timeout", config.timeout.is_some(), &["url"]),
+        ("retries", config.retries.is_some(), HTTP),
+        (
+            "retry_base_delay_ms",
+            config.retry_base_delay_ms.is_some(),
+            HTTP,
+        ),
+        ("ca_cert", config.ca_cert.is_some(), HTTP),
+        ("tag_pattern", config.tag_pattern.is_some(), &["git"]),
+        ("build_command", config.build_command.is_some(), &["git"]),
+        (
+            "artifact",
+            config.artifact.is_some(),
+            &["git", "github-actions"],
+        ),
+        ("verify_tag", config.verify_tag.is_some(), &["git"]),
+        ("gpg_home", config.gpg_home.is_some(), &["git"]),
+        (
+            "trusted_signers",
+            config.trusted_signers.is_some(),
+            &["git"],
+        ),
+    ];
+
+    let known = [
+        "github",
+        "github-actions",
+        "gitlab",
+        "git",
+        "crates",
+        "url",
+        "apt",
+    ];
+    if !known.contains(&config.r#type.as_str()) {
+        return Ok(());
+    }
+    for (field, set, types) in fields {
+        if set && !types.contains(&config.r#type.as_str()) {
+            return Err(anyhow!(
+                "{} is not valid for fetcher type '{}' in app {} (it applies to: {})",
+                field,
+                config.r#type,
+                app.name,
+                types.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The GitHub API refused a request because of rate limiting.
+#[derive(Debug, thiserror::Error)]
+#[error("GitHub API rate limit exceeded; resets at unix time {reset}")]
+pub struct RateLimited {
+    /// Unix time at which requests are allowed again
+    pub reset: u64,
+}
+
+/// A download that failed its checksum or signature check. Attached as context
+/// to the error, so the caller can quarantine the file at `path`.
+#[derive(Debug, thiserror::Error)]
+#[error("Refusing {name}")]
+pub struct RejectedDownload {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Fetchers whose checks go through the GitHub API (and are subject to its rate limit).
+pub fn uses_github_api(config: &FetcherConfig) -> bool {
+    matches!(config.r#type.as_str(), "github" | "github-actions")
+}
+
+/// Error for a non-success GitHub API response: [`RateLimited`] for rate-limit
+/// responses (403/429 with no remaining quota, or with `retry-after`), otherwise
+/// a plain status error.
+pub fn github_status_error(resp: &Response) -> anyhow::Error {
+    let status = resp.status().as_u16();
+    let header = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+    if status == 403 || status == 429 {
+        if let Some(retry_after) = header("retry-after") {
+            return RateLimited {
+                reset: crate::state::now() + retry_after,
+            }
+            .into();
+        }
+        if header("x-ratelimit-remaining") == Some(0) {
+            return RateLimited {
+                reset: header("x-ratelimit-reset").unwrap_or_else(|| crate::state::now() + 60),
+            }
+            .into();
+        }
+    }
+    anyhow!("GitHub API returned non-success status: {}", resp.status())
+}
+
+/// A file of cached HTTP responses, by key, which fetchers revalidate with
+/// conditional requests; with `refresh` (`--refresh`), cached responses are
+/// ignored (and replaced).
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    pub path: PathBuf,
+    pub refresh: bool,
+}
+
+impl ResponseCache {
+    pub fn new(path: &Path, refresh: bool) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            refresh,
+        }
+    }
+
+    /// The cached responses. A missing or unreadable cache is empty, so
+    /// requests fall back to fetching the response in full.
+    fn load<T: DeserializeOwned>(&self) -> BTreeMap<String, T> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return BTreeMap::new(),
+            Err(e) => {
+                warn!(
+                    "Failed to read response cache {}: {}",
+                    self.path.display(),
+                    e
+                );
+                return BTreeMap::new();
+            }
+        };
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            warn!(
+                "Ignoring invalid response cache {}: {}",
+                self.path.display(),
+                e
+            );
+            BTreeMap::new()
+        })
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        if self.refresh {
+            return None;
+        }
+        self.load().remove(key)
+    }
+
+    /// Store the response for `key`. Failures only cost a full request next
+    /// time, so they are logged, not returned.
+    pub fn set<T: Serialize + DeserializeOwned>(&self, key: &str, entry: T) {
+        let result = (|| {
+            let _lock = lock::lock(&self.path)?;
+            let mut entries: BTreeMap<String, T> = self.load();
+            entries.insert(key.to_string(), entry);
+            lock::write_atomically(&self.path, serde_json::to_string(&entries)?.as_bytes())
+        })();
+        if let Err(e) = result {
+            warn!(
+                "Failed to update response cache {}: {:#}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+static DEFAULT_GITHUB_TOKEN: Mutex<Option<String>> = Mutex::new(None);
+
+/// Set the token used by GitHub fetchers that don't configure their own
+/// (`settings.github_token`).
+pub fn set_default_github_token(token: Option<String>) {
+    *DEFAULT_GITHUB_TOKEN.lock().unwrap() = token;
+}
+
+/// Resolve the GitHub API token for a fetcher, as an `Authorization` header value.
+///
+/// In order: the fetcher's `token`, its `token_env` variable, `settings.github_token`,
+/// then the `GITHUB_TOKEN` environment variable. An unset `token_env` variable is
+/// warned about and falls through to the next source.
+pub fn github_auth(config: &FetcherConfig) -> Result<Option<HeaderValue>> {
+    let from_env = || {
+        let var = config.token_env.as_ref()?;
+        let token = std::env::var(var).ok();
+        if token.is_none() {
+            warn!(
+                "Environment variable {} (token_env) is not set; falling back to settings.github_token or GITHUB_TOKEN",
+                var
+            );
+        }
+        token
+    };
+    let token = config
+        .token
+        .clone()
+        .or_else(from_env)
+        .or_else(|| DEFAULT_GITHUB_TOKEN.lock().unwrap().clone())
+        .or_else(|| std::env::var("GITHUB_TOKEN").ok());
+
+    token
+        .map(|token| {
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .context("Invalid GitHub token")?;
+            value.set_sensitive(true);
+            Ok(value)
+        })
+        .transpose()
+}
+
+/// Last path segment of a download URL (without query or fragment), if any.
+pub fn url_file_name(url: &str) -> Option<&str> {
+    url.split(['?', '#'])
+        .next()
+        .and_then(|u| u.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+}
+
+/// File name of a local path, for [`Download::asset`](crate::types::Download::asset).
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Suffix of files still being downloaded.
+pub const PARTIAL_SUFFIX: &str = ".part";
+
+/// Writer that hashes everything written through it.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Width of the progress bar, in characters.
+const PROGRESS_BAR_WIDTH: usize = 30;
+/// Minimum time between redraws of the progress bar.
+const PROGRESS_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// Percentage steps at which progress is logged when stderr isn't a terminal.
+const PROGRESS_LOG_STEP_PERCENT: u64 = 10;
+/// Bytes between progress logs when stderr isn't a terminal and the size is unknown.
+const PROGRESS_LOG_STEP_BYTES: u64 = 10 * 1024 * 1024;
+/// Downloads smaller than this are not logged step by step.
+const PROGRESS_LOG_MIN_BYTES: u64 = 1024 * 1024;
+
+/// Download progress: a bar redrawn on stderr when it is a terminal, else
+/// periodic log lines, so journal entries show that a slow download is alive.
+struct Progress {
+    name: String,
+    total: Option<u64>,
+    done: u64,
+    tty: bool,
+    last_draw: Option<Instant>,
+    /// Last logged step (percent, or bytes when `total` is unknown)
+    last_logged: u64,
+}
+
+impl Progress {
+    fn new(name: String, total: Option<u64>) -> Self {
+        Self {
+            name,
+            total,
+            done: 0,
+            tty: std::io::stderr().is_terminal(),
+            last_draw: None,
+            last_logged: 0,
+        }
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.done += bytes as u64;
+        if self.tty {
+            if self
+                .last_draw
+                .is_none_or(|t| t.elapsed() >= PROGRESS_REDRAW_INTERVAL)
+            {
+                self.draw();
+            }
+            return;
+        }
+
+        match self.total {
+            Some(total) if total >= PROGRESS_LOG_MIN_BYTES => {
+                let step =
+                    self.done * 100 / total / PROGRESS_LOG_STEP_PERCENT * PROGRESS_LOG_STEP_PERCENT;
+                if step > self.last_logged && step < 100 {
+                    self.last_logged = step;
+                    info!(
+                        "Downloading {}: {}% ({} of {})",
+                        self.name,
+                        step,
+                        crate::report::format_bytes(self.done),
+                        crate::report::format_bytes(total)
+                    );
+                }
+            }
+            Some(_) => {}
+            None => {
+                let step = self.done / PROGRESS_LOG_STEP_BYTES * PROGRESS_LOG_STEP_BYTES;
+                if step > self.last_logged {
+                    self.last_logged = step;
+                    info!(
+                        "Downloading {}: {}",
+                        self.name,
+                        crate::report::format_bytes(self.done)
+                    );
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self) {
+        self.last_draw = Some(Instant::now());
+        let done = crate::report::format_bytes(self.done);
+        let line = match self.total.filter(|&t| t > 0) {
+            Some(total) => {
+                let filled = (self.done.min(total) * PROGRESS_BAR_WIDTH as u64 / total) as usize;
+                format!(
+                    "{} [{}{}] {:>3}% {}/{}",
+                    self.name,
+                    "#".repeat(filled),
+                    "-".repeat(PROGRESS_BAR_WIDTH - filled),
+                    self.done.min(total) * 100 / total,
+                    done,
+                    crate::report::format_bytes(total)
+                )
+            }
+            None => format!("{} {}", self.name, done),
+        };
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+    }
+
+    /// Draw the final state and end the bar's line.
+    fn finish(&mut self) {
+        if self.tty && self.last_draw.is_some() {
+            self.draw();
+            eprintln!();
+        }
+    }
+}
+
+/// Writer that reports everything written through it to a [`Progress`].
+struct ProgressWriter<W> {
+    inner: W,
+    progress: Progress,
+}
+
+impl<W: Write> Write for ProgressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.progress.advance(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Directory downloads are saved in: `downloads` in the private
+/// [cache directory](cache::dir), created if needed. Downloads are installed as
+/// root, so they must not go to predictable paths in the shared temp directory,
+/// where another user could create them in advance.
+pub fn download_dir() -> Result<PathBuf> {
+    let dir = cache::dir("downloads")?;
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// Stream a response body to `path`, via `<path>.part` so an interrupted
+/// download never leaves a truncated file under the final name. Progress is
+/// shown as it goes, and a body shorter or longer than its `Content-Length`
+/// is an error. Returns the SHA256 (hex) of the body, computed while streaming.
+pub fn save_response(resp: &mut Response, path: &Path) -> Result<String> {
+    let part = PathBuf::from(format!("{}{}", path.display(), PARTIAL_SUFFIX));
+    let expected = resp.content_length();
+    let result = (|| -> Result<String> {
+        let mut out = ProgressWriter {
+            inner: HashingWriter {
+                inner: File::create(&part)
+                    .with_context(|| format!("Failed to create {}", part.display()))?,
+                hasher: Sha256::new(),
+            },
+            progress: Progress::new(file_name(path), expected),
+        };
+        let copied = copy(resp, &mut out);
+        out.progress.finish();
+        let copied = copied.with_context(|| format!("Failed to download to {}", part.display()))?;
+        if let Some(expected) = expected.filter(|&expected| expected != copied) {
+            return Err(anyhow!(
+                "Download to {} is incomplete: expected {} bytes (Content-Length), got {}",
+                part.display(),
+                expected,
+                copied
+            ));
+        }
+        let out = out.inner;
+        fs::rename(&part, path)
+            .with_context(|| format!("Failed to move {} into place", part.display()))?;
+        Ok(hex::encode(out.hasher.finalize()))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&part);
+    }
+    result
+}
+
+/// Expected SHA256 (lowercase hex) of `file_name` in the contents of a
+/// checksums file: `sha256sum` output (`<hash>  <name>`, `<hash> *<name>`),
+/// BSD-style `SHA256 (<name>) = <hash>`, or a lone hash for single-file
+/// `.sha256` assets.
+pub fn find_checksum(contents: &str, file_name: &str) -> Option<String> {
+    find_named_checksum(contents, file_name).or_else(|| match checksum_lines(contents).as_slice() {
+        [hash] if is_sha256(hash) => Some(hash.to_ascii_lowercase()),
+        _ => None,
+    })
+}
+
+/// Like [`find_checksum`], without the lone-hash fallback: only an entry that
+/// names `file_name` counts.
+pub fn find_named_checksum(contents: &str, file_name: &str) -> Option<String> {
+    for line in checksum_lines(contents) {
+        if let Some(rest) = line.strip_prefix("SHA256 (") {
+            if let Some((name, hash)) = rest.split_once(") = ") {
+                if name == file_name && is_sha256(hash.trim()) {
+                    return Some(hash.trim().to_ascii_lowercase());
+                }
+            }
+            continue;
+        }
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let (Some(hash), Some(name)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let name = name.trim_start().trim_start_matches('*');
+        let name = name.strip_prefix("./").unwrap_or(name);
+        if name == file_name && is_sha256(hash) {
+            return Some(hash.to_ascii_lowercase());
+        }
+    }
+    None
+}
+
+fn checksum_lines(contents: &str) -> Vec<&str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+}
+
+fn is_sha256(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Temporary GnuPG home, removed when dropped.
+struct TempGpgHome(PathBuf);
+
+impl Drop for TempGpgHome {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.0) {
+            warn!("Failed to remove {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Verify the detached `signature` of `file` with `gpg --verify`, trusting only
+/// the armored public key in `key`. The key is imported into a temporary GnuPG
+/// home, so the user's keyring is neither used nor changed.
+pub fn verify_signature(file: &Path, signature: &Path, key: &Path) -> Result<()> {
+    let gpg =
+        which::which("gpg").context("gpg not found in PATH; required to verify signatures")?;
+
+    let name = file
+        .file_name()
+        .map_or_else(|| "download".into(), |n| n.to_string_lossy());
+    let home = TempGpgHome(std::env::temp_dir().join(format!(
+        "autopkg-gnupg-{}-{}",
+        std::process::id(),
+        name
+    )));
+    let _ = fs::remove_dir_all(&home.0);
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&home.0)
+            .with_context(|| format!("Failed to create {}", home.0.display()))?;
+    }
+
+    let gpg_command = |args: &[&std::ffi::OsStr]| {
+        Command::new(&gpg)
+            .env("GNUPGHOME", &home.0)
+            .args(["--batch", "--no-tty"])
+            .args(args)
+            .output()
+            .context("Failed to run gpg")
+    };
+
+    let output = gpg_command(&["--import".as_ref(), key.as_os_str()])?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to import gpg_key {}: {}",
+            key.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let output = gpg_command(&["--verify".as_ref(), signature.as_os_str(), file.as_os_str()])?;
+    // gpg reports the signature details on stderr
+    let details = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Signature verification of {} failed: {}",
+            name,
+            details.trim()
+        ));
+    }
+    debug!("gpg: {}", details.trim());
+    Ok(())
+}
+
+/// A response body being read as a download, holding a download slot until it
+/// is dropped.
+pub struct StreamedResponse {
+    resp: Response,
+    _permit: DownloadPermit,
+}
+
+impl StreamedResponse {
+    pub fn new(resp: Response, permit: DownloadPermit) -> Self {
+        Self {
+            resp,
+            _permit: permit,
+        }
+    }
+}
+
+impl Read for StreamedResponse {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.resp.read(buf)
+    }
+}
+
+/// Limits how many asset downloads run at the same time, independently of how
+/// many applications are processed in parallel.
+struct DownloadLimiter {
+    /// (downloads in progress, maximum allowed; 0 = unlimited)
+    state: Mutex<(usize, usize)>,
+    released: Condvar,
+}
+
+static DOWNLOADS: DownloadLimiter = DownloadLimiter {
+    state: Mutex::new((0, 0)),
+    released: Condvar::new(),
+};
+
+/// Set the maximum number of concurrent downloads (0 = unlimited).
+pub fn set_max_concurrent_downloads(max: usize) {
+    DOWNLOADS.state.lock().unwrap().1 = max;
+    DOWNLOADS.released.notify_all();
+}
+
+/// Permit to download; the slot is released when it is dropped.
+pub struct DownloadPermit(());
+
+/// Block until a download slot is free. Fetchers hold the permit for the
+/// duration of an asset download.
+pub fn acquire_download_permit() -> DownloadPermit {
+    let mut state = DOWNLOADS.state.lock().unwrap();
+    while state.1 != 0 && state.0 >= state.1 {
+        state = DOWNLOADS.released.wait(state).unwrap();
+    }
+    state.0 += 1;
+    DownloadPermit(())
+}
+
+impl Drop for DownloadPermit {
+    fn drop(&mut self) {
+        DOWNLOADS.state.lock().unwrap().0 -= 1;
+        DOWNLOADS.released.notify_one();
+    }
+}
+
+static TRACE_HTTP: AtomicBool = AtomicBool::new(false);
+
+/// Response headers worth showing when tracing; everything else is noise.
+const TRACED_RESPONSE_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "location",
+    "retry-after",
+    "www-authenticate",
+    "x-github-request-id",
+    "x-ratelimit-limit",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+    "x-ratelimit-resource",
+];
+
+/// Log metadata of every HTTP call made by fetchers (at debug level).
+pub fn set_trace_http(enabled: bool) {
+    TRACE_HTTP.store(enabled, Ordering::Relaxed);
+}
+
+/// Request timeout when `http.timeout` isn't set.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// User agent when `http.user_agent` isn't set.
+const DEFAULT_USER_AGENT: &str = "autopkg-rust/0.1";
+
+/// The HTTP client shared by fetchers, built once from the `http` config
+/// section so connections are pooled across applications. Proxies are taken
+/// from the standard `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` and `NO_PROXY`
+/// environment variables.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    timeout: Duration,
+    connect_timeout: Option<Duration>,
+    user_agent: String,
+}
+
+impl HttpClient {
+    pub fn new(config: &HttpConfig) -> Result<Self> {
+        let parse = |field: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|v| {
+                    humantime::parse_duration(v)
+                        .with_context(|| format!("Invalid http.{}: {}", field, v))
+                })
+                .transpose()
+        };
+        let timeout = parse("timeout", &config.timeout)?.unwrap_or(DEFAULT_TIMEOUT);
+        let connect_timeout = parse("connect_timeout", &config.connect_timeout)?;
+        let user_agent = config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string());
+
+        let mut http = Self {
+            client: Client::new(),
+            timeout,
+            connect_timeout,
+            user_agent,
+        };
+        http.client = http
+            .builder(timeout)
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(http)
+    }
+
+    fn builder(&self, timeout: Duration) -> ClientBuilder {
+        let builder = Client::builder()
+            .user_agent(&self.user_agent)
+            .timeout(timeout);
+        match self.connect_timeout {
+            Some(connect_timeout) => builder.connect_timeout(connect_timeout),
+            None => builder,
+        }
+    }
+
+    /// The shared client.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The client for a fetcher: the shared one, unless the fetcher sets its
+    /// own `timeout` or `ca_cert`. Those need a client of their own, which
+    /// otherwise has the same settings.
+    pub fn for_fetcher(&self, config: &FetcherConfig, timeout: Option<Duration>) -> Result<Client> {
+        if timeout.is_none() && config.ca_cert.is_none() {
+            return Ok(self.client.clone());
+        }
+
+        let mut builder = self.builder(timeout.unwrap_or(self.timeout));
+        if let Some(path) = &config.ca_cert {
+            let pem = fs::read(path).with_context(|| format!("Failed to read ca_cert {}", path))?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid certificate in ca_cert {}", path))?;
+            if certs.is_empty() {
+                return Err(anyhow!("ca_cert {} contains no PEM certificates", path));
+            }
+            debug!("Trusting {} certificate(s) from {}", certs.len(), path);
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// Whether a request failed in the TLS handshake, typically because a proxy
+/// intercepts HTTPS or the server's certificate is signed by a private CA.
+/// Such failures aren't transient, so aren't retried.
+fn is_tls_error(e: &reqwest::Error) -> bool {
+    if !e.is_connect() {
+        return false;
+    }
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        let message = err.to_string().to_lowercase();
+        if ["certificate", "tls", "handshake"]
+            .iter()
+            .any(|word| message.contains(word))
+        {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Point TLS failures at the settings that usually fix them.
+fn explain_error(e: reqwest::Error) -> anyhow::Error {
+    if !is_tls_error(&e) {
+        return e.into();
+    }
+    let url = e
+        .url()
+        .map_or_else(|| "server".to_string(), |url| url.to_string());
+    let context = format!(
+        "TLS handshake with {} failed; if HTTPS goes through a proxy (HTTPS_PROXY, NO_PROXY) \
+         or the server uses a private CA, set the fetcher's ca_cert to its CA certificate",
+        url
+    );
+    anyhow::Error::new(e).context(context)
+}
+
+/// Retries when `retries` isn't set.
+const DEFAULT_RETRIES: u32 = 3;
+
+/// First retry delay when `retry_base_delay_ms` isn't set.
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// A 429 asking to wait longer than this isn't retried; the caller reports it
+/// as rate limited instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How a fetcher retries transient HTTP failures: connection errors and
+/// timeouts (e.g. DNS not up yet after resume), 5xx and 429 responses. Other
+/// 4xx responses are returned at once.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    retries: u32,
+    base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &FetcherConfig) -> Self {
+        Self {
+            retries: config.retries.unwrap_or(DEFAULT_RETRIES),
+            base_delay: config
+                .retry_base_delay_ms
+                .map_or(DEFAULT_RETRY_BASE_DELAY, Duration::from_millis),
+        }
+    }
+
+    /// [`send`] the request, retrying transient failures with exponential
+    /// backoff. Once retries run out, the last response or error is returned.
+    pub fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            // Requests with streamed bodies can't be cloned, so can't be retried
+            let Some(retry) = request.try_clone().filter(|_| attempt < self.retries) else {
+                return send(request).map_err(explain_error);
+            };
+            let result = send(retry);
+            let delay = self.base_delay * 2u32.saturating_pow(attempt);
+            let delay = match &result {
+                Err(e) if (e.is_connect() && !is_tls_error(e)) || e.is_timeout() => {
+                    warn!("HTTP request failed ({}); retrying in {:?}", e, delay);
+                    delay
+                }
+                Ok(resp) if resp.status().is_server_error() => {
+                    warn!(
+                        "{} returned {}; retrying in {:?}",
+                        resp.url(),
+                        resp.status(),
+                        delay
+                    );
+                    delay
+                }
+                Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                    let retry_after = resp
+                        .headers()
+                        .get("retry-after")
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.parse().ok())
+                        .map(Duration::from_secs);
+                    if retry_after.is_some_and(|d| d > MAX_RETRY_AFTER) {
+                        return result.map_err(explain_error);
+                    }
+                    let delay = delay.max(retry_after.unwrap_or_default());
+                    warn!("{} returned 429; retrying in {:?}", resp.url(), delay);
+                    delay
+                }
+                _ => return result.map_err(explain_error),
+            };
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+/// Send a request, logging method, URL, headers and response status when
+/// `--trace-http` is enabled. Bodies and secret header values are never logged.
+pub fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    if !TRACE_HTTP.load(Ordering::Relaxed) {
+        return request.send();
+    }
+
+    let (client, request) = request.build_split();
+    let request = request?;
+    debug!(
+        "HTTP: > {} {} {}",
+        request.method(),
+        request.url(),
+        format_headers(request.headers(), |_| true)
+    );
+
+    let result = client.execute(request);
+    match &result {
+        Ok(resp) => {
+            let mut url = resp.url().clone();
+            url.set_query(None);
+            debug!(
+                "HTTP: < {} from {} {}",
+                resp.status(),
+                url,
+                format_headers(resp.headers(), |name| TRACED_RESPONSE_HEADERS
+                    .contains(&name))
+            );
+        }
+        Err(e) => debug!("HTTP: < error: {}", e),
+    }
+    result
+}
+
+fn format_headers(headers: &HeaderMap, include: impl Fn(&str) -> bool) -> String {
+    let fields: Vec<String> = headers
+        .iter()
+        .filter(|(name, _)| include(name.as_str()))
+        .map(|(name, value)| {
+            let redacted = value.is_sensitive()
+                || matches!(
+                    name.as_str(),
+                    "authorization" | "proxy-authorization" | "cookie" | "set-cookie"
+                );
+            if redacted {
+                format!("{}: <redacted>", name)
+            } else {
+                format!("{}: {}", name, value.to_str().unwrap_or("<binary>"))
+            }
+        })
+        .collect();
+    format!("[{}]", fields.join(", "))
+}
@@ -0,0 +1,409 @@
+use crate::config::{ApplicationConfig, FetcherConfig};
+use crate::fetcher::{
+    acquire_download_permit, download_dir, save_response, url_file_name, Fetcher, HttpClient,
+    RetryPolicy, StreamedResponse,
+};
+use crate::host;
+use crate::state::{CachedVersion, State};
+use crate::types::{Download, FetchResult, Resolved};
+use crate::version::{should_replace, TagFormat, Version, VersionRequirement};
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use reqwest::blocking::Client;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where `version_url` responses are cached, if anywhere.
+static VERSION_CACHE: Mutex<Option<VersionCache>> = Mutex::new(None);
+
+/// Cache `version_url` responses in the state file at `state_path` and
+/// revalidate them with their `ETag` / `Last-Modified`, so an unchanged version
+/// costs a `304`. With `refresh`, cached responses are ignored (and replaced).
+pub fn set_version_cache(state_path: &Path, refresh: bool) {
+    *VERSION_CACHE.lock().unwrap() = Some(VersionCache {
+        state_path: state_path.to_path_buf(),
+        refresh,
+    });
+}
+
+/// `version_url` responses kept in the state file.
+#[derive(Debug, Clone)]
+struct VersionCache {
+    state_path: PathBuf,
+    refresh: bool,
+}
+
+impl VersionCache {
+    fn get(&self, version_url: &str) -> Option<CachedVersion> {
+        if self.refresh {
+            return None;
+        }
+        State::load(&self.state_path)
+            .url_versions
+            .remove(version_url)
+    }
+
+    /// Store the response for `version_url`. Failures only cost a full request
+    /// next time, so they are logged, not returned.
+    fn set(&self, version_url: &str, entry: CachedVersion) {
+        let result = State::update(&self.state_path, |state| {
+            state.url_versions.insert(version_url.to_string(), entry);
+        });
+        if let Err(e) = result {
+            warn!("UrlFetcher: failed to cache {}: {:#}", version_url, e);
+        }
+    }
+}
+
+/// Fetcher for a file at a fixed URL, for projects without a release API.
+///
+/// With `version_url`, the latest version is read from that endpoint and the
+/// file is only downloaded when it is newer; without it, the file is downloaded
+/// on every run and the installer decides what to do with it.
+pub struct UrlFetcher {
+    url: String,
+    version_url: Option<String>,
+    /// The application's `version` requirement the version must satisfy
+    requirement: Option<VersionRequirement>,
+    tag_format: TagFormat,
+    client: Client,
+    retry: RetryPolicy,
+    app_name: String,
+    version_cache: Option<VersionCache>,
+}
+
+impl UrlFetcher {
+    pub fn new(config: &FetcherConfig, app: &ApplicationConfig, http: &HttpClient) -> Result<Self> {
+        let url = config
+            .url
+            .as_deref()
+            .ok_or_else(|| anyhow!("URL fetcher requires `url` field"))?;
+        let requirement = VersionRequirement::from_app(app)?;
+        if requirement.is_some() && config.version_url.is_none() {
+            return Err(anyhow!(
+                "URL fetcher needs `version_url` to check the `version` requirement"
+            ));
+        }
+        let timeout = config
+            .timeout
+            .as_deref()
+            .map(|timeout| {
+                humantime::parse_duration(timeout)
+                    .with_context(|| format!("Invalid timeout: {}", timeout))
+            })
+            .transpose()?;
+
+        let client = http.for_fetcher(config, timeout)?;
+
+        Ok(Self {
+            url: host::substitute(url)?,
+            version_url: config
+                .version_url
+                .as_deref()
+                .map(host::substitute)
+                .transpose()?,
+            requirement,
+            tag_format: TagFormat::from_config(config),
+            client,
+            retry: RetryPolicy::from_config(config),
+            app_name: app.name.clone(),
+            version_cache: VERSION_CACHE.lock().unwrap().clone(),
+        })
+    }
+
+    /// Cache version responses in the state file at `state_path`, without
+    /// going through the global setting.
+    #[cfg(test)]
+    fn with_version_cache(mut self, state_path: &Path) -> Self {
+        self.version_cache = Some(VersionCache {
+            state_path: state_path.to_path_buf(),
+            refresh: false,
+        });
+        self
+    }
+
+    /// Latest version, read from `version_url` (its first non-empty line);
+    /// `None` if it doesn't satisfy the `version` requirement.
+    fn latest_version(&self, version_url: &str) -> Result<Option<String>> {
+        info!("UrlFetcher: querying {}", version_url);
+        let body = self.get_version(version_url)?;
+        let line = body
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .ok_or_else(|| anyhow!("Version endpoint {} returned no version", version_url))?;
+        let version = self.tag_format.version(line);
+        if let Some(requirement) = &self.requirement {
+            if !requirement.matches(&version) {
+                warn!(
+                    "UrlFetcher: latest version {} doesn't satisfy version {}",
+                    version, requirement
+                );
+                return Ok(None);
+            }
+        }
+        Ok(Some(version))
+    }
+
+    /// GET `version_url`, sending the validators of the cached response if
+    /// there is one. A `304` reuses the cached body; a response with an `ETag`
+    /// or `Last-Modified` is cached for the next run.
+    fn get_version(&self, version_url: &str) -> Result<String> {
+        let cached = self.version_cache.as_ref().and_then(|c| c.get(version_url));
+
+        let mut request = self.client.get(version_url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = self.retry.send(request)?;
+
+        if let (StatusCode::NOT_MODIFIED, Some(cached)) = (resp.status(), cached) {
+            info!(
+                "UrlFetcher: {} not modified; using cached response",
+                version_url
+            );
+            return Ok(cached.body);
+        }
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Version endpoint {} returned status {}",
+                version_url,
+                resp.status()
+            ));
+        }
+
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = resp.text()?;
+        if let Some(cache) = &self.version_cache {
+            if etag.is_some() || last_modified.is_some() {
+                cache.set(
+                    version_url,
+                    CachedVersion {
+                        etag,
+                        last_modified,
+                        body: body.clone(),
+                    },
+                );
+            }
+        }
+        Ok(body)
+    }
+
+    fn download(&self) -> Result<(PathBuf, String)> {
+        let _permit = acquire_download_permit();
+        let mut resp = self.retry.send(self.client.get(&self.url))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Failed to download from {}: status {}",
+                self.url,
+                resp.status()
+            ));
+        }
+
+        let name = url_file_name(&self.url).unwrap_or("download").to_string();
+        let path = download_dir()?.join(format!("autopkg-{}-{}", self.app_name, name));
+        save_response(&mut resp, &path)?;
+
+        info!("Downloaded {} to {}", self.url, path.display());
+        Ok((path, name))
+    }
+}
+
+impl Fetcher for UrlFetcher {
+    fn resolve_latest(&self) -> Result<Option<Resolved>> {
+        let version_url = self
+            .version_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("URL fetcher needs `version_url` to look up versions"))?;
+        Ok(self.latest_version(version_url)?.map(|version| Resolved {
+            version,
+            url: Some(self.url.clone()),
+        }))
+    }
+
+    fn supports_stream(&self) -> bool {
+        self.version_url.is_some()
+    }
+
+    fn open_stream(&self, url: &str) -> Result<Box<dyn Read>> {
+        let permit = acquire_download_permit();
+        let resp = self.retry.send(self.client.get(url))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Failed to download from {}: status {}",
+                url,
+                resp.status()
+            ));
+        }
+        info!("UrlFetcher: streaming {}", url);
+        Ok(Box::new(StreamedResponse::new(resp, permit)))
+    }
+
+    fn fetch_if_newer(&self, current_version: &Version) -> FetchResult {
+        let version = match &self.version_url {
+            Some(version_url) => {
+                let Some(latest_version) = self.latest_version(version_url)? else {
+                    return Ok(None);
+                };
+                info!(
+                    "UrlFetcher: latest version={}, current={}",
+                    latest_version, current_version
+                );
+                if !should_replace(current_version, &latest_version, self.requirement.as_ref()) {
+                    info!("UrlFetcher: no newer version available");
+                    return Ok(None);
+                }
+                Some(latest_version)
+            }
+            None => {
+                warn!(
+                    "UrlFetcher: no version_url; downloading {} and leaving the version check to the installer",
+                    self.url
+                );
+                None
+            }
+        };
+
+        let (path, asset) = self.download()?;
+        Ok(Some(Download {
+            path,
+            version,
+            asset,
+            installer: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server, ServerGuard};
+
+    /// Build a fetcher for `path` on `server` from the YAML `fetcher` fields.
+    fn fetcher(server: &ServerGuard, path: &str, extra: &str) -> UrlFetcher {
+        let app: ApplicationConfig = serde_yaml::from_str(&format!(
+            "name: test-url\nfetcher:\n  type: url\n  url: {}{}\n{}\ninstaller: deb\n",
+            server.url(),
+            path,
+            extra
+        ))
+        .unwrap();
+        UrlFetcher::new(
+            &app.fetcher,
+            &app,
+            &HttpClient::new(&Default::default()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn downloads_only_when_version_url_reports_newer() {
+        let mut server = Server::new();
+        let version = server
+            .mock("GET", "/latest.txt")
+            .with_status(200)
+            .with_body("\nv1.4.0\n")
+            .expect(2)
+            .create();
+        let file = server
+            .mock("GET", "/tool.deb")
+            .with_status(200)
+            .with_body("deb")
+            .expect(1)
+            .create();
+        let extra = format!("  version_url: {}/latest.txt", server.url());
+        let fetcher = fetcher(&server, "/tool.deb", &extra);
+
+        let current = Version::upstream("1.4.0");
+        assert!(fetcher.fetch_if_newer(&current).unwrap().is_none());
+
+        let download = fetcher
+            .fetch_if_newer(&Version::upstream("1.3.2"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(download.version.as_deref(), Some("1.4.0"));
+        assert_eq!(download.asset, "tool.deb");
+        assert_eq!(
+            download.path,
+            download_dir().unwrap().join("autopkg-test-url-tool.deb")
+        );
+        assert_eq!(std::fs::read(&download.path).unwrap(), b"deb");
+        std::fs::remove_file(&download.path).unwrap();
+        version.assert();
+        file.assert();
+    }
+
+    #[test]
+    fn revalidates_cached_version_with_etag_and_last_modified() {
+        let mut server = Server::new();
+        let full = server
+            .mock("GET", "/VERSION")
+            .match_header("if-none-match", Matcher::Missing)
+            .with_header("etag", "\"v1\"")
+            .with_header("last-modified", "Wed, 14 Oct 2026 08:00:00 GMT")
+            .with_body("2.1.0\n")
+            .expect(1)
+            .create();
+        let not_modified = server
+            .mock("GET", "/VERSION")
+            .match_header("if-none-match", "\"v1\"")
+            .match_header("if-modified-since", "Wed, 14 Oct 2026 08:00:00 GMT")
+            .with_status(304)
+            .expect(1)
+            .create();
+
+        let state_path = std::env::temp_dir().join(format!(
+            "autopkg-test-url-state-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&state_path);
+        let extra = format!("  version_url: {}/VERSION", server.url());
+        let fetcher = fetcher(&server, "/tool.deb", &extra).with_version_cache(&state_path);
+
+        let current = Version::upstream("2.1.0");
+        assert!(fetcher.fetch_if_newer(&current).unwrap().is_none());
+        let cached = &State::load(&state_path).url_versions[&format!("{}/VERSION", server.url())];
+        assert_eq!(cached.etag.as_deref(), Some("\"v1\""));
+        let resolved = fetcher.resolve_latest().unwrap().unwrap();
+        assert_eq!(resolved.version, "2.1.0");
+        std::fs::remove_file(&state_path).unwrap();
+        full.assert();
+        not_modified.assert();
+    }
+
+    #[test]
+    fn always_downloads_without_version_url() {
+        let mut server = Server::new();
+        let file = server
+            .mock("GET", "/nightly.deb")
+            .with_status(200)
+            .with_body("deb")
+            .create();
+        let fetcher = fetcher(&server, "/nightly.deb", "");
+
+        let download = fetcher
+            .fetch_if_newer(&Version::upstream("9.9.9"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(download.version, None);
+        std::fs::remove_file(&download.path).unwrap();
+        assert!(fetcher.resolve_latest().is_err());
+        file.assert();
+    }
+}
@@ -0,0 +1,142 @@
+use crate::config::Config;
+use crate::fetcher::{download_dir, PARTIAL_SUFFIX};
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Prefix of every download's file name.
+const PREFIX: &str = "autopkg-";
+
+const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Partial downloads younger than this may belong to a run in progress.
+const PARTIAL_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// What a sweep removed.
+#[derive(Debug, Default)]
+pub struct GcSummary {
+    pub removed: usize,
+    pub bytes: u64,
+}
+
+struct Entry {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// Remove orphaned partial downloads, downloads older than `older_than` (else
+/// `download_ttl`), and per-application downloads beyond `keep_versions`, from
+/// the private [download directory](download_dir). Nothing outside it is
+/// touched, so files of other users in the shared temp directory are safe.
+/// Anything modified at or after `protect_since` (the start of the current
+/// run) is left alone.
+pub fn sweep(
+    config: &Config,
+    protect_since: Option<SystemTime>,
+    older_than: Option<Duration>,
+) -> Result<GcSummary> {
+    let settings = &config.settings.gc;
+    let ttl = match (older_than, &settings.download_ttl) {
+        (Some(older_than), _) => older_than,
+        (None, Some(ttl)) => humantime::parse_duration(ttl)
+            .map_err(|e| anyhow!("Invalid gc.download_ttl '{}': {}", ttl, e))?,
+        (None, None) => DEFAULT_TTL,
+    };
+    let now = SystemTime::now();
+    let age = |entry: &Entry| now.duration_since(entry.modified).unwrap_or_default();
+
+    let mut entries = list_entries(&download_dir()?)?;
+    entries.retain(|e| protect_since.is_none_or(|since| e.modified < since));
+
+    let mut summary = GcSummary::default();
+    let mut remaining = Vec::new();
+    for entry in entries {
+        let partial = entry.path.to_string_lossy().ends_with(PARTIAL_SUFFIX);
+        if (partial && age(&entry) > PARTIAL_GRACE) || (!partial && age(&entry) > ttl) {
+            remove(&entry, &mut summary);
+        } else if !partial {
+            remaining.push(entry);
+        }
+    }
+
+    if let Some(keep) = settings.keep_versions {
+        // Downloads are named after the repository (see the GitHub fetchers)
+        for repo in config
+            .applications
+            .iter()
+            .filter_map(|app| app.fetcher.repo.as_deref())
+            .filter_map(|repo| repo.split_once('/').map(|(_, name)| name))
+        {
+            let prefix = format!("{}{}-", PREFIX, repo);
+            let mut downloads: Vec<&Entry> = remaining
+                .iter()
+                .filter(|e| file_name(&e.path).starts_with(&prefix))
+                .collect();
+            downloads.sort_by_key(|e| std::cmp::Reverse(e.modified));
+            for entry in downloads.into_iter().skip(keep) {
+                if entry.path.exists() {
+                    remove(entry, &mut summary);
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn list_entries(dir: &Path) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        entries.push(Entry {
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            size: disk_size(&path),
+            path,
+        });
+    }
+    Ok(entries)
+}
+
+fn disk_size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| disk_size(&e.path()))
+                    .sum()
+            })
+            .unwrap_or(0),
+        Ok(m) => m.len(),
+        Err(_) => 0,
+    }
+}
+
+fn remove(entry: &Entry, summary: &mut GcSummary) {
+    let result = if entry.path.is_dir() {
+        fs::remove_dir_all(&entry.path)
+    } else {
+        fs::remove_file(&entry.path)
+    };
+    match result {
+        Ok(()) => {
+            info!("gc: removed {}", entry.path.display());
+            summary.removed += 1;
+            summary.bytes += entry.size;
+        }
+        Err(e) => warn!("gc: failed to remove {}: {}", entry.path.display(), e),
+    }
+}
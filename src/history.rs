@@ -0,0 +1,379 @@
+use crate::lock;
+use crate::report::{Action, AppReport};
+
+use anyhow::{anyhow, Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File name of the history file, next to the config unless `settings.history_file` is set.
+pub const DEFAULT_HISTORY_FILE: &str = "autopkg-history.jsonl";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// One line of the history file: the outcome of an application in a run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    /// Position in the file, starting at 1; gaps reveal removed records
+    pub seq: u64,
+    /// Unix time of the run
+    pub timestamp: u64,
+    pub app: String,
+    pub action: Action,
+    pub dry_run: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// HMAC-SHA256 (hex) over the previous record's `mac` and this record,
+    /// chaining every record to the ones before it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+}
+
+impl HistoryRecord {
+    /// MAC of this record (without its own `mac`) chained to `prev_mac`.
+    fn compute_mac(&self, key: &[u8], prev_mac: &str) -> Result<String> {
+        let mut unsigned = self.clone();
+        unsigned.mac = None;
+        let mut mac =
+            HmacSha256::new_from_slice(key).map_err(|e| anyhow!("Invalid history key: {}", e))?;
+        mac.update(prev_mac.as_bytes());
+        mac.update(serde_json::to_string(&unsigned)?.as_bytes());
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+/// Newest record of a signed history, kept in a file of its own so that
+/// records removed from the end of the history can be detected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainHead {
+    /// Sequence number of the newest record
+    pub seq: u64,
+    /// That record's `mac`
+    pub mac: String,
+    /// HMAC-SHA256 (hex) over `seq` and `mac`, so the head can't be rewritten
+    /// to match a shortened history without the key
+    pub head_mac: String,
+}
+
+impl ChainHead {
+    fn new(key: &[u8], seq: u64, mac: String) -> Result<Self> {
+        let head_mac = Self::compute_mac(key, seq, &mac)?;
+        Ok(Self { seq, mac, head_mac })
+    }
+
+    fn compute_mac(key: &[u8], seq: u64, mac: &str) -> Result<String> {
+        let mut head =
+            HmacSha256::new_from_slice(key).map_err(|e| anyhow!("Invalid history key: {}", e))?;
+        head.update(format!("head:{}:{}", seq, mac).as_bytes());
+        Ok(hex::encode(head.finalize().into_bytes()))
+    }
+}
+
+/// Location of the history file: `history_file` if set, else next to the config file.
+pub fn path(history_file: Option<&str>, config_path: &Path) -> PathBuf {
+    match history_file {
+        Some(path) => PathBuf::from(path),
+        None => config_path.with_file_name(DEFAULT_HISTORY_FILE),
+    }
+}
+
+/// Location of the chain head of the history file at `path`.
+pub fn head_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.head", path.display()))
+}
+
+/// Signing key from `settings.history_key_file` or the variable named by
+/// `settings.history_key_env`, if either is set. An empty key is an error.
+pub fn load_key(key_file: Option<&str>, key_env: Option<&str>) -> Result<Option<Vec<u8>>> {
+    let key = if let Some(file) = key_file {
+        let key = fs::read(file).with_context(|| format!("Failed to read history key {}", file))?;
+        key.trim_ascii().to_vec()
+    } else if let Some(var) = key_env {
+        std::env::var(var)
+            .map_err(|_| anyhow!("Environment variable {} (history_key_env) is not set", var))?
+            .into_bytes()
+    } else {
+        return Ok(None);
+    };
+    if key.is_empty() {
+        return Err(anyhow!("History key is empty"));
+    }
+    Ok(Some(key))
+}
+
+/// Read the chain head of the history file at `path`, if there is one.
+pub fn read_head(path: &Path) -> Result<Option<ChainHead>> {
+    let head_path = head_path(path);
+    match fs::read_to_string(&head_path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("{}: invalid chain head", head_path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", head_path.display())),
+    }
+}
+
+/// Read all records of a history file (a missing file has none).
+pub fn read(path: &Path) -> Result<Vec<HistoryRecord>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("{}:{}: invalid history record", path.display(), i + 1))
+        })
+        .collect()
+}
+
+/// Append one record per application that did something (anything but
+/// skipped or up-to-date), signed if `key` is given. Signed records also
+/// move the chain head to the newest one.
+pub fn append(
+    path: &Path,
+    key: Option<&[u8]>,
+    timestamp: u64,
+    dry_run: bool,
+    apps: &[AppReport],
+) -> Result<()> {
+    let apps: Vec<&AppReport> = apps
+        .iter()
+        .filter(|a| !matches!(a.action, Action::Skipped | Action::UpToDate))
+        .collect();
+    if apps.is_empty() {
+        return Ok(());
+    }
+
+    // Held from reading the last record until the new ones are appended, so
+    // concurrent runs can't both continue the chain from the same record
+    let _lock = lock::lock(path)?;
+    let existing = read(path)?;
+    let last_seq = existing.last().map_or(0, |r| r.seq);
+    let mut prev_mac = existing
+        .last()
+        .and_then(|r| r.mac.clone())
+        .unwrap_or_default();
+
+    let mut lines = String::new();
+    for (seq, app) in (last_seq + 1..).zip(&apps) {
+        let mut record = HistoryRecord {
+            seq,
+            timestamp,
+            app: app.name.clone(),
+            action: app.action,
+            dry_run,
+            from_version: app.current_version.clone(),
+            to_version: app.new_version.clone(),
+            error: app.error.clone(),
+            mac: None,
+        };
+        if let Some(key) = key {
+            let mac = record.compute_mac(key, &prev_mac)?;
+            prev_mac = mac.clone();
+            record.mac = Some(mac);
+        }
+        lines.push_str(&serde_json::to_string(&record)?);
+        lines.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(lines.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    if let Some(key) = key {
+        let head = ChainHead::new(key, last_seq + apps.len() as u64, prev_mac)?;
+        lock::write_atomically(&head_path(path), serde_json::to_string(&head)?.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Check every record's MAC and sequence number, and that the newest record is
+/// the one `head` names. Returns one message per problem found; an empty list
+/// means the history is intact.
+pub fn verify(
+    records: &[HistoryRecord],
+    head: Option<&ChainHead>,
+    key: &[u8],
+) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let mut prev_mac = String::new();
+    let mut expected_seq = 1;
+
+    for record in records {
+        if record.seq != expected_seq {
+            if record.seq == expected_seq + 1 {
+                problems.push(format!("record {} is missing", expected_seq));
+            } else if record.seq > expected_seq {
+                problems.push(format!(
+                    "records {}-{} are missing",
+                    expected_seq,
+                    record.seq - 1
+                ));
+            } else {
+                problems.push(format!(
+                    "record {} is out of order (expected {})",
+                    record.seq, expected_seq
+                ));
+            }
+        }
+        expected_seq = record.seq + 1;
+
+        match &record.mac {
+            None => problems.push(format!(
+                "record {} ({}) is not signed",
+                record.seq, record.app
+            )),
+            Some(mac) => {
+                if *mac != record.compute_mac(key, &prev_mac)? {
+                    problems.push(format!(
+                        "record {} ({}) has been tampered with, or a record before it was removed",
+                        record.seq, record.app
+                    ));
+                }
+            }
+        }
+        prev_mac = record.mac.clone().unwrap_or_default();
+    }
+
+    let last_seq = records.last().map_or(0, |r| r.seq);
+    match head {
+        None if records.is_empty() => {}
+        None => problems.push(
+            "the chain head is missing, so records removed from the end can't be detected"
+                .to_string(),
+        ),
+        Some(head) if head.head_mac != ChainHead::compute_mac(key, head.seq, &head.mac)? => {
+            problems.push("the chain head has been tampered with".to_string())
+        }
+        Some(head) if head.seq > last_seq => problems.push(if head.seq == last_seq + 1 {
+            format!("record {} at the end is missing", head.seq)
+        } else {
+            format!(
+                "records {}-{} at the end are missing",
+                last_seq + 1,
+                head.seq
+            )
+        }),
+        Some(head) if head.seq < last_seq => problems.push(format!(
+            "records after {} were added without moving the chain head",
+            head.seq
+        )),
+        Some(head) => {
+            if Some(&head.mac) != records.last().and_then(|r| r.mac.as_ref()) {
+                problems.push(format!("record {} doesn't match the chain head", head.seq));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"secret";
+
+    /// A history of `count` signed records in a fresh file named after `test`.
+    fn signed_history(test: &str, count: usize) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "autopkg-history-test-{}-{}.jsonl",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(head_path(&path));
+        for i in 0..count {
+            let mut app = AppReport::new(&format!("app{}", i));
+            app.action = Action::Installed;
+            app.new_version = Some(format!("1.{}.0", i));
+            append(&path, Some(KEY), 1_700_000_000 + i as u64, false, &[app]).unwrap();
+        }
+        path
+    }
+
+    fn problems(path: &Path, records: &[HistoryRecord]) -> Vec<String> {
+        verify(records, read_head(path).unwrap().as_ref(), KEY).unwrap()
+    }
+
+    fn cleanup(path: &Path) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(head_path(path));
+        let _ = fs::remove_file(format!("{}.lock", path.display()));
+    }
+
+    #[test]
+    fn intact_history_verifies() {
+        let path = signed_history("intact", 3);
+        let records = read(&path).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(problems(&path, &records), Vec::<String>::new());
+        assert!(
+            !verify(&records, read_head(&path).unwrap().as_ref(), b"other")
+                .unwrap()
+                .is_empty()
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn detects_tampered_record() {
+        let path = signed_history("tampered", 3);
+        let mut records = read(&path).unwrap();
+        records[1].to_version = Some("6.6.6".into());
+        assert_eq!(
+            problems(&path, &records),
+            ["record 2 (app1) has been tampered with, or a record before it was removed"]
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn detects_truncated_history() {
+        let path = signed_history("truncated", 4);
+        let records = read(&path).unwrap();
+        assert_eq!(
+            problems(&path, &records[..3]),
+            ["record 4 at the end is missing"]
+        );
+        assert_eq!(
+            problems(&path, &records[..1]),
+            ["records 2-4 at the end are missing"]
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn detects_forged_head() {
+        let path = signed_history("forged", 3);
+        let records = read(&path).unwrap();
+        // A head rewritten to match a shortened history, without the key
+        let mut head = read_head(&path).unwrap().unwrap();
+        head.seq = 2;
+        head.mac = records[1].mac.clone().unwrap();
+        assert_eq!(
+            verify(&records[..2], Some(&head), KEY).unwrap(),
+            ["the chain head has been tampered with"]
+        );
+        assert_eq!(
+            verify(&records, None, KEY).unwrap(),
+            ["the chain head is missing, so records removed from the end can't be detected"]
+        );
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::installer::user_command;
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::path::Path;
+
+/// Run a scan command on a downloaded file before it is installed.
+///
+/// `{file}` in the command is replaced with the (shell-quoted) file path. A
+/// non-zero exit is an error; the caller disposes of the rejected file. The
+/// command runs as the `run_as` user, if set.
+pub fn run_scan(command: &str, file_path: &Path, run_as: Option<&str>) -> Result<()> {
+    let command = command.replace("{file}", &shell_quote(&file_path.to_string_lossy()));
+    info!("Running pre-install scan: {}", command);
+
+    let status = user_command("sh", run_as)?
+        .arg("-c")
+        .arg(&command)
+        .status()
+        .with_context(|| format!("Failed to run pre-install scan: {}", command))?;
+
+    if !status.success() {
+        return Err(anyhow!(
+            "Pre-install scan rejected {} (status {})",
+            file_path.display(),
+            status
+        ));
+    }
+
+    info!("Pre-install scan passed for {}", file_path.display());
+    Ok(())
+}
+
+/// Run an application's `pre_install` or `post_install` commands (`stage`), in
+/// order, through the user's shell (`$SHELL`, else `sh`) with inherited output.
+/// Stops at the first command that fails. Commands run as the `run_as` user, if
+/// set.
+pub fn run_install_hooks(stage: &str, commands: &[String], run_as: Option<&str>) -> Result<()> {
+    let shell = std::env::var("SHELL")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "sh".to_string());
+    for command in commands {
+        info!("Running {} hook: {}", stage, command);
+        let status = user_command(&shell, run_as)?
+            .arg("-c")
+            .arg(command)
+            .status()
+            .with_context(|| format!("Failed to run {} hook: {}", stage, command))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "{} hook `{}` failed with status {}",
+                stage,
+                command,
+                status
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Quote a string for safe use as a single `sh` word.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
@@ -0,0 +1,205 @@
+use crate::config::Settings;
+
+use anyhow::{anyhow, Result};
+use std::sync::Mutex;
+
+/// Values from `settings` that replace the detected ones.
+struct Overrides {
+    codename: Option<String>,
+    arch: Option<String>,
+    os_family: Option<String>,
+}
+
+static OVERRIDES: Mutex<Overrides> = Mutex::new(Overrides {
+    codename: None,
+    arch: None,
+    os_family: None,
+});
+
+/// Override the detected host values with `settings.codename`, `settings.arch`
+/// and `settings.os_family`.
+pub fn set_overrides(settings: &Settings) {
+    *OVERRIDES.lock().unwrap() = Overrides {
+        codename: settings.codename.clone(),
+        arch: settings.arch.clone(),
+        os_family: settings.os_family.clone(),
+    };
+}
+
+/// Value of `key` in `/etc/os-release`, unquoted.
+fn os_release(key: &str) -> Option<String> {
+    let contents = std::fs::read_to_string("/etc/os-release").ok()?;
+    contents.lines().find_map(|line| {
+        line.strip_prefix(key)?
+            .strip_prefix('=')
+            .map(|v| v.trim().trim_matches('"').to_string())
+            .filter(|v| !v.is_empty())
+    })
+}
+
+/// Distro codename, e.g. `jammy`: the override, else `VERSION_CODENAME` from `/etc/os-release`.
+pub fn codename() -> Option<String> {
+    if let Some(codename) = OVERRIDES.lock().unwrap().codename.clone() {
+        return Some(codename);
+    }
+    os_release("VERSION_CODENAME")
+}
+
+/// Distro family: `debian`, `rhel` or `suse`. The override, else derived from
+/// `ID` and `ID_LIKE` in `/etc/os-release`.
+pub fn os_family() -> Option<String> {
+    if let Some(family) = OVERRIDES.lock().unwrap().os_family.clone() {
+        return Some(family);
+    }
+    let ids = format!(
+        "{} {}",
+        os_release("ID").unwrap_or_default(),
+        os_release("ID_LIKE").unwrap_or_default()
+    );
+    let family = ids.split_whitespace().find_map(|id| match id {
+        "debian" | "ubuntu" => Some("debian"),
+        "rhel" | "fedora" | "centos" => Some("rhel"),
+        "suse" | "opensuse" | "sles" => Some("suse"),
+        _ => None,
+    })?;
+    Some(family.to_string())
+}
+
+/// Native package format of the host (`deb` or `rpm`), from its [`os_family`].
+pub fn pkg_ext() -> Option<&'static str> {
+    match os_family()?.as_str() {
+        "debian" => Some("deb"),
+        "rhel" | "suse" => Some("rpm"),
+        _ => None,
+    }
+}
+
+/// Architecture in the naming of the host's package format, e.g. `amd64` on
+/// Debian and `x86_64` on RPM distros: the override, else derived from the
+/// architecture autopkg was built for and the [`os_family`].
+pub fn arch() -> String {
+    if let Some(arch) = OVERRIDES.lock().unwrap().arch.clone() {
+        return arch;
+    }
+    package_arch(std::env::consts::ARCH, pkg_ext()).to_string()
+}
+
+/// Name of the Rust target architecture `arch` in packages of format `pkg_ext`
+/// (`deb` or `rpm`); Debian naming when the format is unknown.
+fn package_arch<'a>(arch: &'a str, pkg_ext: Option<&str>) -> &'a str {
+    if pkg_ext == Some("rpm") {
+        return match arch {
+            "x86" => "i686",
+            "arm" => "armv7hl",
+            "powerpc64" => "ppc64le",
+            other => other,
+        };
+    }
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "arm" => "armhf",
+        "x86" => "i386",
+        "powerpc64" => "ppc64el",
+        other => other,
+    }
+}
+
+/// Replace `${codename}`, `${arch}`, `${os_family}` and `${pkg_ext}` in a pattern
+/// with the host's values.
+pub fn substitute(pattern: &str) -> Result<String> {
+    let mut result = pattern.to_string();
+    if result.contains("${codename}") {
+        let codename = codename().ok_or_else(|| {
+            anyhow!(
+                "Pattern '{}' uses ${{codename}} but VERSION_CODENAME is not set in /etc/os-release; set settings.codename",
+                pattern
+            )
+        })?;
+        result = result.replace("${codename}", &codename);
+    }
+    if result.contains("${arch}") {
+        result = result.replace("${arch}", &arch());
+    }
+    if result.contains("${os_family}") || result.contains("${pkg_ext}") {
+        let family = os_family().ok_or_else(|| {
+            anyhow!(
+                "Pattern '{}' uses the host's OS family, which could not be detected from /etc/os-release; set settings.os_family",
+                pattern
+            )
+        })?;
+        let ext = pkg_ext().ok_or_else(|| anyhow!("Unknown settings.os_family: {}", family))?;
+        result = result
+            .replace("${os_family}", &family)
+            .replace("${pkg_ext}", ext);
+    }
+    Ok(result)
+}
+
+/// Memory available for new processes (`MemAvailable` in `/proc/meminfo`), in bytes.
+pub fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Host name (from `/proc/sys/kernel/hostname`, else `$HOSTNAME`), or "unknown".
+pub fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// One-minute load average (from `/proc/loadavg`).
+pub fn load_average() -> Option<f64> {
+    let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+/// Parse a size such as `256M`, `1.5G` or `1048576` (bytes); suffixes are
+/// powers of 1024 and an optional trailing `B` is accepted.
+pub fn parse_size(s: &str) -> Result<u64> {
+    let trimmed = s.trim();
+    let upper = trimmed.to_ascii_uppercase();
+    let upper = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, multiplier) = match upper.chars().last() {
+        Some('K') => (&upper[..upper.len() - 1], 1u64 << 10),
+        Some('M') => (&upper[..upper.len() - 1], 1 << 20),
+        Some('G') => (&upper[..upper.len() - 1], 1 << 30),
+        Some('T') => (&upper[..upper.len() - 1], 1 << 40),
+        _ => (upper, 1),
+    };
+    let value: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Invalid size: {} (expected e.g. 256M or 1G)", trimmed))?;
+    if value < 0.0 {
+        return Err(anyhow!("Invalid size: {}", trimmed));
+    }
+    Ok((value * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arch_uses_debian_names_for_deb_and_unknown_formats() {
+        assert_eq!(package_arch("x86_64", Some("deb")), "amd64");
+        assert_eq!(package_arch("aarch64", Some("deb")), "arm64");
+        assert_eq!(package_arch("arm", None), "armhf");
+        assert_eq!(package_arch("riscv64", Some("deb")), "riscv64");
+    }
+
+    #[test]
+    fn arch_uses_rpm_names_for_rpm() {
+        assert_eq!(package_arch("x86_64", Some("rpm")), "x86_64");
+        assert_eq!(package_arch("aarch64", Some("rpm")), "aarch64");
+        assert_eq!(package_arch("x86", Some("rpm")), "i686");
+        assert_eq!(package_arch("powerpc64", Some("rpm")), "ppc64le");
+    }
+}
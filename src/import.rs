@@ -0,0 +1,365 @@
+use crate::config::{ApplicationConfig, Config};
+use crate::fetcher::{create_fetcher, HttpClient};
+use crate::installer::create_installer;
+use crate::lock;
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::Path;
+
+/// One application of an import manifest.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    /// GitHub repo in the form "owner/repo"
+    pub repo: String,
+    /// Asset pattern; the fetcher's default (`*`) if not set
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Installer type (defaults to `deb`)
+    #[serde(default)]
+    pub installer: Option<String>,
+}
+
+/// What an import did (or would do, in a dry run).
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub imported: Vec<String>,
+    pub duplicates: Vec<String>,
+    /// Entries that failed validation, with the reason
+    pub invalid: Vec<(String, String)>,
+}
+
+/// Read a manifest: a YAML (or JSON) list of entries, or, for `.csv` files,
+/// lines of `name,repo,pattern,installer` with an optional header line before
+/// the first entry. CSV fields may be double-quoted to hold commas, with `""`
+/// for a quote.
+pub fn read_manifest(path: &Path) -> Result<Vec<ManifestEntry>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+    if !path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+    {
+        return serde_yaml::from_str(&contents)
+            .with_context(|| format!("Failed to parse manifest {}", path.display()));
+    }
+
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = csv_fields(line)
+            .with_context(|| format!("{}:{}: invalid CSV line", path.display(), i + 1))?;
+        if entries.is_empty()
+            && fields[0] == "name"
+            && fields.get(1).map(String::as_str) == Some("repo")
+        {
+            continue;
+        }
+        if fields.len() < 2 || fields.len() > 4 {
+            return Err(anyhow!(
+                "{}:{}: expected name,repo[,pattern[,installer]]",
+                path.display(),
+                i + 1
+            ));
+        }
+        let optional = |n: usize| {
+            fields
+                .get(n)
+                .filter(|f| !f.is_empty())
+                .map(|f| f.to_string())
+        };
+        entries.push(ManifestEntry {
+            name: fields[0].clone(),
+            repo: fields[1].clone(),
+            pattern: optional(2),
+            installer: optional(3),
+        });
+    }
+    Ok(entries)
+}
+
+/// Split a CSV line into trimmed fields. A field may be enclosed in double
+/// quotes to contain commas; `""` inside quotes is a literal quote.
+fn csv_fields(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+        let mut field = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err(anyhow!("unterminated quoted field")),
+                }
+            }
+            while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+            if !matches!(chars.peek(), None | Some(',')) {
+                return Err(anyhow!("unexpected text after quoted field"));
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                field.push(c);
+            }
+            field.truncate(field.trim_end().len());
+        }
+        fields.push(field);
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+/// Append the manifest's entries to the config file at `config_path`, skipping
+/// applications the config already has (same name, or same repo and pattern)
+/// and entries that don't validate. Importing the same manifest again only adds
+/// what is missing, so an interrupted import can simply be re-run.
+pub fn import(
+    config_path: &Path,
+    entries: &[ManifestEntry],
+    dry_run: bool,
+) -> Result<ImportSummary> {
+    if config_path.is_dir() {
+        return Err(anyhow!(
+            "{} is a config directory; import into one of its files instead",
+            config_path.display()
+        ));
+    }
+    // Held until the new applications are written, so nothing else changes the
+    // config in between
+    let _lock = lock::lock(config_path)?;
+    let config = Config::load(config_path, None)?;
+    if config.settings.config_repo.is_some() {
+        return Err(anyhow!(
+            "{} uses settings.config_repo; import into the repository's config instead",
+            config_path.display()
+        ));
+    }
+
+    let mut known: Vec<(String, String, Vec<String>)> = config
+        .applications
+        .iter()
+        .map(|a| {
+            let patterns = a.fetcher.file_patterns();
+            (
+                a.name.clone(),
+                a.fetcher.repo.clone().unwrap_or_default(),
+                patterns.iter().map(|p| p.to_string()).collect(),
+            )
+        })
+        .collect();
+
+    let mut summary = ImportSummary::default();
+    let mut items = Vec::new();
+    for entry in entries {
+        let value = entry_value(entry);
+        let app = match validate(&value) {
+            Ok(app) => app,
+            Err(e) => {
+                info!("Import: skipping '{}': {:#}", entry.name, e);
+                summary
+                    .invalid
+                    .push((entry.name.clone(), format!("{:#}", e)));
+                continue;
+            }
+        };
+
+        let patterns: Vec<String> = app
+            .fetcher
+            .file_patterns()
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let repo = app.fetcher.repo.clone().unwrap_or_default();
+        if known
+            .iter()
+            .any(|(name, r, p)| *name == app.name || (*r == repo && *p == patterns))
+        {
+            info!("Import: '{}' is already configured", app.name);
+            summary.duplicates.push(app.name);
+            continue;
+        }
+
+        known.push((app.name.clone(), repo, patterns));
+        summary.imported.push(app.name.clone());
+        items.push(app);
+    }
+
+    if !dry_run && !items.is_empty() {
+        let contents = fs::read_to_string(config_path)
+            .with_context(|| format!("Failed to read {}", config_path.display()))?;
+        let updated = append_applications(&contents, &items)?;
+        lock::write_atomically(config_path, updated.as_bytes())?;
+        info!(
+            "Import: added {} application(s) to {}",
+            items.len(),
+            config_path.display()
+        );
+    }
+
+    Ok(summary)
+}
+
+/// The config entry for a manifest entry, as a YAML mapping.
+fn entry_value(entry: &ManifestEntry) -> Value {
+    let mut fetcher = Mapping::new();
+    fetcher.insert("type".into(), "github".into());
+    fetcher.insert("repo".into(), entry.repo.clone().into());
+    if let Some(pattern) = &entry.pattern {
+        fetcher.insert("file_pattern".into(), pattern.clone().into());
+    }
+
+    let mut app = Mapping::new();
+    app.insert("name".into(), entry.name.clone().into());
+    app.insert("fetcher".into(), Value::Mapping(fetcher));
+    app.insert(
+        "installer".into(),
+        entry.installer.as_deref().unwrap_or("deb").into(),
+    );
+    Value::Mapping(app)
+}
+
+/// Parse an entry as an application and build its fetcher and installer, which
+/// checks the repo, patterns and installer type without network access.
+fn validate(value: &Value) -> Result<ApplicationConfig> {
+    let app: ApplicationConfig = serde_yaml::from_value(value.clone())?;
+    if app.name.trim().is_empty() {
+        return Err(anyhow!("name is empty"));
+    }
+    create_fetcher(&app.fetcher, &app, &HttpClient::new(&Default::default())?)?;
+    create_installer(&app.installer, &app)?;
+    Ok(app)
+}
+
+/// Append `items` to the top-level `applications` list of a config file's
+/// text, creating the list if needed. The document is re-serialized, so
+/// comments and formatting are not kept. Unset options are left out.
+fn append_applications(contents: &str, items: &[ApplicationConfig]) -> Result<String> {
+    let mut document: Value = serde_yaml::from_str(contents)?;
+    if document.is_null() {
+        document = Value::Mapping(Mapping::new());
+    }
+    let root = document
+        .as_mapping_mut()
+        .ok_or_else(|| anyhow!("The config file must be a mapping to import into"))?;
+    let applications = root
+        .entry("applications".into())
+        .or_insert(Value::Sequence(Vec::new()));
+    if applications.is_null() {
+        *applications = Value::Sequence(Vec::new());
+    }
+    let applications = applications
+        .as_sequence_mut()
+        .ok_or_else(|| anyhow!("`applications` must be a list to import into"))?;
+    for item in items {
+        let mut value = serde_yaml::to_value(item)?;
+        remove_nulls(&mut value);
+        applications.push(value);
+    }
+    Ok(serde_yaml::to_string(&document)?)
+}
+
+/// Drop `null` entries from the mappings in `value`, recursively.
+fn remove_nulls(value: &mut Value) {
+    match value {
+        Value::Mapping(mapping) => {
+            mapping.retain(|_, v| !v.is_null());
+            mapping.values_mut().for_each(remove_nulls);
+        }
+        Value::Sequence(sequence) => sequence.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `contents` to a file named `name` in a fresh directory for this test.
+    fn write_temp(test: &str, name: &str, contents: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "autopkg-import-test-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_csv_with_quoted_fields_and_header_after_comments() {
+        let path = write_temp(
+            "csv",
+            "apps.csv",
+            "# exported from the old updater\n\nname,repo,pattern,installer\nripgrep, BurntSushi/ripgrep ,\"ripgrep_*_{amd64,arm64}.deb\",deb\n\"say \"\"hi\"\"\",o/hi,,\n",
+        );
+        let entries = read_manifest(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "ripgrep");
+        assert_eq!(entries[0].repo, "BurntSushi/ripgrep");
+        assert_eq!(
+            entries[0].pattern.as_deref(),
+            Some("ripgrep_*_{amd64,arm64}.deb")
+        );
+        assert_eq!(entries[0].installer.as_deref(), Some("deb"));
+        assert_eq!(entries[1].name, "say \"hi\"");
+        assert_eq!(entries[1].pattern, None);
+        assert_eq!(entries[1].installer, None);
+    }
+
+    #[test]
+    fn rejects_unterminated_quote() {
+        let path = write_temp("quote", "apps.csv", "tool,\"o/tool\n");
+        let err = read_manifest(&path).unwrap_err();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert!(format!("{:#}", err).contains("unterminated"), "{:#}", err);
+    }
+
+    #[test]
+    fn imports_list_manifest_into_config_and_skips_duplicates() {
+        let config = write_temp(
+            "list",
+            "autopkg.yml",
+            "applications:\n  - name: bat\n    fetcher:\n      type: github\n      repo: sharkdp/bat\n    installer: deb\n",
+        );
+        let manifest = config.with_file_name("apps.yaml");
+        fs::write(
+            &manifest,
+            "- name: ripgrep\n  repo: BurntSushi/ripgrep\n  pattern: \"*: amd64 #1.deb\"\n- name: bat\n  repo: sharkdp/bat\n- name: broken\n  repo: not-a-repo\n",
+        )
+        .unwrap();
+
+        let entries = read_manifest(&manifest).unwrap();
+        let summary = import(&config, &entries, false).unwrap();
+        let again = import(&config, &entries, false).unwrap();
+        let loaded = Config::load(&config, None).unwrap();
+        fs::remove_dir_all(config.parent().unwrap()).unwrap();
+
+        assert_eq!(summary.imported, ["ripgrep"]);
+        assert_eq!(summary.duplicates, ["bat"]);
+        assert_eq!(summary.invalid.len(), 1);
+        assert!(again.imported.is_empty());
+        let names: Vec<&str> = loaded
+            .applications
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect();
+        assert_eq!(names, ["bat", "ripgrep"]);
+        let patterns = loaded.applications[1].fetcher.file_patterns();
+        assert_eq!(patterns[0].to_string(), "*: amd64 #1.deb");
+    }
+}
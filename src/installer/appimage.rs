@@ -0,0 +1,352 @@
+use crate::config::{ApplicationConfig, DesktopEntry};
+use crate::installer::{check_downloaded_file, install_file_atomically, Installer};
+use crate::types::UpdateCheck;
+use crate::version::Version;
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use regex::Regex;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directory AppImages are installed to when `install_path` isn't set,
+/// relative to the home directory.
+const DEFAULT_INSTALL_DIR: &str = ".local/bin";
+
+/// Version found in `--appimage-version` output or the AppImage's file name
+/// when `version_regex` isn't set: the first dotted number. Unlike for the
+/// binary installer, no suffix is taken, since file names go on with the
+/// architecture (`App-1.2.3-x86_64.AppImage`).
+const DEFAULT_VERSION_REGEX: &str = r"\d+(?:\.\d+)+";
+
+/// Installer for applications shipped as an AppImage. The AppImage is copied
+/// into `install_path` as `<binary_name>-<version>.AppImage`, and the symlink
+/// `<install_path>/<binary_name>` is pointed at it, so the command name stays
+/// the same across updates and the file name records the installed version.
+/// Everything is written to the user's home directory by default, so no root
+/// is needed.
+pub struct AppImageInstaller {
+    binary_name: String,
+    install_dir: PathBuf,
+    version_regex: Regex,
+    desktop_entry: Option<DesktopEntry>,
+    app_name: String,
+    pinned: bool,
+}
+
+/// The user's home directory, from `HOME`.
+fn home_dir() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("HOME is not set; set install_path to an absolute path"))
+}
+
+/// Expand a leading `~/` to the home directory.
+fn expand_home(path: &str) -> Result<PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(rest) => Ok(home_dir()?.join(rest)),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+/// Quote an `Exec` argument of a desktop entry if it needs it.
+fn desktop_exec_arg(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if path.contains(|c: char| c.is_whitespace() || "\"'\\$`".contains(c)) {
+        let escaped = path
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('`', "\\`")
+            .replace('$', "\\$");
+        format!("\"{}\"", escaped)
+    } else {
+        path.into_owned()
+    }
+}
+
+impl AppImageInstaller {
+    pub fn new(app: &ApplicationConfig) -> Result<Self> {
+        let config = &app.installer;
+        let binary_name = config
+            .binary_name
+            .clone()
+            .unwrap_or_else(|| app.name.clone());
+        if binary_name.contains('/') {
+            return Err(anyhow!(
+                "binary_name must be a file name, got {}",
+                binary_name
+            ));
+        }
+
+        let install_dir = match config.install_path.as_deref() {
+            Some(path) => expand_home(path)?,
+            None => home_dir()?.join(DEFAULT_INSTALL_DIR),
+        };
+        if !install_dir.is_absolute() {
+            return Err(anyhow!(
+                "install_path must be absolute or start with ~/, got {}",
+                install_dir.display()
+            ));
+        }
+
+        let pattern = config
+            .version_regex
+            .as_deref()
+            .unwrap_or(DEFAULT_VERSION_REGEX);
+        let version_regex =
+            Regex::new(pattern).with_context(|| format!("Invalid version_regex: {}", pattern))?;
+
+        let desktop_entry = config.desktop_entry.clone();
+        if desktop_entry
+            .as_ref()
+            .is_some_and(|e| e.name.trim().is_empty())
+        {
+            return Err(anyhow!("desktop_entry.name must not be empty"));
+        }
+
+        Ok(Self {
+            binary_name,
+            install_dir,
+            version_regex,
+            desktop_entry,
+            app_name: app.name.clone(),
+            pinned: app.pinned.unwrap_or(false),
+        })
+    }
+
+    /// The symlink the application is run through.
+    fn link_path(&self) -> PathBuf {
+        self.install_dir.join(&self.binary_name)
+    }
+
+    /// Apply `version_regex` to `text`, using the named group `version`, else
+    /// the first group, else the whole match.
+    fn find_version(&self, text: &str) -> Option<String> {
+        let caps = self.version_regex.captures(text)?;
+        caps.name("version")
+            .or_else(|| caps.get(1))
+            .or_else(|| caps.get(0))
+            .map(|m| m.as_str().trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Read the installed version from `<link> --appimage-version`, falling
+    /// back to the file name the link points to.
+    fn get_installed_version(&self) -> Result<Option<String>> {
+        let link = self.link_path();
+        let target = match fs::read_link(&link) {
+            Ok(target) => target,
+            Err(_) if !link.exists() => {
+                info!(
+                    "AppImageInstaller: {} does not exist; assuming not installed",
+                    link.display()
+                );
+                return Ok(None);
+            }
+            Err(_) => {
+                return Err(anyhow!(
+                    "{} is not a symlink to an AppImage; remove it or set binary_name",
+                    link.display()
+                ))
+            }
+        };
+
+        match Command::new(&link).arg("--appimage-version").output() {
+            Ok(output) if output.status.success() => {
+                let text = String::from_utf8_lossy(&output.stdout).into_owned()
+                    + &String::from_utf8_lossy(&output.stderr);
+                if let Some(version) = self.find_version(&text) {
+                    info!(
+                        "AppImageInstaller: found installed version of {}: {}",
+                        link.display(),
+                        version
+                    );
+                    return Ok(Some(version));
+                }
+                debug!(
+                    "AppImageInstaller: no version in {} --appimage-version output: {}",
+                    link.display(),
+                    text.trim()
+                );
+            }
+            Ok(output) => debug!(
+                "AppImageInstaller: {} --appimage-version failed with status {}",
+                link.display(),
+                output.status
+            ),
+            Err(e) => debug!(
+                "AppImageInstaller: failed to run {} --appimage-version: {}",
+                link.display(),
+                e
+            ),
+        }
+
+        let file_name = target
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let version = self.find_version(&file_name).ok_or_else(|| {
+            anyhow!(
+                "Cannot tell the version of {}: version_regex '{}' matches neither its --appimage-version output nor its file name {}",
+                link.display(),
+                self.version_regex,
+                file_name
+            )
+        })?;
+        info!(
+            "AppImageInstaller: found installed version of {} from file name {}: {}",
+            link.display(),
+            file_name,
+            version
+        );
+        Ok(Some(version))
+    }
+
+    /// Whether `path` is an AppImage: an ELF file with the `AI` magic and
+    /// type 1 or 2 at offset 8.
+    fn is_appimage(path: &Path) -> Result<bool> {
+        let mut header = Vec::with_capacity(11);
+        File::open(path)
+            .and_then(|f| f.take(11).read_to_end(&mut header))
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(header.starts_with(b"\x7fELF")
+            && matches!(header.get(8..11), Some(b"AI\x01") | Some(b"AI\x02")))
+    }
+
+    /// Point the symlink at `file_name` in the install directory, replacing
+    /// the previous link atomically.
+    fn relink(&self, file_name: &str) -> Result<()> {
+        let link = self.link_path();
+        let tmp_link = self.install_dir.join(format!(
+            ".{}.autopkg-tmp-{}",
+            self.binary_name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&tmp_link);
+        std::os::unix::fs::symlink(file_name, &tmp_link)
+            .with_context(|| format!("Failed to create symlink {}", tmp_link.display()))?;
+        fs::rename(&tmp_link, &link).with_context(|| {
+            let _ = fs::remove_file(&tmp_link);
+            format!("Failed to move symlink into place at {}", link.display())
+        })
+    }
+
+    /// Write the desktop entry launching the AppImage through its symlink.
+    fn write_desktop_entry(&self, entry: &DesktopEntry) -> Result<()> {
+        let mut contents = format!(
+            "[Desktop Entry]\nType=Application\nName={}\nExec={} %U\nTerminal=false\n",
+            entry.name.trim(),
+            desktop_exec_arg(&self.link_path())
+        );
+        if let Some(icon) = &entry.icon {
+            contents.push_str(&format!("Icon={}\n", expand_home(icon)?.display()));
+        }
+
+        // $XDG_DATA_HOME/applications, where desktop environments look for
+        // per-user entries
+        let dir = match std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+            Some(data_home) => PathBuf::from(data_home).join("applications"),
+            None => home_dir()?.join(".local/share/applications"),
+        };
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(format!("autopkg-{}.desktop", self.app_name));
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write desktop entry {}", path.display()))?;
+        info!("AppImageInstaller: wrote desktop entry {}", path.display());
+        Ok(())
+    }
+}
+
+impl Installer for AppImageInstaller {
+    fn should_check_for_update(&self) -> Result<UpdateCheck> {
+        if self.pinned {
+            info!(
+                "AppImageInstaller: {} is pinned; skipping update check",
+                self.link_path().display()
+            );
+            return Ok(UpdateCheck::No);
+        }
+
+        match self.get_installed_version()? {
+            Some(v) => Ok(UpdateCheck::Yes(Version::upstream(v))),
+            None => Ok(UpdateCheck::Yes(Version::not_installed())),
+        }
+    }
+
+    fn verify_download(&self, file_path: &Path) -> Result<()> {
+        check_downloaded_file(file_path, Some((b"\x7fELF", "AppImage")))?;
+        if !Self::is_appimage(file_path)? {
+            return Err(anyhow!(
+                "Downloaded file {} is an ELF executable but not an AppImage",
+                file_path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    fn install(&self, file_path: &Path) -> Result<()> {
+        // The version goes in the file name so it can be read back without
+        // running the AppImage
+        let download_name = file_path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let file_name = match self.find_version(&download_name) {
+            Some(version) => format!("{}-{}.AppImage", self.binary_name, version),
+            None => {
+                warn!(
+                    "AppImageInstaller: version_regex '{}' does not match {}; its version will be read from --appimage-version only",
+                    self.version_regex, download_name
+                );
+                format!("{}.AppImage", self.binary_name)
+            }
+        };
+
+        fs::create_dir_all(&self.install_dir)
+            .with_context(|| format!("Failed to create {}", self.install_dir.display()))?;
+        let link = self.link_path();
+        let previous = match fs::read_link(&link) {
+            Ok(target) => Some(target),
+            Err(_) if link.exists() => {
+                return Err(anyhow!(
+                    "{} exists and is not a symlink; not replacing it",
+                    link.display()
+                ))
+            }
+            Err(_) => None,
+        };
+
+        let dest = self.install_dir.join(&file_name);
+        info!(
+            "AppImageInstaller: installing {} to {}",
+            file_path.display(),
+            dest.display()
+        );
+        install_file_atomically(file_path, &dest, 0o755)
+            .with_context(|| format!("Failed to install {}", dest.display()))?;
+        self.relink(&file_name)?;
+
+        // Only remove a previous AppImage this installer put there
+        if let Some(previous) = previous.filter(|p| p.as_os_str() != file_name.as_str()) {
+            let name = previous.to_string_lossy();
+            let ours = !name.contains('/')
+                && name.starts_with(&self.binary_name)
+                && name.ends_with(".AppImage");
+            if ours {
+                let old = self.install_dir.join(&previous);
+                match fs::remove_file(&old) {
+                    Ok(()) => info!("AppImageInstaller: removed {}", old.display()),
+                    Err(e) => warn!("Failed to remove {}: {}", old.display(), e),
+                }
+            }
+        }
+
+        if let Some(entry) = &self.desktop_entry {
+            self.write_desktop_entry(entry)?;
+        }
+        Ok(())
+    }
+}
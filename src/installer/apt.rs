@@ -0,0 +1,76 @@
+use crate::config::ApplicationConfig;
+use crate::installer::deb::DebInstaller;
+use crate::installer::{run_as_root, Installer};
+use crate::types::{Resolved, UpdateCheck};
+use crate::version::Version;
+
+use anyhow::{anyhow, Result};
+use log::info;
+use std::path::Path;
+use which::which;
+
+/// Installer for packages from the host's apt repositories.
+///
+/// apt downloads the package itself, so this installer installs a version
+/// (`apt-get install <package>=<version>`) rather than a downloaded file. The
+/// installed version is read as the `deb` installer reads it.
+pub struct AptInstaller {
+    package_name: String,
+    deb: DebInstaller,
+}
+
+impl AptInstaller {
+    pub fn new(app: &ApplicationConfig) -> Result<Self> {
+        Ok(Self {
+            package_name: app.effective_package_name().to_string(),
+            deb: DebInstaller::new(app)?,
+        })
+    }
+}
+
+impl Installer for AptInstaller {
+    fn should_check_for_update(&self) -> Result<UpdateCheck> {
+        self.deb.should_check_for_update()
+    }
+
+    fn install(&self, _file_path: &Path) -> Result<()> {
+        Err(anyhow!(
+            "apt installer installs packages by version from the apt repositories, not from a downloaded file"
+        ))
+    }
+
+    fn handles_fetch(&self) -> bool {
+        true
+    }
+
+    fn install_version(&self, resolved: &Resolved) -> Result<()> {
+        if which("apt-get").is_err() {
+            return Err(anyhow!(
+                "apt-get not found in PATH; required by the apt installer"
+            ));
+        }
+        let spec = format!("{}={}", self.package_name, resolved.version);
+        // The version to converge to may be older than the installed one
+        let command = ["apt-get", "install", "-y", "--allow-downgrades", &spec];
+
+        info!("Running install command: {}", command.join(" "));
+        let status = run_as_root(&command, || "installing apt package")?;
+        if !status.success() {
+            return Err(anyhow!("Installer command failed with status {}", status));
+        }
+        Ok(())
+    }
+
+    fn rollback(&self, previous_version: &Version) -> Result<()> {
+        if *previous_version == Version::not_installed() {
+            return Err(anyhow!(
+                "{} wasn't installed before; remove it by hand if needed",
+                self.package_name
+            ));
+        }
+        self.install_version(&Resolved {
+            version: previous_version.as_str().to_string(),
+            url: None,
+        })
+    }
+}
@@ -0,0 +1,281 @@
+use crate::config::ApplicationConfig;
+use crate::installer::{check_downloaded_file, install_file_atomically, Installer};
+use crate::types::UpdateCheck;
+use crate::version::Version;
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use regex::Regex;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use which::which;
+
+/// Directory executables are installed to when `install_path` isn't set.
+const DEFAULT_INSTALL_DIR: &str = "/usr/local/bin";
+
+/// Version found in `--version` output when `version_regex` isn't set: the
+/// first dotted number, with any pre-release or build suffix.
+const DEFAULT_VERSION_REGEX: &str = r"\d+\.\d+(?:\.\d+)?(?:[-+][0-9A-Za-z.-]+)?";
+
+/// Installer for tools shipped as a single executable, either bare or inside
+/// a tar archive (`.tar.gz`, `.tar.xz`, ...). The executable is copied to
+/// `install_path`; the installed version is read from its `--version` output.
+pub struct BinaryInstaller {
+    binary_name: String,
+    install_path: PathBuf,
+    version_regex: Regex,
+    pinned: bool,
+}
+
+/// What a downloaded file is, from its first bytes.
+#[derive(Debug, PartialEq, Eq)]
+enum DownloadKind {
+    Archive,
+    Executable,
+}
+
+impl BinaryInstaller {
+    pub fn new(app: &ApplicationConfig) -> Result<Self> {
+        let config = &app.installer;
+        let binary_name = config
+            .binary_name
+            .clone()
+            .unwrap_or_else(|| app.name.clone());
+        if binary_name.contains('/') {
+            return Err(anyhow!(
+                "binary_name must be a file name, got {}",
+                binary_name
+            ));
+        }
+
+        // An existing directory (or a trailing slash) gets the binary's name appended
+        let install_path = match config.install_path.as_deref() {
+            None => Path::new(DEFAULT_INSTALL_DIR).join(&binary_name),
+            Some(path) if path.ends_with('/') || Path::new(path).is_dir() => {
+                Path::new(path).join(&binary_name)
+            }
+            Some(path) => PathBuf::from(path),
+        };
+        if !install_path.is_absolute() {
+            return Err(anyhow!(
+                "install_path must be absolute, got {}",
+                install_path.display()
+            ));
+        }
+
+        let pattern = config
+            .version_regex
+            .as_deref()
+            .unwrap_or(DEFAULT_VERSION_REGEX);
+        let version_regex =
+            Regex::new(pattern).with_context(|| format!("Invalid version_regex: {}", pattern))?;
+
+        Ok(Self {
+            binary_name,
+            install_path,
+            version_regex,
+            pinned: app.pinned.unwrap_or(false),
+        })
+    }
+
+    /// Run `<install_path> --version` and extract the version with `version_regex`.
+    fn get_installed_version(&self) -> Result<Option<String>> {
+        if !self.install_path.exists() {
+            info!(
+                "BinaryInstaller: {} does not exist; assuming not installed",
+                self.install_path.display()
+            );
+            return Ok(None);
+        }
+
+        let output = Command::new(&self.install_path)
+            .arg("--version")
+            .output()
+            .with_context(|| format!("Failed to run {} --version", self.install_path.display()))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{} --version failed with status {}",
+                self.install_path.display(),
+                output.status
+            ));
+        }
+
+        // Some tools print their version on stderr
+        let text = String::from_utf8_lossy(&output.stdout).into_owned()
+            + &String::from_utf8_lossy(&output.stderr);
+        let caps = self.version_regex.captures(&text).ok_or_else(|| {
+            anyhow!(
+                "version_regex '{}' does not match the output of {} --version: {}",
+                self.version_regex,
+                self.install_path.display(),
+                text.trim()
+            )
+        })?;
+        let version = caps
+            .name("version")
+            .or_else(|| caps.get(1))
+            .or_else(|| caps.get(0))
+            .map(|m| m.as_str().trim().to_string())
+            .unwrap_or_default();
+
+        info!(
+            "BinaryInstaller: found installed version of {}: {}",
+            self.install_path.display(),
+            version
+        );
+        Ok(Some(version))
+    }
+
+    /// Tell archives from bare executables by their magic bytes.
+    fn download_kind(file_path: &Path) -> Result<Option<DownloadKind>> {
+        let mut header = Vec::with_capacity(512);
+        File::open(file_path)
+            .and_then(|f| f.take(512).read_to_end(&mut header))
+            .with_context(|| format!("Failed to read {}", file_path.display()))?;
+
+        const COMPRESSED: &[&[u8]] = &[
+            b"\x1f\x8b",         // gzip
+            b"\xfd7zXZ\x00",     // xz
+            b"BZh",              // bzip2
+            b"\x28\xb5\x2f\xfd", // zstd
+        ];
+        const EXECUTABLE: &[&[u8]] = &[
+            b"\x7fELF",
+            b"\xcf\xfa\xed\xfe", // Mach-O 64-bit
+            b"\xca\xfe\xba\xbe", // Mach-O universal
+            b"#!",
+        ];
+        let is_tar = header.get(257..262) == Some(b"ustar");
+        if is_tar || COMPRESSED.iter().any(|m| header.starts_with(m)) {
+            return Ok(Some(DownloadKind::Archive));
+        }
+        if EXECUTABLE.iter().any(|m| header.starts_with(m)) {
+            return Ok(Some(DownloadKind::Executable));
+        }
+        Ok(None)
+    }
+
+    /// Extract `archive` into a fresh temporary directory with `tar` and return
+    /// the path of the file named `binary_name` in it.
+    fn extract(&self, archive: &Path) -> Result<(PathBuf, PathBuf)> {
+        if which("tar").is_err() {
+            return Err(anyhow!(
+                "tar not found in PATH; required to extract {}",
+                archive.display()
+            ));
+        }
+
+        // What is extracted here gets installed, so the directory must be a
+        // fresh one only we can write to: creating it fails if another user
+        // put one at this path in the meantime.
+        let dir = std::env::temp_dir().join(format!(
+            "autopkg-extract-{}-{}",
+            self.binary_name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        info!(
+            "BinaryInstaller: extracting {} into {}",
+            archive.display(),
+            dir.display()
+        );
+        let output = Command::new("tar")
+            .arg("-xf")
+            .arg(archive)
+            .arg("-C")
+            .arg(&dir)
+            .output()
+            .context("Failed to run tar")?;
+        if !output.status.success() {
+            let _ = std::fs::remove_dir_all(&dir);
+            return Err(anyhow!(
+                "tar failed to extract {} with status {}: {}",
+                archive.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let pattern = dir.join("**").join(&self.binary_name);
+        let found = glob::glob(&pattern.to_string_lossy())
+            .with_context(|| format!("Invalid binary_name: {}", self.binary_name))?
+            .filter_map(|entry| entry.ok())
+            .find(|p| p.is_file());
+        match found {
+            Some(binary) => Ok((dir, binary)),
+            None => {
+                let _ = std::fs::remove_dir_all(&dir);
+                Err(anyhow!(
+                    "No file named '{}' in {}; set binary_name",
+                    self.binary_name,
+                    archive.display()
+                ))
+            }
+        }
+    }
+}
+
+impl Installer for BinaryInstaller {
+    fn should_check_for_update(&self) -> Result<UpdateCheck> {
+        if self.pinned {
+            info!(
+                "BinaryInstaller: {} is pinned; skipping update check",
+                self.install_path.display()
+            );
+            return Ok(UpdateCheck::No);
+        }
+
+        match self.get_installed_version()? {
+            Some(v) => Ok(UpdateCheck::Yes(Version::upstream(v))),
+            None => Ok(UpdateCheck::Yes(Version::not_installed())),
+        }
+    }
+
+    fn verify_download(&self, file_path: &Path) -> Result<()> {
+        check_downloaded_file(file_path, None)?;
+        if Self::download_kind(file_path)?.is_none() {
+            // Also catches an HTML error page saved in place of the file
+            return Err(anyhow!(
+                "Downloaded file {} is neither a tar archive nor an executable",
+                file_path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    fn install(&self, file_path: &Path) -> Result<()> {
+        let (extracted, binary) = match Self::download_kind(file_path)? {
+            Some(DownloadKind::Archive) => {
+                let (dir, binary) = self.extract(file_path)?;
+                (Some(dir), binary)
+            }
+            _ => (None, file_path.to_path_buf()),
+        };
+
+        info!(
+            "BinaryInstaller: installing {} to {}",
+            binary.display(),
+            self.install_path.display()
+        );
+        let result =
+            install_file_atomically(&binary, &self.install_path, 0o755).with_context(|| {
+                format!(
+                    "Failed to install {}. Do you have permission to write to this directory?",
+                    self.install_path.display()
+                )
+            });
+
+        if let Some(dir) = extracted {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        result
+    }
+}
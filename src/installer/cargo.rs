@@ -0,0 +1,165 @@
+use crate::config::ApplicationConfig;
+use crate::installer::{user_command, Installer};
+use crate::types::{Resolved, UpdateCheck};
+use crate::version::Version;
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use std::path::Path;
+use std::process::Command;
+use which::which;
+
+/// Installer for Rust tools managed with `cargo install`.
+///
+/// Cargo downloads and builds crates itself, so this installer installs a
+/// version rather than a downloaded file.
+pub struct CargoInstaller {
+    crate_name: String,
+    pinned: bool,
+    run_as: Option<String>,
+}
+
+impl CargoInstaller {
+    pub fn new(app: &ApplicationConfig) -> Result<Self> {
+        Ok(Self {
+            crate_name: app.effective_package_name().to_string(),
+            pinned: app.pinned.unwrap_or(false),
+            run_as: app.run_as.clone(),
+        })
+    }
+
+    /// `cargo`, run as the `run_as` user if set.
+    fn cargo(&self) -> Result<Command> {
+        user_command("cargo", self.run_as.as_deref())
+    }
+
+    fn ensure_cargo(&self) -> Result<()> {
+        // The `run_as` user's PATH (e.g. ~/.cargo/bin) is only known once we switch to it
+        if self.run_as.is_none() && which("cargo").is_err() {
+            return Err(anyhow!(
+                "cargo not found in PATH; required by the cargo installer"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parse `cargo install --list` output, whose entries look like `ripgrep v14.1.0:`.
+    fn get_installed_version(&self) -> Result<Option<String>> {
+        self.ensure_cargo()?;
+
+        let output = self
+            .cargo()?
+            .arg("install")
+            .arg("--list")
+            .output()
+            .with_context(|| "Failed to run cargo install --list")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "cargo install --list failed with status {}",
+                output.status
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for line in stdout
+            .lines()
+            .filter(|l| !l.starts_with(char::is_whitespace))
+        {
+            let mut parts = line.split_whitespace();
+            if parts.next() != Some(self.crate_name.as_str()) {
+                continue;
+            }
+            if let Some(version) = parts.next() {
+                let version = version.trim_end_matches(':').trim_start_matches('v');
+                info!(
+                    "CargoInstaller: found installed version for {}: {}",
+                    self.crate_name, version
+                );
+                return Ok(Some(version.to_string()));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl Installer for CargoInstaller {
+    fn should_check_for_update(&self) -> Result<UpdateCheck> {
+        if self.pinned {
+            info!(
+                "CargoInstaller: crate {} is pinned; skipping update check",
+                self.crate_name
+            );
+            return Ok(UpdateCheck::No);
+        }
+
+        match self.get_installed_version()? {
+            Some(v) => Ok(UpdateCheck::Yes(Version::upstream(v))),
+            None => {
+                info!(
+                    "CargoInstaller: crate {} not installed; treating as version 0.0.0",
+                    self.crate_name
+                );
+                Ok(UpdateCheck::Yes(Version::not_installed()))
+            }
+        }
+    }
+
+    fn install(&self, _file_path: &Path) -> Result<()> {
+        Err(anyhow!(
+            "cargo installer installs crates by version, not from a downloaded file"
+        ))
+    }
+
+    fn handles_fetch(&self) -> bool {
+        true
+    }
+
+    fn install_version(&self, resolved: &Resolved) -> Result<()> {
+        self.ensure_cargo()?;
+        let version = &resolved.version;
+
+        info!(
+            "Running install command: cargo install {} --version {} --force",
+            self.crate_name, version
+        );
+        let status = self
+            .cargo()?
+            .arg("install")
+            .arg(&self.crate_name)
+            .arg("--version")
+            .arg(version)
+            .arg("--force")
+            .status()
+            .with_context(|| "Failed to run cargo install")?;
+
+        if !status.success() {
+            return Err(anyhow!("Installer command failed with status {}", status));
+        }
+        Ok(())
+    }
+
+    fn rollback(&self, previous_version: &Version) -> Result<()> {
+        // The crate wasn't installed before (see `should_check_for_update`)
+        if *previous_version == Version::not_installed() {
+            info!(
+                "Running install command: cargo uninstall {}",
+                self.crate_name
+            );
+            let status = self
+                .cargo()?
+                .arg("uninstall")
+                .arg(&self.crate_name)
+                .status()
+                .with_context(|| "Failed to run cargo uninstall")?;
+            if !status.success() {
+                return Err(anyhow!("cargo uninstall failed with status {}", status));
+            }
+            return Ok(());
+        }
+        self.install_version(&Resolved {
+            version: previous_version.to_string(),
+            url: None,
+        })
+    }
+}
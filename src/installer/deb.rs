@@ -1,10 +1,12 @@
-use crate::config::ApplicationConfig;
-use crate::installer::{Installer, run_as_root};
+use crate::config::{ApplicationConfig, DebInstallMethod, DebsigConfig, NeedrestartMode};
+use crate::installer::{check_downloaded_file, run_as_root, AlreadyInstalled, Installer};
 use crate::types::UpdateCheck;
+use crate::version::{Scheme, Version};
 
 use anyhow::{anyhow, Context, Result};
 use log::{info, warn};
-use std::path::Path;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use which::which;
 
@@ -12,16 +14,34 @@ use which::which;
 pub struct DebInstaller {
     package_name: String,
     pinned: bool,
+    check_architecture: bool,
+    allow_reinstall: bool,
+    /// Whether a `version` requirement may move the package back to an older version
+    allow_downgrade: bool,
+    /// Whether to save the installed package and reinstall it if `dpkg -i` fails
+    rollback_on_failure: bool,
+    install_method: DebInstallMethod,
+    debsig: Option<DebsigConfig>,
 }
 
 impl DebInstaller {
     pub fn new(app: &ApplicationConfig) -> Result<Self> {
-        let package_name = app.package_name.clone().unwrap_or_else(|| app.name.clone());
+        let package_name = app.effective_package_name().to_string();
         let pinned = app.pinned.unwrap_or(false);
+        let check_architecture = app.check_architecture.unwrap_or(true);
 
         Ok(Self {
             package_name,
             pinned,
+            check_architecture,
+            allow_reinstall: app.allow_reinstall.unwrap_or(false),
+            allow_downgrade: app.version.is_some(),
+            rollback_on_failure: app.rollback_on_failure.unwrap_or(false),
+            install_method: app
+                .installer
+                .install_method
+                .unwrap_or(DebInstallMethod::Dpkg),
+            debsig: app.debsig.clone(),
         })
     }
 
@@ -62,13 +82,313 @@ impl DebInstaller {
         Ok(None)
     }
 
-    fn run_install_command(&self, file_path: &Path) -> Result<()> {
-        let file_path_str = file_path.display().to_string();
+    /// Refuse to install a package built for a different architecture than the host.
+    /// Packages with `Architecture: all` are accepted on any host.
+    fn verify_architecture(&self, file_path: &Path) -> Result<()> {
+        if which("dpkg-deb").is_err() || which("dpkg").is_err() {
+            warn!("dpkg-deb or dpkg not found in PATH; skipping architecture check");
+            return Ok(());
+        }
+
+        let output = Command::new("dpkg-deb")
+            .arg("-f")
+            .arg(file_path)
+            .arg("Architecture")
+            .output()
+            .with_context(|| "Failed to run dpkg-deb -f")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "dpkg-deb could not read {}: {}",
+                file_path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let package_arch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        let output = Command::new("dpkg")
+            .arg("--print-architecture")
+            .output()
+            .with_context(|| "Failed to run dpkg --print-architecture")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "dpkg --print-architecture failed with status {}",
+                output.status
+            ));
+        }
+        let host_arch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        if package_arch != "all" && package_arch != host_arch {
+            return Err(anyhow!(
+                "Package {} is {} but host is {}; check the fetcher's file_pattern",
+                file_path.display(),
+                package_arch,
+                host_arch
+            ));
+        }
+
+        info!(
+            "DebInstaller: package architecture {} is compatible with host {}",
+            package_arch, host_arch
+        );
+        Ok(())
+    }
+
+    /// Version of the package in `file_path` (`dpkg-deb -f <file> Version`), or
+    /// `None` if dpkg-deb isn't available.
+    fn package_version(&self, file_path: &Path) -> Result<Option<String>> {
+        if which("dpkg-deb").is_err() {
+            warn!("dpkg-deb not found in PATH; cannot read the package version");
+            return Ok(None);
+        }
+
+        let output = Command::new("dpkg-deb")
+            .arg("-f")
+            .arg(file_path)
+            .arg("Version")
+            .output()
+            .with_context(|| "Failed to run dpkg-deb -f")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "dpkg-deb could not read {}: {}",
+                file_path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(Some(version).filter(|v| !v.is_empty()))
+    }
+
+    /// Refuse to reinstall the installed version (an [`AlreadyInstalled`] error)
+    /// or to downgrade, comparing Debian versions with `dpkg --compare-versions`.
+    fn verify_newer(&self, file_path: &Path) -> Result<()> {
+        let Some(package_version) = self.package_version(file_path)? else {
+            return Ok(());
+        };
+        let Some(installed_version) = self.get_installed_version()? else {
+            return Ok(());
+        };
+
+        let compare = |op: &str| -> Result<bool> {
+            let status = Command::new("dpkg")
+                .args([
+                    "--compare-versions",
+                    &package_version,
+                    op,
+                    &installed_version,
+                ])
+                .status()
+                .with_context(|| "Failed to run dpkg --compare-versions")?;
+            Ok(status.success())
+        };
+        if compare("eq")? {
+            return Err(AlreadyInstalled {
+                package: self.package_name.clone(),
+                version: installed_version,
+            }
+            .into());
+        }
+        if compare("lt")? {
+            if self.allow_downgrade {
+                info!(
+                    "DebInstaller: downgrading {} from {} to {} to satisfy its version requirement",
+                    self.package_name, installed_version, package_version
+                );
+                return Ok(());
+            }
+            return Err(anyhow!(
+                "Refusing to downgrade {} from {} to {}; set allow_reinstall to force it",
+                self.package_name,
+                installed_version,
+                package_version
+            ));
+        }
+
+        info!(
+            "DebInstaller: package version {} is newer than installed {}",
+            package_version, installed_version
+        );
+        Ok(())
+    }
+
+    /// Refuse packages whose signature doesn't verify with `debsig-verify`.
+    fn verify_signature(&self, file_path: &Path, debsig: &DebsigConfig) -> Result<()> {
+        if which("debsig-verify").is_err() {
+            return Err(anyhow!(
+                "debsig-verify not found in PATH; required because `debsig` is configured"
+            ));
+        }
+
+        let mut cmd = Command::new("debsig-verify");
+        if let Some(dir) = &debsig.policies_dir {
+            cmd.arg(format!("--policies-dir={}", dir));
+        }
+        if let Some(dir) = &debsig.keyrings_dir {
+            cmd.arg(format!("--keyrings-dir={}", dir));
+        }
+        let output = cmd
+            .arg(file_path)
+            .output()
+            .with_context(|| "Failed to run debsig-verify")?;
+
+        if !output.status.success() {
+            let details = String::from_utf8_lossy(&output.stdout).to_string()
+                + &String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!(
+                "Package {} failed signature verification ({}): {}",
+                file_path.display(),
+                output.status,
+                details.trim()
+            ));
+        }
+
+        info!(
+            "DebInstaller: signature of {} verified",
+            file_path.display()
+        );
+        Ok(())
+    }
+
+    /// Save the installed version of the package as a `.deb`, for
+    /// `rollback_on_failure`: from the apt archives (`apt-get download`), else
+    /// rebuilt from the installed files with `dpkg-repack`. `None` if the package
+    /// isn't installed or couldn't be saved; the upgrade then goes ahead
+    /// without a backup.
+    fn backup_installed(&self) -> Result<Option<(String, PathBuf)>> {
+        let Some(version) = self.get_installed_version()? else {
+            return Ok(None);
+        };
+        // The backup is installed as root on rollback, so it goes in a fresh
+        // directory only we can write to: creating it fails if another user
+        // put one at this path in the meantime.
+        let dir = std::env::temp_dir().join(format!(
+            "autopkg-rollback-{}-{}",
+            self.package_name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
 
-        info!("Running install command: dpkg -i {}", file_path_str);
+        let spec = format!("{}={}", self.package_name, version);
+        let attempts: [(&str, Vec<&str>); 2] = [
+            ("apt-get", vec!["download", &spec]),
+            ("dpkg-repack", vec![&self.package_name]),
+        ];
+        for (program, args) in attempts {
+            if which(program).is_err() {
+                continue;
+            }
+            info!(
+                "DebInstaller: saving {} {} with {}",
+                self.package_name, version, program
+            );
+            let saved = Command::new(program)
+                .args(&args)
+                .current_dir(&dir)
+                .output()
+                .with_context(|| format!("Failed to run {}", program))?;
+            if !saved.status.success() {
+                warn!(
+                    "DebInstaller: {} failed with status {}: {}",
+                    program,
+                    saved.status,
+                    String::from_utf8_lossy(&saved.stderr).trim()
+                );
+                continue;
+            }
+            let deb = std::fs::read_dir(&dir)?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .find(|p| p.extension().is_some_and(|ext| ext == "deb"));
+            if let Some(deb) = deb {
+                return Ok(Some((version, deb)));
+            }
+        }
+
+        warn!(
+            "DebInstaller: could not save {} {} (needs apt-get download or dpkg-repack); upgrading without a rollback backup",
+            self.package_name, version
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(None)
+    }
+
+    /// `dpkg -i` the package, reinstalling the saved previous version if that
+    /// fails and `rollback_on_failure` is set.
+    fn install_with_rollback(&self, file_path: &Path) -> Result<()> {
+        let backup = if self.rollback_on_failure {
+            self.backup_installed()?
+        } else {
+            None
+        };
+        let result = self.run_install_command(file_path, false);
+
+        let Some((version, deb)) = backup else {
+            return result;
+        };
+        let result = match result {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                warn!(
+                    "DebInstaller: install failed; rolling {} back to {}",
+                    self.package_name, version
+                );
+                match self.run_install_command(&deb, true) {
+                    Ok(()) => Err(e.context(format!(
+                        "Install of {} failed; rolled back to {}",
+                        self.package_name, version
+                    ))),
+                    Err(rollback) => Err(e.context(format!(
+                        "Install of {} failed and rollback to {} failed too: {:#}",
+                        self.package_name, version, rollback
+                    ))),
+                }
+            }
+        };
+        if let Some(dir) = deb.parent() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        result
+    }
+
+    /// Install the package with the configured `install_method`. `downgrade`
+    /// lets apt install an older version than the installed one (dpkg always does).
+    fn run_install_command(&self, file_path: &Path, downgrade: bool) -> Result<()> {
+        let command: Vec<String> = match self.install_method {
+            DebInstallMethod::Dpkg => {
+                vec!["dpkg".into(), "-i".into(), file_path.display().to_string()]
+            }
+            DebInstallMethod::Apt => {
+                if which("apt-get").is_err() {
+                    return Err(anyhow!(
+                        "apt-get not found in PATH; required by install_method: apt"
+                    ));
+                }
+                let mut command: Vec<String> =
+                    vec!["apt-get".into(), "install".into(), "-y".into()];
+                if self.allow_reinstall {
+                    command.push("--reinstall".into());
+                }
+                if downgrade || self.allow_reinstall || self.allow_downgrade {
+                    command.push("--allow-downgrades".into());
+                }
+                // A path without a slash would be taken for a package name
+                let local = if file_path.is_absolute() {
+                    file_path.to_path_buf()
+                } else {
+                    Path::new(".").join(file_path)
+                };
+                command.push(local.display().to_string());
+                command
+            }
+        };
+        let command: Vec<&str> = command.iter().map(String::as_str).collect();
+
+        info!("Running install command: {}", command.join(" "));
+
+        let status = run_as_root(&command, || "installing deb package")?;
 
-        let status = run_as_root(&["dpkg", "-i", &file_path_str], ||"installing deb package")?;
-        
         if !status.success() {
             return Err(anyhow!("Installer command failed with status {}", status));
         }
@@ -88,19 +408,121 @@ impl Installer for DebInstaller {
         }
 
         match self.get_installed_version()? {
-            Some(v) => Ok(UpdateCheck::Yes(v)),
+            Some(v) => Ok(UpdateCheck::Yes(Version::new(v, Scheme::Debian))),
             None => {
                 // Treat "not installed" as version "0.0.0"
                 info!(
                     "DebInstaller: package {} not installed; treating as version 0.0.0",
                     self.package_name
                 );
-                Ok(UpdateCheck::Yes("0.0.0".to_string()))
+                Ok(UpdateCheck::Yes(Version::not_installed()))
             }
         }
     }
 
+    fn verify_download(&self, file_path: &Path) -> Result<()> {
+        // .deb files are `ar` archives
+        check_downloaded_file(file_path, Some((b"!<arch>\n", "deb")))?;
+        // Checked here rather than in `install`, so a package that fails is
+        // quarantined like any other rejected download
+        if let Some(debsig) = &self.debsig {
+            self.verify_signature(file_path, debsig)?;
+        }
+        Ok(())
+    }
+
+    fn check_install(&self, file_path: &Path) -> Result<()> {
+        if self.allow_reinstall {
+            return Ok(());
+        }
+        self.verify_newer(file_path)
+    }
+
     fn install(&self, file_path: &Path) -> Result<()> {
-        self.run_install_command(file_path)
+        if self.check_architecture {
+            self.verify_architecture(file_path)?;
+        }
+        self.install_with_rollback(file_path)
+    }
+}
+
+/// Run `needrestart` after a batch of deb installs, either restarting stale
+/// services automatically or reporting which ones need a restart.
+pub fn run_needrestart(mode: NeedrestartMode) -> Result<()> {
+    if which("needrestart").is_err() {
+        warn!("needrestart not found in PATH; skipping service restart check");
+        return Ok(());
+    }
+
+    match mode {
+        NeedrestartMode::Restart => {
+            info!("Running needrestart -r a to restart outdated services");
+            let status = run_as_root(&["needrestart", "-r", "a"], || "running needrestart")?;
+            if !status.success() {
+                return Err(anyhow!("needrestart failed with status {}", status));
+            }
+        }
+        NeedrestartMode::Report => {
+            let output = Command::new("needrestart")
+                .arg("-b")
+                .output()
+                .with_context(|| "Failed to run needrestart -b")?;
+            if !output.status.success() {
+                return Err(anyhow!(
+                    "needrestart -b failed with status {}",
+                    output.status
+                ));
+            }
+
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let services: Vec<&str> = stdout
+                .lines()
+                .filter_map(|line| line.strip_prefix("NEEDRESTART-SVC:"))
+                .map(str::trim)
+                .collect();
+            if services.is_empty() {
+                info!("needrestart: no services need a restart");
+            } else {
+                warn!(
+                    "needrestart: services need a restart: {}",
+                    services.join(", ")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_failing_debsig_is_quarantined() {
+        let app: ApplicationConfig = serde_yaml::from_str(
+            "name: tool\nfetcher:\n  type: url\n  url: https://example.com/tool.deb\ninstaller: deb\ndebsig: {}\n",
+        )
+        .unwrap();
+        let installer = DebInstaller::new(&app).unwrap();
+        let download =
+            std::env::temp_dir().join(format!("autopkg-test-debsig-{}.deb", std::process::id()));
+        std::fs::write(&download, b"!<arch>\nunsigned").unwrap();
+
+        // Fails whether debsig-verify is missing or rejects the package
+        let err = installer.verify_download(&download).unwrap_err();
+        let quarantine = std::env::temp_dir().join(format!(
+            "autopkg-test-debsig-quarantine-{}",
+            std::process::id()
+        ));
+        let err = crate::quarantine::reject("tool", &download, err, Some(&quarantine));
+        assert!(
+            format!("{:#}", err).contains("file quarantined as"),
+            "{:#}",
+            err
+        );
+        assert!(!download.exists());
+        assert_eq!(std::fs::read_dir(&quarantine).unwrap().count(), 2);
+        std::fs::remove_dir_all(quarantine).unwrap();
     }
 }
@@ -0,0 +1,253 @@
+use crate::config::ApplicationConfig;
+use crate::installer::{check_downloaded_file, run_as_root, Installer};
+use crate::types::UpdateCheck;
+use crate::version::Version;
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers the disk image mount points of this process, so images attached by
+/// applications processed in parallel (`run --jobs`) never share one.
+static NEXT_MOUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Installer for macOS `.pkg` installers and `.dmg` disk images holding an `.app` bundle.
+///
+/// The installed version is read from the bundle's `CFBundleShortVersionString`.
+pub struct MacosInstaller {
+    /// Installed bundle, e.g. `/Applications/Obsidian.app`
+    app_path: PathBuf,
+    pinned: bool,
+}
+
+impl MacosInstaller {
+    pub fn new(app: &ApplicationConfig) -> Result<Self> {
+        // `package_name` is the bundle name ("Obsidian" or "Obsidian.app") or an absolute path
+        let name = app.effective_package_name();
+        let app_path = if name.starts_with('/') {
+            PathBuf::from(name)
+        } else if name.ends_with(".app") {
+            Path::new("/Applications").join(name)
+        } else {
+            Path::new("/Applications").join(format!("{}.app", name))
+        };
+
+        Ok(Self {
+            app_path,
+            pinned: app.pinned.unwrap_or(false),
+        })
+    }
+
+    fn get_installed_version(&self) -> Result<Option<String>> {
+        if !self.app_path.exists() {
+            info!(
+                "MacosInstaller: {} does not exist; assuming not installed",
+                self.app_path.display()
+            );
+            return Ok(None);
+        }
+
+        // `defaults` takes the plist path without its extension
+        let info_plist = self.app_path.join("Contents/Info");
+        let output = Command::new("defaults")
+            .arg("read")
+            .arg(&info_plist)
+            .arg("CFBundleShortVersionString")
+            .output()
+            .with_context(|| "Failed to run defaults read")?;
+        if output.status.success() {
+            let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !version.is_empty() {
+                info!(
+                    "MacosInstaller: found installed version for {}: {}",
+                    self.app_path.display(),
+                    version
+                );
+                return Ok(Some(version));
+            }
+        }
+
+        // Fall back to Spotlight metadata
+        let output = Command::new("mdls")
+            .args(["-raw", "-name", "kMDItemVersion"])
+            .arg(&self.app_path)
+            .output()
+            .with_context(|| "Failed to run mdls")?;
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !version.is_empty() && version != "(null)" {
+            info!(
+                "MacosInstaller: found installed version for {} via mdls: {}",
+                self.app_path.display(),
+                version
+            );
+            return Ok(Some(version));
+        }
+
+        warn!(
+            "MacosInstaller: could not read the version of {}",
+            self.app_path.display()
+        );
+        Ok(None)
+    }
+
+    fn install_pkg(&self, file_path: &Path) -> Result<()> {
+        let file_path_str = file_path.display().to_string();
+        info!(
+            "Running install command: installer -pkg {} -target /",
+            file_path_str
+        );
+
+        let status = run_as_root(
+            &["installer", "-pkg", &file_path_str, "-target", "/"],
+            || "installing pkg",
+        )?;
+        if !status.success() {
+            return Err(anyhow!("Installer command failed with status {}", status));
+        }
+        Ok(())
+    }
+
+    fn install_dmg(&self, file_path: &Path) -> Result<()> {
+        let mount_point = std::env::temp_dir().join(format!(
+            "autopkg-dmg-{}-{}",
+            std::process::id(),
+            NEXT_MOUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&mount_point)
+            .with_context(|| format!("Failed to create {}", mount_point.display()))?;
+
+        info!(
+            "MacosInstaller: mounting {} at {}",
+            file_path.display(),
+            mount_point.display()
+        );
+        let status = Command::new("hdiutil")
+            .args(["attach", "-nobrowse", "-readonly", "-quiet", "-mountpoint"])
+            .arg(&mount_point)
+            .arg(file_path)
+            .status()
+            .with_context(|| "Failed to run hdiutil attach")?;
+        if !status.success() {
+            let _ = fs::remove_dir(&mount_point);
+            return Err(anyhow!(
+                "hdiutil attach {} failed with status {}",
+                file_path.display(),
+                status
+            ));
+        }
+
+        let result = self.copy_app_from(&mount_point);
+
+        // Always detach, even if the copy failed
+        let detached = Command::new("hdiutil")
+            .args(["detach", "-quiet"])
+            .arg(&mount_point)
+            .status();
+        if !detached.is_ok_and(|s| s.success()) {
+            warn!("MacosInstaller: failed to detach {}", mount_point.display());
+        }
+        let _ = fs::remove_dir(&mount_point);
+
+        result
+    }
+
+    /// Copy the `.app` bundle found at the root of the mounted image over the
+    /// installed one. The bundle is staged next to the destination first, so a
+    /// failed copy leaves the installed app untouched.
+    fn copy_app_from(&self, mount_point: &Path) -> Result<()> {
+        let bundle_name = self
+            .app_path
+            .file_name()
+            .ok_or_else(|| anyhow!("Invalid app path: {}", self.app_path.display()))?;
+
+        let mut apps: Vec<PathBuf> = fs::read_dir(mount_point)
+            .with_context(|| format!("Failed to read {}", mount_point.display()))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "app"))
+            .collect();
+        apps.sort();
+        let source = match apps.iter().position(|p| p.file_name() == Some(bundle_name)) {
+            Some(i) => apps.remove(i),
+            None if apps.len() == 1 => apps.remove(0),
+            None => {
+                return Err(anyhow!(
+                    "Disk image contains {} app bundles but none named {}",
+                    apps.len(),
+                    bundle_name.to_string_lossy()
+                ))
+            }
+        };
+
+        let staging = self
+            .app_path
+            .with_file_name(format!(".{}.autopkg-tmp", bundle_name.to_string_lossy()));
+        let source_str = source.display().to_string();
+        let staging_str = staging.display().to_string();
+        let dest_str = self.app_path.display().to_string();
+
+        info!("MacosInstaller: copying {} to {}", source_str, dest_str);
+        let steps: [&[&str]; 3] = [
+            &["ditto", &source_str, &staging_str],
+            &["rm", "-rf", &dest_str],
+            &["mv", &staging_str, &dest_str],
+        ];
+        for (i, step) in steps.iter().enumerate() {
+            let status = run_as_root(step, || format!("running {}", step[0]))?;
+            if !status.success() {
+                // Once the old bundle is gone, keep the staged copy for manual recovery
+                if i < 2 {
+                    let _ = run_as_root(&["rm", "-rf", &staging_str], || "cleaning up");
+                }
+                return Err(anyhow!("{} failed with status {}", step.join(" "), status));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Installer for MacosInstaller {
+    fn should_check_for_update(&self) -> Result<UpdateCheck> {
+        if self.pinned {
+            info!(
+                "MacosInstaller: {} is pinned; skipping update check",
+                self.app_path.display()
+            );
+            return Ok(UpdateCheck::No);
+        }
+
+        match self.get_installed_version()? {
+            Some(v) => Ok(UpdateCheck::Yes(Version::upstream(v))),
+            None => Ok(UpdateCheck::Yes(Version::not_installed())),
+        }
+    }
+
+    fn verify_download(&self, file_path: &Path) -> Result<()> {
+        if has_extension(file_path, "pkg") {
+            // Flat .pkg files are xar archives
+            check_downloaded_file(file_path, Some((b"xar!", "pkg")))
+        } else {
+            check_downloaded_file(file_path, None)
+        }
+    }
+
+    fn install(&self, file_path: &Path) -> Result<()> {
+        if has_extension(file_path, "pkg") {
+            self.install_pkg(file_path)
+        } else if has_extension(file_path, "dmg") {
+            self.install_dmg(file_path)
+        } else {
+            Err(anyhow!(
+                "MacosInstaller: don't know how to install {} (expected .pkg or .dmg)",
+                file_path.display()
+            ))
+        }
+    }
+}
+
+fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
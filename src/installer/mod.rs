@@ -1,11 +1,23 @@
+pub mod appimage;
+pub mod apt;
+pub mod binary;
+pub mod cargo;
 pub mod deb;
+#[cfg(target_os = "macos")]
+pub mod macos;
+pub mod rpm;
+pub mod version_file;
 
 use crate::config::{ApplicationConfig, InstallerConfig};
-use crate::types::UpdateCheck;
+use crate::types::{Resolved, UpdateCheck};
+use crate::version::Version;
 use anyhow::{Context, Result, anyhow};
 use std::fmt::Display;
-use std::io::{IsTerminal};
-use std::process::{Command, ExitStatus};
+use std::fs::File;
+use std::io::{IsTerminal, Read};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
 
 /// Trait for installing updates.
 pub trait Installer {
@@ -14,32 +26,174 @@ pub trait Installer {
 
     /// Install the file at `file_path`.
     fn install(&self, file_path: &std::path::Path) -> Result<()>;
+
+    /// Sanity-check a freshly downloaded file before it is installed.
+    /// The default only rejects empty files.
+    fn verify_download(&self, file_path: &Path) -> Result<()> {
+        check_downloaded_file(file_path, None)
+    }
+
+    /// Check, without changing anything, that the file at `file_path` should be
+    /// installed, e.g. that it isn't the installed version (an
+    /// [`AlreadyInstalled`] error). Runs before the `pre_install` hooks, so a
+    /// file that won't be installed never stops a service for nothing.
+    fn check_install(&self, _file_path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether this installer fetches updates by itself (given a version) rather
+    /// than installing a file downloaded by the fetcher.
+    fn handles_fetch(&self) -> bool {
+        false
+    }
+
+    /// Install the resolved version directly, from its URL if the installer
+    /// needs one. Only called when `handles_fetch` returns true.
+    fn install_version(&self, _resolved: &Resolved) -> Result<()> {
+        Err(anyhow!("This installer cannot install by version"))
+    }
+
+    /// Whether this installer can install a package read from a stream instead
+    /// of a downloaded file (see `install_stream`).
+    fn supports_stream(&self) -> bool {
+        false
+    }
+
+    /// Install the package read from `reader`. Only called when
+    /// `supports_stream` returns true.
+    fn install_stream(&self, _reader: &mut dyn Read) -> Result<()> {
+        Err(anyhow!("This installer cannot install from a stream"))
+    }
+
+    /// Reinstall `previous_version` after an unexpected install.
+    fn rollback(&self, _previous_version: &Version) -> Result<()> {
+        Err(anyhow!("This installer cannot roll back"))
+    }
+}
+
+/// Serializes installs when applications are processed in parallel (`run
+/// --jobs`): package managers hold their own locks (dpkg, rpm) and fail rather
+/// than wait, and privilege prompts must not interleave.
+static INSTALLS: Mutex<()> = Mutex::new(());
+
+/// Hold the install slot until the guard is dropped.
+pub fn exclusive() -> std::sync::MutexGuard<'static, ()> {
+    INSTALLS.lock().unwrap_or_else(|e| e.into_inner())
 }
 
-/// Check if sudo is needed and available.
-/// Returns Ok(true) if running as root (sudo not needed).
-/// Returns Ok(false) if not root but sudo is available and terminal is present.
-/// Returns Err if not root and either sudo is unavailable or no terminal is available.
+/// The downloaded package is the version already installed, so the install
+/// was skipped.
+#[derive(Debug, thiserror::Error)]
+#[error("{package} {version} is already installed; not reinstalling")]
+pub struct AlreadyInstalled {
+    pub package: String,
+    pub version: String,
+}
+
+/// Check that a downloaded file is non-empty and, if `magic` is given as
+/// `(bytes, kind)`, that it starts with the expected magic bytes.
+pub fn check_downloaded_file(file_path: &Path, magic: Option<(&[u8], &str)>) -> Result<()> {
+    let mut file = File::open(file_path)
+        .with_context(|| format!("Failed to open downloaded file {}", file_path.display()))?;
+
+    let mut header = [0u8; 512];
+    let mut len = 0;
+    while len < header.len() {
+        let n = file
+            .read(&mut header[len..])
+            .with_context(|| format!("Failed to read downloaded file {}", file_path.display()))?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+    }
+    let header = &header[..len];
+
+    if header.is_empty() {
+        return Err(anyhow!("Downloaded file {} is empty", file_path.display()));
+    }
+
+    if let Some((expected, kind)) = magic {
+        if !header.starts_with(expected) {
+            let trimmed = String::from_utf8_lossy(header).trim_start().to_lowercase();
+            let hint = if trimmed.starts_with("<!doctype html") || trimmed.starts_with("<html") {
+                " (looks like HTML)"
+            } else {
+                ""
+            };
+            return Err(anyhow!(
+                "Downloaded file {} is not a valid {}{}",
+                file_path.display(),
+                kind,
+                hint
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+static PRIVILEGE_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+
+/// Set the command used to run installers as root when autopkg isn't root
+/// (`settings.privilege_command`, e.g. `doas` or `run0`); `sudo` if unset.
+pub fn set_privilege_command(command: Option<String>) {
+    *PRIVILEGE_COMMAND.lock().unwrap() = command;
+}
+
+/// The privilege escalation command as program and arguments.
+fn privilege_command() -> Vec<String> {
+    let command = PRIVILEGE_COMMAND.lock().unwrap().clone();
+    let words: Vec<String> = command
+        .as_deref()
+        .unwrap_or("sudo")
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    if words.is_empty() {
+        vec!["sudo".to_string()]
+    } else {
+        words
+    }
+}
+
+/// Command running `cmd_args` through the privilege escalation command.
+fn escalated(cmd_args: &[&str]) -> Command {
+    let privilege = privilege_command();
+    let mut command = Command::new(&privilege[0]);
+    command.args(&privilege[1..]).args(cmd_args);
+    command
+}
+
+/// Check if privilege escalation (`sudo`, or `settings.privilege_command`) is needed and available.
+/// Returns Ok(true) if running as root (escalation not needed).
+/// Returns Ok(false) if not root but the command is available and terminal is present.
+/// Returns Err if not root and either the command is unavailable or no terminal is available.
 pub fn check_sudo_availability() -> Result<bool> {
     // Check if running as root
     let uid = nix::unistd::getuid();
     if uid.is_root() {
-        return Ok(true); // Running as root, sudo not needed
+        return Ok(true); // Running as root, escalation not needed
     }
 
-    // Not running as root, check if sudo is available
-    if which::which("sudo").is_err() {
-        return Err(anyhow!("Not running as root and sudo is not available in PATH"));
+    // Not running as root, check if the escalation command is available
+    let program = privilege_command().swap_remove(0);
+    if which::which(&program).is_err() {
+        return Err(anyhow!(
+            "Not running as root and {} is not available in PATH",
+            program
+        ));
     }
 
-    // Check if terminal is available for sudo password prompt
+    // Check if terminal is available for a password prompt
     if !std::io::stdin().is_terminal() {
         return Err(anyhow!(
-            "Not running as root and no terminal available for sudo password prompt"
+            "Not running as root and no terminal available for {} password prompt",
+            program
         ));
     }
 
-    Ok(false) // Sudo needed and available
+    Ok(false) // Escalation needed and available
 }
 
 pub fn run_as_root<C, F>(cmd_args: &[&str], context: F) -> Result<ExitStatus>
@@ -49,10 +203,7 @@ where
 {
     if !check_sudo_availability()? {
         // Run as root
-        Command::new("sudo")
-            .args(cmd_args)
-            .status()
-            .with_context(context)
+        escalated(cmd_args).status().with_context(context)
     } else {
         // Already root, no sudo needed
         Command::new(cmd_args[0])
@@ -62,13 +213,210 @@ where
     }
 }
 
-/// Factory for installers.
+/// Like [`run_as_root`], with `input` copied to the command's stdin.
+pub fn run_as_root_with_input<C, F>(
+    cmd_args: &[&str],
+    input: &mut dyn Read,
+    context: F,
+) -> Result<ExitStatus>
+where
+    C: Display + Send + Sync + 'static,
+    F: FnOnce() -> C,
+{
+    let mut command = if !check_sudo_availability()? {
+        escalated(cmd_args)
+    } else {
+        let mut command = Command::new(cmd_args[0]);
+        command.args(&cmd_args[1..]);
+        command
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(context)?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let copied = std::io::copy(input, &mut stdin);
+    drop(stdin);
+    let status = child.wait().context("Failed to wait for command")?;
+    // A command that exits early closes its stdin; its status explains why
+    if status.success() {
+        copied.context("Failed to write to command's stdin")?;
+    }
+    Ok(status)
+}
+
+/// Command running `program` as the application's `run_as` user, with that
+/// user's login environment (`sudo -u <user> -i`), or as the current user if
+/// `run_as` isn't set or already is the current user. Switching users requires
+/// running as root.
+pub fn user_command(program: &str, run_as: Option<&str>) -> Result<Command> {
+    let user = match run_as {
+        Some(user) => user,
+        None => return Ok(Command::new(program)),
+    };
+
+    let uid = nix::unistd::getuid();
+    let current = nix::unistd::User::from_uid(uid).ok().flatten();
+    if current.is_some_and(|u| u.name == user) {
+        return Ok(Command::new(program));
+    }
+    if !uid.is_root() {
+        return Err(anyhow!(
+            "run_as: {} requires running as root (or as {})",
+            user,
+            user
+        ));
+    }
+    if nix::unistd::User::from_name(user).ok().flatten().is_none() {
+        return Err(anyhow!("run_as: unknown user {}", user));
+    }
+
+    let mut command = Command::new("sudo");
+    command.args(["-u", user, "-i", "--", program]);
+    Ok(command)
+}
+
+/// Copy `src` to `dest` atomically: the file is written to a temporary file in
+/// the destination directory, given `mode` (on Unix), flushed, and then renamed
+/// over `dest`, so a concurrently running program never sees a partial file.
+pub fn install_file_atomically(src: &Path, dest: &Path, mode: u32) -> Result<()> {
+    let dir = dest
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid destination path: {}", dest.display()))?;
+    let file_name = dest
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid destination path: {}", dest.display()))?;
+    let tmp_path = dir.join(format!(
+        ".{}.autopkg-tmp-{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = (|| -> Result<()> {
+        std::fs::copy(src, &tmp_path).with_context(|| {
+            format!("Failed to copy {} to {}", src.display(), tmp_path.display())
+        })?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(mode))
+                .with_context(|| format!("Failed to set permissions on {}", tmp_path.display()))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        File::open(&tmp_path)
+            .and_then(|f| f.sync_all())
+            .with_context(|| format!("Failed to flush {}", tmp_path.display()))?;
+
+        std::fs::rename(&tmp_path, dest).with_context(|| {
+            format!(
+                "Failed to move {} into place at {}",
+                tmp_path.display(),
+                dest.display()
+            )
+        })
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Installer type to use for `type`: `auto` becomes the host's native package
+/// format (`deb` or `rpm`); other types are returned as they are.
+pub fn resolve_type(r#type: &str) -> Result<String> {
+    if r#type != "auto" {
+        return Ok(r#type.to_string());
+    }
+    crate::host::pkg_ext().map(str::to_string).ok_or_else(|| {
+        anyhow!(
+            "installer: auto could not detect the host's package format; set settings.os_family"
+        )
+    })
+}
+
+/// Factory for installers. With `version_file` set, the installer is wrapped
+/// to read the installed version from that file.
 pub fn create_installer(
     config: &InstallerConfig,
     app: &ApplicationConfig,
 ) -> Result<Box<dyn Installer>> {
-    match config.r#type.as_str() {
+    let installer = create_base_installer(config, app)?;
+    match &app.version_file {
+        Some(version_file) => Ok(Box::new(version_file::VersionFileInstaller::new(
+            installer,
+            version_file,
+            app.pinned.unwrap_or(false),
+        )?)),
+        None => Ok(installer),
+    }
+}
+
+/// The installer that reports `app`'s installed version: the `recorded` type
+/// that installed it (a `prefer` entry may choose another installer than the
+/// application's), else the application's own.
+pub fn create_reporting_installer(
+    app: &ApplicationConfig,
+    recorded: Option<&str>,
+) -> Result<Box<dyn Installer>> {
+    match recorded {
+        Some(r#type) if r#type != app.installer.r#type => {
+            create_installer(&InstallerConfig::of_type(r#type), app)
+        }
+        _ => create_installer(&app.installer, app),
+    }
+}
+
+fn create_base_installer(
+    config: &InstallerConfig,
+    app: &ApplicationConfig,
+) -> Result<Box<dyn Installer>> {
+    let r#type = resolve_type(&config.r#type)?;
+    check_fields(config, &r#type)?;
+    match r#type.as_str() {
         "deb" => Ok(Box::new(deb::DebInstaller::new(app)?)),
+        "rpm" => Ok(Box::new(rpm::RpmInstaller::new(app)?)),
+        "cargo" => Ok(Box::new(cargo::CargoInstaller::new(app)?)),
+        "apt" => Ok(Box::new(apt::AptInstaller::new(app)?)),
+        "binary" => Ok(Box::new(binary::BinaryInstaller::new(app)?)),
+        "appimage" => Ok(Box::new(appimage::AppImageInstaller::new(app)?)),
+        #[cfg(target_os = "macos")]
+        "macos" => Ok(Box::new(macos::MacosInstaller::new(app)?)),
+        #[cfg(not(target_os = "macos"))]
+        "macos" => Err(anyhow!("The macos installer is only available on macOS")),
         other => Err(anyhow!("Unknown installer type: {}", other)),
     }
 }
+
+/// Reject installer fields that the resolved installer type ignores. With
+/// `type: auto` the deb-only fields are allowed, since the type depends on the host.
+fn check_fields(config: &InstallerConfig, r#type: &str) -> Result<()> {
+    const FILES: &[&str] = &["binary", "appimage"];
+    let fields: [(&str, bool, &[&str]); 5] = [
+        ("install_method", config.install_method.is_some(), &["deb"]),
+        ("binary_name", config.binary_name.is_some(), FILES),
+        ("install_path", config.install_path.is_some(), FILES),
+        ("version_regex", config.version_regex.is_some(), FILES),
+        (
+            "desktop_entry",
+            config.desktop_entry.is_some(),
+            &["appimage"],
+        ),
+    ];
+    for (field, set, types) in fields {
+        let auto_deb = config.r#type == "auto" && types.contains(&"deb");
+        if set && !types.contains(&r#type) && !auto_deb {
+            return Err(anyhow!(
+                "{} is not valid for installer type '{}' (it applies to: {})",
+                field,
+                r#type,
+                types.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use crate::config::ApplicationConfig;
+use crate::installer::{check_downloaded_file, run_as_root, run_as_root_with_input, Installer};
+use crate::types::UpdateCheck;
+use crate::version::{Scheme, Version};
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+use which::which;
+
+/// Installer for RPM (.rpm) packages.
+///
+/// Packages are installed with `rpm -U`, which reads streamed packages from
+/// stdin. Dependencies are not resolved.
+pub struct RpmInstaller {
+    package_name: String,
+    pinned: bool,
+    /// Whether a `version` requirement may move the package back to an older version
+    allow_downgrade: bool,
+}
+
+impl RpmInstaller {
+    pub fn new(app: &ApplicationConfig) -> Result<Self> {
+        Ok(Self {
+            package_name: app.effective_package_name().to_string(),
+            pinned: app.pinned.unwrap_or(false),
+            allow_downgrade: app.version.is_some(),
+        })
+    }
+
+    fn get_installed_version(&self) -> Result<Option<String>> {
+        if which("rpm").is_err() {
+            warn!("rpm not found in PATH; cannot query installed version");
+            return Ok(None);
+        }
+
+        let output = Command::new("rpm")
+            .args(["-q", "--queryformat", "%{VERSION}-%{RELEASE}"])
+            .arg(&self.package_name)
+            .output()
+            .with_context(|| "Failed to run rpm -q")?;
+
+        if !output.status.success() {
+            info!(
+                "rpm -q {} failed with status {}; assuming not installed",
+                self.package_name, output.status
+            );
+            return Ok(None);
+        }
+
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if version.is_empty() {
+            return Ok(None);
+        }
+        info!(
+            "RpmInstaller: found installed version for {}: {}",
+            self.package_name, version
+        );
+        Ok(Some(version))
+    }
+}
+
+impl Installer for RpmInstaller {
+    fn should_check_for_update(&self) -> Result<UpdateCheck> {
+        if self.pinned {
+            info!(
+                "RpmInstaller: package {} is pinned; skipping update check",
+                self.package_name
+            );
+            return Ok(UpdateCheck::No);
+        }
+
+        match self.get_installed_version()? {
+            Some(v) => Ok(UpdateCheck::Yes(Version::new(v, Scheme::Rpm))),
+            None => {
+                info!(
+                    "RpmInstaller: package {} not installed; treating as version 0.0.0",
+                    self.package_name
+                );
+                Ok(UpdateCheck::Yes(Version::not_installed()))
+            }
+        }
+    }
+
+    fn verify_download(&self, file_path: &Path) -> Result<()> {
+        // RPM lead magic
+        check_downloaded_file(file_path, Some((b"\xed\xab\xee\xdb", "rpm")))
+    }
+
+    fn install(&self, file_path: &Path) -> Result<()> {
+        if which("rpm").is_err() {
+            return Err(anyhow!(
+                "rpm not found in PATH; required by the rpm installer"
+            ));
+        }
+        let file_path_str = file_path.display().to_string();
+        let mut command = vec!["rpm", "-U", "--replacepkgs"];
+        if self.allow_downgrade {
+            command.push("--oldpackage");
+        }
+        command.push(&file_path_str);
+
+        info!("Running install command: {}", command.join(" "));
+        let status = run_as_root(&command, || "installing rpm package")?;
+        if !status.success() {
+            return Err(anyhow!("Installer command failed with status {}", status));
+        }
+        Ok(())
+    }
+
+    fn supports_stream(&self) -> bool {
+        true
+    }
+
+    fn install_stream(&self, reader: &mut dyn Read) -> Result<()> {
+        let command: &[&str] = if self.allow_downgrade {
+            &["rpm", "-U", "--replacepkgs", "--oldpackage", "-"]
+        } else {
+            &["rpm", "-U", "--replacepkgs", "-"]
+        };
+        info!(
+            "Running install command: {} (package on stdin)",
+            command.join(" ")
+        );
+        let status = run_as_root_with_input(command, reader, || "installing rpm package")?;
+        if !status.success() {
+            return Err(anyhow!("Installer command failed with status {}", status));
+        }
+        Ok(())
+    }
+}
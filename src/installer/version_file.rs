@@ -0,0 +1,130 @@
+use crate::config::VersionFile;
+use crate::installer::Installer;
+use crate::types::{Resolved, UpdateCheck};
+use crate::version::Version;
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use regex::Regex;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Wraps an installer to read the installed version from a file (`version_file`)
+/// instead of asking the installer, e.g. for apps deployed as a directory with a
+/// `VERSION` file. Everything else is delegated to the wrapped installer.
+pub struct VersionFileInstaller {
+    inner: Box<dyn Installer>,
+    path: PathBuf,
+    regex: Option<Regex>,
+    pinned: bool,
+}
+
+impl VersionFileInstaller {
+    pub fn new(inner: Box<dyn Installer>, config: &VersionFile, pinned: bool) -> Result<Self> {
+        let regex = config
+            .regex
+            .as_deref()
+            .map(|r| Regex::new(r).with_context(|| format!("Invalid version_file regex: {}", r)))
+            .transpose()?;
+        Ok(Self {
+            inner,
+            path: PathBuf::from(&config.path),
+            regex,
+            pinned,
+        })
+    }
+
+    fn get_installed_version(&self) -> Result<Option<String>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!(
+                    "VersionFile: {} does not exist; assuming not installed",
+                    self.path.display()
+                );
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.path.display()))
+            }
+        };
+
+        let version = match &self.regex {
+            None => contents.trim().to_string(),
+            Some(regex) => {
+                let caps = regex.captures(&contents).ok_or_else(|| {
+                    anyhow!(
+                        "version_file regex '{}' does not match {}",
+                        regex,
+                        self.path.display()
+                    )
+                })?;
+                caps.name("version")
+                    .or_else(|| caps.get(1))
+                    .or_else(|| caps.get(0))
+                    .map(|m| m.as_str().trim().to_string())
+                    .unwrap_or_default()
+            }
+        };
+
+        if version.is_empty() {
+            return Err(anyhow!("No version found in {}", self.path.display()));
+        }
+        info!(
+            "VersionFile: found installed version in {}: {}",
+            self.path.display(),
+            version
+        );
+        Ok(Some(version))
+    }
+}
+
+impl Installer for VersionFileInstaller {
+    fn should_check_for_update(&self) -> Result<UpdateCheck> {
+        if self.pinned {
+            info!(
+                "VersionFile: {} is pinned; skipping update check",
+                self.path.display()
+            );
+            return Ok(UpdateCheck::No);
+        }
+
+        match self.get_installed_version()? {
+            Some(v) => Ok(UpdateCheck::Yes(Version::upstream(v))),
+            None => Ok(UpdateCheck::Yes(Version::not_installed())),
+        }
+    }
+
+    fn install(&self, file_path: &Path) -> Result<()> {
+        self.inner.install(file_path)
+    }
+
+    fn verify_download(&self, file_path: &Path) -> Result<()> {
+        self.inner.verify_download(file_path)
+    }
+
+    fn check_install(&self, file_path: &Path) -> Result<()> {
+        self.inner.check_install(file_path)
+    }
+
+    fn handles_fetch(&self) -> bool {
+        self.inner.handles_fetch()
+    }
+
+    fn install_version(&self, resolved: &Resolved) -> Result<()> {
+        self.inner.install_version(resolved)
+    }
+
+    fn supports_stream(&self) -> bool {
+        self.inner.supports_stream()
+    }
+
+    fn install_stream(&self, reader: &mut dyn Read) -> Result<()> {
+        self.inner.install_stream(reader)
+    }
+
+    fn rollback(&self, previous_version: &Version) -> Result<()> {
+        self.inner.rollback(previous_version)
+    }
+}
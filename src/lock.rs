@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// Serializes writes to shared files between threads of this process; the
+/// file lock alone doesn't, since a process may hold it only once.
+static WRITES: Mutex<()> = Mutex::new(());
+
+/// Exclusive lock on a shared file (state, history, config), held until dropped.
+pub struct FileLock {
+    _file: File,
+    _guard: MutexGuard<'static, ()>,
+}
+
+/// Lock `path` against concurrent writers in this and other processes, via an
+/// exclusive `flock` on `<path>.lock` (the file itself is replaced on every
+/// write, so it can't carry the lock). Blocks until the lock is free.
+pub fn lock(path: &Path) -> Result<FileLock> {
+    let guard = WRITES.lock().unwrap_or_else(|e| e.into_inner());
+    let lock_path = PathBuf::from(format!("{}.lock", path.display()));
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open lock file {}", lock_path.display()))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+    Ok(FileLock {
+        _file: file,
+        _guard: guard,
+    })
+}
+
+/// Lock file held for the whole of a `run` unless `--lock-file` says otherwise.
+pub const RUN_LOCK_FILE: &str = "/var/run/autopkg.lock";
+
+/// Exclusive lock held for the duration of a run, released when dropped (or
+/// when the process exits).
+pub struct RunLock {
+    _file: File,
+}
+
+/// Path of the run lock: `path` if given, else [`RUN_LOCK_FILE`] when running as
+/// root, else a per-user file in the temp directory (unprivileged users can't
+/// create files in `/var/run`).
+pub fn run_lock_path(path: Option<&Path>) -> PathBuf {
+    match path {
+        Some(path) => path.to_path_buf(),
+        None if nix::unistd::geteuid().is_root() => PathBuf::from(RUN_LOCK_FILE),
+        None => std::env::temp_dir().join(format!("autopkg-{}.lock", nix::unistd::geteuid())),
+    }
+}
+
+/// Take the run lock at `path` without waiting, recording our PID in it.
+/// `None` if another process holds it.
+pub fn try_lock_run(path: &Path) -> Result<Option<RunLock>> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => return Ok(None),
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+        }
+    }
+    file.set_len(0)
+        .and_then(|()| writeln!(file, "{}", std::process::id()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(Some(RunLock { _file: file }))
+}
+
+/// When the run holding the run lock at `path` took it (the lock file is
+/// rewritten with the PID then), or `None` if no run holds it.
+pub fn running_since(path: &Path) -> Result<Option<SystemTime>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to open lock file {}", path.display()))
+        }
+    };
+    match file.try_lock() {
+        Ok(()) => Ok(None),
+        Err(TryLockError::WouldBlock) => Ok(Some(
+            file.metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or_else(|_| SystemTime::now()),
+        )),
+        Err(TryLockError::Error(e)) => {
+            Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+        }
+    }
+}
+
+/// Replace `path` with `contents` via a temporary file in the same directory,
+/// flushed to disk and renamed into place, so readers never see a partial
+/// file. An existing file's permissions are kept. Callers serialize writers
+/// with [`lock`].
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid path: {}", path.display()))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.autopkg-tmp-{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = (|| -> Result<()> {
+        let mut file =
+            File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?;
+        file.write_all(contents)
+            .and_then(|()| file.sync_all())
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&tmp, metadata.permissions())?;
+        }
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to move {} into place", tmp.display()))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
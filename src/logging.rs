@@ -0,0 +1,56 @@
+use std::cell::RefCell;
+use std::io::Write;
+
+thread_local! {
+    /// Name of the application currently being processed on this thread.
+    static CURRENT_APP: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Initialize the logger to stderr. Every line logged while an [`AppContext`]
+/// is active is prefixed with `app=<name>` so interleaved output stays attributable.
+pub fn init(log_level: &str) {
+    // Respect RUST_LOG if already set
+    std::env::set_var(
+        "RUST_LOG",
+        std::env::var("RUST_LOG").unwrap_or_else(|_| format!("autopkg={}", log_level)),
+    );
+    env_logger::builder()
+        .format(|buf, record| {
+            let style = buf.default_level_style(record.level());
+            let app = CURRENT_APP.with(|app| {
+                app.borrow()
+                    .as_ref()
+                    .map(|name| format!("app={} ", name))
+                    .unwrap_or_default()
+            });
+            writeln!(
+                buf,
+                "[{} {style}{:<5}{style:#}] {}{}",
+                buf.timestamp_seconds(),
+                record.level(),
+                app,
+                record.args()
+            )
+        })
+        .target(env_logger::Target::Stderr)
+        .init();
+}
+
+/// Guard that tags log lines on the current thread with an application name
+/// until it is dropped.
+pub struct AppContext {
+    previous: Option<String>,
+}
+
+impl AppContext {
+    pub fn enter(name: &str) -> Self {
+        let previous = CURRENT_APP.with(|app| app.replace(Some(name.to_string())));
+        Self { previous }
+    }
+}
+
+impl Drop for AppContext {
+    fn drop(&mut self) {
+        CURRENT_APP.with(|app| *app.borrow_mut() = self.previous.take());
+    }
+}
@@ -1,12 +1,31 @@
+mod cache;
+mod collector;
 mod config;
+mod config_repo;
 mod fetcher;
+mod gc;
+mod history;
+mod hooks;
+mod host;
+mod import;
 mod installer;
+mod lock;
+mod logging;
+mod notify;
+mod quarantine;
+mod report;
+mod state;
+mod status;
 mod types;
+mod version;
 
-use crate::config::Config;
-use crate::fetcher::create_fetcher;
+use crate::config::{Config, PostInstallMismatch};
+use crate::fetcher::{create_fetcher, HttpClient, RateLimited};
 use crate::installer::create_installer;
-use crate::types::UpdateCheck;
+use crate::report::{Action, AppReport, ReportFormat, RunReport};
+use crate::state::{LastCheck, State};
+use crate::types::{Download, Resolved, UpdateCheck};
+use crate::version::{should_replace, Version, VersionRequirement};
 
 // Embedded template files
 const DEFAULT_CONFIG: &str = include_str!("../config/default_config.yml");
@@ -15,10 +34,13 @@ const SYSTEMD_TIMER: &str = include_str!("../systemd/autopkg.timer");
 
 use anyhow::{anyhow, Context, Result};
 use clap::{Parser, Subcommand};
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
 
 /// Auto-updater tool for applications defined in a YAML config.
 #[derive(Parser, Debug)]
@@ -29,6 +51,19 @@ struct Cli {
     #[arg(long, value_name = "LEVEL", default_value = "info", global = true)]
     log_level: String,
 
+    /// Config profile to apply over the base application settings
+    #[arg(long, value_name = "NAME", global = true)]
+    profile: Option<String>,
+
+    /// Log method, URL, headers (secrets redacted) and status of every HTTP call at debug level
+    #[arg(long, global = true)]
+    trace_http: bool,
+
+    /// Override a config value after loading, e.g. `app.firefox.pinned=true` or
+    /// `settings.max_concurrent_downloads=1` (repeatable)
+    #[arg(long = "set", value_name = "PATH=VALUE", global = true)]
+    overrides: Vec<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -44,6 +79,81 @@ enum Commands {
         /// Check for updates without installing
         #[arg(long)]
         dry_run: bool,
+
+        /// Never access the network; only use data already available locally
+        #[arg(long, visible_alias = "no-network")]
+        offline: bool,
+
+        /// With --dry-run, compare each selected asset's file name with the one last installed
+        #[arg(long, requires = "dry_run")]
+        diff_assets: bool,
+
+        /// Lock file that keeps runs from overlapping (default: /var/run/autopkg.lock as root)
+        #[arg(long, value_name = "PATH")]
+        lock_file: Option<PathBuf>,
+
+        /// Stop at the first application that fails instead of processing the rest
+        #[arg(long)]
+        fail_fast: bool,
+
+        /// Check every application, even those checked within their `check_interval`
+        #[arg(long)]
+        force: bool,
+
+        /// Ignore cached GitHub release metadata and version_url responses and fetch them again in full
+        #[arg(long)]
+        refresh: bool,
+
+        /// Number of applications to process in parallel (installs still run one at a time)
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+
+        /// Only process the application with this name (repeatable)
+        #[arg(long, value_name = "NAME")]
+        only: Vec<String>,
+
+        /// Only process applications with this `schedule`
+        #[arg(long, value_name = "SCHEDULE")]
+        schedule: Option<String>,
+
+        /// Only process applications without a `schedule`
+        #[arg(long, conflicts_with = "schedule")]
+        unscheduled: bool,
+
+        /// Only process applications in this `group`
+        #[arg(long, value_name = "NAME")]
+        group: Option<String>,
+
+        /// Only process applications with this tag in `tags`
+        #[arg(long, value_name = "TAG")]
+        tag: Option<String>,
+
+        /// Don't print the summary line at the end of the run
+        #[arg(long)]
+        quiet: bool,
+
+        /// Print a report of the run to stdout in the given format
+        #[arg(long, value_name = "FORMAT", value_enum)]
+        report_format: Option<ReportFormat>,
+
+        /// Write a JSON report of the run to this file when it finishes
+        #[arg(long, value_name = "PATH")]
+        report: Option<PathBuf>,
+    },
+
+    /// Show installed and latest versions of each application, without downloading or installing
+    Status {
+        /// Path to config file (default: autopkg.yml in current directory)
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+
+        /// Print a JSON array instead of a table
+        #[arg(long)]
+        json: bool,
+
+        /// Ignore cached GitHub release metadata and version_url responses and fetch them again in full
+        #[arg(long)]
+        refresh: bool,
     },
 
     /// Show the parsed configuration
@@ -51,6 +161,92 @@ enum Commands {
         /// Path to config file (default: autopkg.yml in current directory)
         #[arg(long, value_name = "PATH")]
         config: Option<PathBuf>,
+
+        /// Only show applications in this `group`
+        #[arg(long, value_name = "NAME")]
+        group: Option<String>,
+
+        /// Only show applications with this tag in `tags`
+        #[arg(long, value_name = "TAG")]
+        tag: Option<String>,
+    },
+
+    /// Check that the config parses and every application's fetcher and installer can be set up
+    Validate {
+        /// Path to config file (default: autopkg.yml in current directory)
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+    },
+
+    /// List the assets of an application's latest release and whether each matches its pattern
+    ListAssets {
+        /// Path to config file (default: autopkg.yml in current directory)
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+
+        /// Name of the application to inspect
+        #[arg(long, value_name = "NAME")]
+        name: String,
+    },
+
+    /// Show the audit history of past runs
+    History {
+        /// Path to config file (default: autopkg.yml in current directory)
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+
+        /// Check the signature chain and report tampered or missing records
+        #[arg(long)]
+        verify: bool,
+    },
+
+    /// Remove stale downloads and partial files
+    #[command(visible_alias = "cleanup")]
+    Gc {
+        /// Path to config file (default: autopkg.yml in current directory)
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+
+        /// Remove downloads older than this, e.g. "2d" (default: settings.gc.download_ttl, else 7 days)
+        #[arg(long, value_name = "AGE")]
+        older_than: Option<String>,
+
+        /// Lock file of the runs whose downloads must be kept (same default as `run --lock-file`)
+        #[arg(long, value_name = "PATH")]
+        lock_file: Option<PathBuf>,
+    },
+
+    /// Add applications from a manifest to the config file
+    Import {
+        /// Path to config file (default: autopkg.yml in current directory)
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+
+        /// Manifest to import: a YAML list of {name, repo, pattern, installer}, or a .csv file
+        #[arg(long, value_name = "PATH")]
+        from: PathBuf,
+
+        /// Report what would be imported without changing the config
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show how two version strings are normalized and compared
+    #[command(hide = true)]
+    ExplainVersion {
+        /// Installed (current) version
+        a: String,
+
+        /// Candidate (remote) version
+        b: String,
+
+        /// Scheme of the installed version (as reported by the installer)
+        #[arg(long, value_enum, default_value = "upstream")]
+        scheme_a: version::Scheme,
+
+        /// Scheme of the candidate version
+        #[arg(long, value_enum, default_value = "upstream")]
+        scheme_b: version::Scheme,
     },
 
     /// Install autopkg binary, config, and systemd units
@@ -62,6 +258,33 @@ enum Commands {
         /// Config file path (default: /etc/autopkg/config.yml)
         #[arg(long, value_name = "PATH", default_value = "/etc/autopkg/config.yml")]
         config_path: PathBuf,
+
+        /// Finish with a dry run of the installed config, reporting any application that fails
+        #[arg(long)]
+        verify_config: bool,
+
+        /// When the timer runs, as a systemd OnCalendar expression (default: daily)
+        #[arg(long, value_name = "ONCALENDAR")]
+        timer_interval: Option<String>,
+
+        /// Random delay added to each timer run, e.g. "30m" (default: 1h)
+        #[arg(long, value_name = "DURATION")]
+        randomized_delay: Option<String>,
+    },
+
+    /// Stop the timers and remove the binary and systemd units installed by self-install
+    SelfUninstall {
+        /// Directory the binary was installed to (default: /usr/local/bin)
+        #[arg(long, value_name = "PATH", default_value = "/usr/local/bin")]
+        install_dir: PathBuf,
+
+        /// Config file path (default: /etc/autopkg/config.yml)
+        #[arg(long, value_name = "PATH", default_value = "/etc/autopkg/config.yml")]
+        config_path: PathBuf,
+
+        /// Also remove the config file
+        #[arg(long)]
+        purge: bool,
     },
 }
 
@@ -69,106 +292,1316 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
 
     // Initialize logger to stderr
-    std::env::set_var(
-        "RUST_LOG",
-        std::env::var("RUST_LOG").unwrap_or_else(|_| format!("autopkg={}", cli.log_level)),
-    );
-    env_logger::builder()
-        .format_target(false)
-        .format_timestamp_secs()
-        .target(env_logger::Target::Stderr)
-        .init();
+    logging::init(&cli.log_level);
+    fetcher::set_trace_http(cli.trace_http);
 
+    let profile = cli.profile.as_deref();
+    let overrides = cli.overrides.as_slice();
     match cli.command {
-        Commands::Run { config, dry_run } => run_command(config, dry_run),
-        Commands::ShowConfig { config } => show_config_command(config),
+        Commands::Run {
+            config,
+            dry_run,
+            offline,
+            diff_assets,
+            lock_file,
+            fail_fast,
+            force,
+            refresh,
+            jobs,
+            only,
+            schedule,
+            unscheduled,
+            group,
+            tag,
+            quiet,
+            report_format,
+            report,
+        } => run_command(
+            config,
+            profile,
+            overrides,
+            RunOptions {
+                dry_run,
+                offline,
+                diff_assets,
+                lock_file,
+                fail_fast,
+                force,
+                refresh,
+                jobs,
+                quiet,
+                report_path: report,
+                verify_only: false,
+            },
+            Selection {
+                only,
+                schedule,
+                unscheduled,
+                group,
+                tag,
+            },
+            report_format,
+        )
+        .and_then(|report| {
+            let failed = report.count(Action::Failed);
+            if failed > 0 {
+                return Err(anyhow!(
+                    "{} of {} processed application(s) failed",
+                    failed,
+                    report.applications.len()
+                ));
+            }
+            Ok(())
+        }),
+        Commands::Status {
+            config,
+            json,
+            refresh,
+        } => status_command(config, profile, overrides, json, refresh),
+        Commands::ShowConfig { config, group, tag } => show_config_command(
+            config,
+            profile,
+            overrides,
+            Selection {
+                only: Vec::new(),
+                schedule: None,
+                unscheduled: false,
+                group,
+                tag,
+            },
+        ),
+        Commands::Validate { config } => validate_command(config, profile, overrides),
+        Commands::ListAssets { config, name } => {
+            list_assets_command(config, profile, overrides, &name)
+        }
+        Commands::History { config, verify } => history_command(config, profile, overrides, verify),
+        Commands::Gc {
+            config,
+            older_than,
+            lock_file,
+        } => gc_command(
+            config,
+            profile,
+            overrides,
+            older_than.as_deref(),
+            lock_file.as_deref(),
+        ),
+        Commands::Import {
+            config,
+            from,
+            dry_run,
+        } => import_command(config, &from, dry_run),
+        Commands::ExplainVersion {
+            a,
+            b,
+            scheme_a,
+            scheme_b,
+        } => {
+            explain_version_command(&Version::new(a, scheme_a), &Version::new(b, scheme_b));
+            Ok(())
+        }
         Commands::SelfInstall {
             install_dir,
             config_path,
-        } => self_install_command(install_dir, config_path),
+            verify_config,
+            timer_interval,
+            randomized_delay,
+        } => self_install_command(
+            install_dir,
+            config_path,
+            verify_config,
+            timer_interval.as_deref(),
+            randomized_delay.as_deref(),
+        ),
+        Commands::SelfUninstall {
+            install_dir,
+            config_path,
+            purge,
+        } => self_uninstall_command(install_dir, config_path, purge),
     }
 }
 
-fn load_config(config: Option<PathBuf>) -> Result<(Config, PathBuf)> {
+fn load_config(
+    config: Option<PathBuf>,
+    profile: Option<&str>,
+    overrides: &[String],
+) -> Result<(Config, PathBuf)> {
     let config_path = config.unwrap_or_else(|| PathBuf::from("autopkg.yml"));
     info!("Using config file: {}", config_path.display());
 
-    let config_contents =
-        fs::read_to_string(&config_path).with_context(|| "Failed to read config file")?;
-    let config: Config =
-        serde_yaml::from_str(&config_contents).with_context(|| "Failed to parse config YAML")?;
+    let mut config = Config::load_with_overrides(&config_path, profile, overrides)?;
+    let mut config_path = config_path;
+    if let Some(repo) = &config.settings.config_repo {
+        config_path = config_repo::sync(repo)?;
+        info!(
+            "Using config file from {}: {}",
+            repo.url,
+            config_path.display()
+        );
+        config = Config::load_with_overrides(&config_path, profile, overrides)?;
+        if config.settings.config_repo.is_some() {
+            warn!("Config: settings.config_repo in the repository's config file is ignored");
+        }
+    }
+    if let Some(profile) = profile {
+        info!("Using config profile: {}", profile);
+    }
+    for warning in config.validate() {
+        warn!("Config: {}", warning);
+    }
     Ok((config, config_path))
 }
 
-fn run_command(config: Option<PathBuf>, dry_run: bool) -> Result<()> {
-    let (config, _) = load_config(config)?;
+/// Hand global settings that fetchers need over to the fetcher module.
+fn apply_settings(settings: &config::Settings) {
+    if let Some(max) = settings.max_concurrent_downloads {
+        fetcher::set_max_concurrent_downloads(max);
+    }
+    fetcher::set_default_github_token(settings.github_token.clone());
+    host::set_overrides(settings);
+    installer::set_privilege_command(settings.privilege_command.clone());
+}
+
+/// How `run` processes the selected applications.
+struct RunOptions {
+    dry_run: bool,
+    offline: bool,
+    diff_assets: bool,
+    /// Lock file against overlapping runs (`--lock-file`)
+    lock_file: Option<PathBuf>,
+    /// Stop at the first failed application
+    fail_fast: bool,
+    /// Ignore `check_interval` (`--force`)
+    force: bool,
+    /// Bypass the GitHub release cache (`--refresh`)
+    refresh: bool,
+    /// Applications processed in parallel
+    jobs: usize,
+    /// Don't print the summary line (`--quiet`)
+    quiet: bool,
+    /// File the JSON report is written to (`--report`)
+    report_path: Option<PathBuf>,
+    /// Only verifying the config (`self-install --verify-config`): the run
+    /// leaves no trace in the history, state or collector, and doesn't clean up
+    verify_only: bool,
+}
+
+/// Run state shared by the workers processing applications.
+struct RunState {
+    state: State,
+    state_changed: bool,
+    /// Assets installed in this run, recorded in the state file at the end
+    installed_assets: Vec<(String, String)>,
+    /// Installers that installed updates in this run, where they changed;
+    /// `None` for the application's own
+    installers: Vec<(String, Option<String>)>,
+    /// Update checks completed in this run, recorded in the state file at the end
+    checks: Vec<(String, LastCheck)>,
+    /// Deferred downloads to record (`Some`) or forget (`None`) at the end
+    downloads: Vec<(String, Option<Download>)>,
+}
+
+fn lock_run_state(shared: &Mutex<RunState>) -> MutexGuard<'_, RunState> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Why `app` doesn't need checking yet, if it was checked within its `check_interval`.
+fn recently_checked(app: &config::ApplicationConfig, state: &State) -> Result<Option<String>> {
+    let Some(interval) = app.check_interval()? else {
+        return Ok(None);
+    };
+    let Some(ago) = state.last_checked_ago(&app.name) else {
+        return Ok(None);
+    };
+    if ago >= interval.as_secs() {
+        return Ok(None);
+    }
+    let latest = state.checks[&app.name]
+        .latest_version
+        .as_deref()
+        .map(|v| format!(", latest version was {}", v))
+        .unwrap_or_default();
+    Ok(Some(format!(
+        "last checked {} ago (check_interval {}){}",
+        humantime::format_duration(std::time::Duration::from_secs(ago)),
+        humantime::format_duration(interval),
+        latest
+    )))
+}
+
+/// The check to record for an application whose update check reached the
+/// fetcher and left nothing to do, with the newest version it found. Deferred
+/// and held updates aren't recorded, so the next run tries them again.
+fn completed_check(report: &AppReport) -> Option<LastCheck> {
+    let latest_version = match report.action {
+        Action::UpToDate => report.current_version.clone(),
+        Action::Installed => report.new_version.clone(),
+        Action::Skipped
+        | Action::WouldUpdate
+        | Action::Downloaded
+        | Action::Deferred
+        | Action::NeedsApproval
+        | Action::Failed => return None,
+    };
+    Some(LastCheck {
+        checked_at: state::now(),
+        latest_version,
+    })
+}
+
+/// Applications picked by the `--only`, `--schedule`, `--unscheduled`, `--group`
+/// and `--tag` filters.
+struct Selection {
+    only: Vec<String>,
+    schedule: Option<String>,
+    unscheduled: bool,
+    group: Option<String>,
+    tag: Option<String>,
+}
+
+impl Selection {
+    fn selects(&self, app: &config::ApplicationConfig) -> bool {
+        (self.only.is_empty() || self.only.contains(&app.name))
+            && (self.schedule.is_none() || app.schedule == self.schedule)
+            && (!self.unscheduled || app.schedule.is_none())
+            && app.is_selected(self.group.as_deref(), self.tag.as_deref())
+    }
+
+    /// The filters in use, for logging; `None` if everything is selected.
+    fn describe(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if !self.only.is_empty() {
+            parts.push(format!("name '{}'", self.only.join("', '")));
+        }
+        if let Some(schedule) = &self.schedule {
+            parts.push(format!("schedule '{}'", schedule));
+        }
+        if self.unscheduled {
+            parts.push("no schedule".to_string());
+        }
+        if let Some(group) = &self.group {
+            parts.push(format!("group '{}'", group));
+        }
+        if let Some(tag) = &self.tag {
+            parts.push(format!("tag '{}'", tag));
+        }
+        (!parts.is_empty()).then(|| parts.join(" and "))
+    }
+
+    /// Fail if `--only` names an application the config doesn't have.
+    fn check_names(&self, applications: &[config::ApplicationConfig]) -> Result<()> {
+        let unknown: Vec<&str> = self
+            .only
+            .iter()
+            .filter(|name| !applications.iter().any(|app| &app.name == *name))
+            .map(String::as_str)
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        let valid: Vec<&str> = applications.iter().map(|app| app.name.as_str()).collect();
+        Err(anyhow!(
+            "No application named '{}' (valid names: {})",
+            unknown.join("', '"),
+            valid.join(", ")
+        ))
+    }
+}
+
+fn run_command(
+    config: Option<PathBuf>,
+    profile: Option<&str>,
+    overrides: &[String],
+    options: RunOptions,
+    selection: Selection,
+    report_format: Option<ReportFormat>,
+) -> Result<RunReport> {
+    let RunOptions {
+        dry_run,
+        offline,
+        diff_assets,
+        lock_file,
+        fail_fast,
+        force,
+        refresh,
+        jobs,
+        quiet,
+        report_path,
+        verify_only,
+    } = options;
+    let lock_path = lock::run_lock_path(lock_file.as_deref());
+    let Some(_run_lock) = lock::try_lock_run(&lock_path)? else {
+        if verify_only {
+            return Err(anyhow!(
+                "Another autopkg run holds {}, so the config wasn't checked; run `autopkg run --dry-run` once it is done",
+                lock_path.display()
+            ));
+        }
+        info!(
+            "Another autopkg run holds {}; skipping this run",
+            lock_path.display()
+        );
+        return Ok(RunReport::new(dry_run));
+    };
+    let (config, config_path) = load_config(config, profile, overrides)?;
     info!(
         "Loaded {} application(s) from config",
         config.applications.len()
     );
+    selection.check_names(&config.applications)?;
 
-    for app in &config.applications {
+    apply_settings(&config.settings);
+    // Loaded before anything is installed, so a bad key can't stop the run
+    // halfway, before the state and history are saved
+    let history_key = history::load_key(
+        config.settings.history_key_file.as_deref(),
+        config.settings.history_key_env.as_deref(),
+    )?;
+    let state_path = State::path(config.settings.state_file.as_deref(), &config_path);
+    let state = State::load(&state_path);
+    fetcher::github::set_release_cache(
+        &state_path.with_file_name(fetcher::github::RELEASE_CACHE_FILE),
+        refresh,
+    );
+    fetcher::url::set_version_cache(&state_path, refresh);
+    let http = HttpClient::new(&config.http)?;
+
+    // Highest priority first; the sort is stable so ties keep config order
+    let mut applications: Vec<&config::ApplicationConfig> = config
+        .applications
+        .iter()
+        .filter(|app| selection.selects(app))
+        .collect();
+    if let Some(description) = selection.describe() {
+        info!(
+            "Processing {} application(s) with {}",
+            applications.len(),
+            description
+        );
+        if applications.is_empty() {
+            warn!("No application matches {}", description);
+        }
+    }
+    applications.sort_by_key(|app| std::cmp::Reverse(app.priority.unwrap_or(0)));
+
+    let quarantine_dir = quarantine::dir(config.settings.quarantine_dir.as_deref(), &config_path);
+    let process_options = ProcessOptions {
+        quarantine_dir: quarantine_dir.as_deref(),
+        dry_run,
+        offline,
+        // Downloads are only kept around when `gc` is asked to keep some versions
+        keep_downloads: config.settings.gc.keep_versions.is_some(),
+        http: &http,
+    };
+    let run_started = std::time::SystemTime::now();
+    let mut report = RunReport::new(dry_run);
+    let shared = Mutex::new(RunState {
+        state,
+        state_changed: false,
+        installed_assets: Vec::new(),
+        installers: Vec::new(),
+        checks: Vec::new(),
+        downloads: Vec::new(),
+    });
+    let run_app = |app: &config::ApplicationConfig| {
+        let _log_context = logging::AppContext::enter(&app.name);
         info!("Processing application: {}", app.name);
 
-        if let Err(e) = process_application(app, dry_run) {
+        let mut app_report = AppReport::new(&app.name);
+        if !offline && !force {
+            match recently_checked(app, &lock_run_state(&shared).state) {
+                Ok(None) => {}
+                Ok(Some(message)) => {
+                    info!("{}: {}; skipping check", app.name, message);
+                    return app_report;
+                }
+                Err(e) => {
+                    error!("Application '{}' failed: {:#}", app.name, e);
+                    app_report.action = Action::Failed;
+                    app_report.error = Some(format!("{:#}", e));
+                    return app_report;
+                }
+            }
+        }
+        if !offline && fetcher::uses_github_api(&app.fetcher) {
+            let backoff = lock_run_state(&shared).state.github_backoff_remaining();
+            if let Some(secs) = backoff {
+                warn!(
+                    "{}: GitHub API is rate-limited for another {}s; skipping check",
+                    app.name, secs
+                );
+                app_report
+                    .warnings
+                    .push(format!("GitHub API rate-limited for another {}s", secs));
+                return app_report;
+            }
+        }
+
+        let (recorded_installer, kept_download) = {
+            let shared = lock_run_state(&shared);
+            (
+                shared.state.installers.get(&app.name).cloned(),
+                shared.state.downloads.get(&app.name).cloned(),
+            )
+        };
+        if let Err(e) = process_application(
+            app,
+            recorded_installer.as_deref(),
+            kept_download.as_ref(),
+            &process_options,
+            &mut app_report,
+        ) {
             error!(
-                "Application '{}' failed: {:?}. Continuing with others.",
-                app.name, e
+                "Application '{}' failed: {:?}.{}",
+                app.name,
+                e,
+                if fail_fast {
+                    ""
+                } else {
+                    " Continuing with others."
+                }
             );
+            if let Some(limit) = e.chain().find_map(|c| c.downcast_ref::<RateLimited>()) {
+                warn!(
+                    "GitHub API rate limit hit; skipping GitHub checks until unix time {}",
+                    limit.reset
+                );
+                let mut shared = lock_run_state(&shared);
+                shared.state.github_rate_limit_reset = Some(limit.reset);
+                shared.state_changed = true;
+            }
+            app_report.action = Action::Failed;
+            app_report.error = Some(format!("{:#}", e));
+        }
+        if !dry_run {
+            notify::notify(&config.notifications, &http, &app_report);
+        }
+
+        if !dry_run && !offline {
+            if let Some(check) = completed_check(&app_report) {
+                lock_run_state(&shared)
+                    .checks
+                    .push((app.name.clone(), check));
+            }
+        }
+
+        if !dry_run {
+            let download = match (&app_report.action, &app_report.downloaded_path) {
+                (Action::Deferred, Some(path)) => Some(Some(Download {
+                    path: path.clone(),
+                    version: app_report.new_version.clone(),
+                    asset: app_report.asset.clone().unwrap_or_default(),
+                    installer: app_report.installer.clone(),
+                })),
+                (Action::Installed | Action::UpToDate, _) if kept_download.is_some() => Some(None),
+                _ => None,
+            };
+            if let Some(download) = download {
+                lock_run_state(&shared)
+                    .downloads
+                    .push((app.name.clone(), download));
+            }
         }
+
+        if app_report.action == Action::Installed && app_report.installer != recorded_installer {
+            lock_run_state(&shared)
+                .installers
+                .push((app.name.clone(), app_report.installer.clone()));
+        }
+
+        if let Some(asset) = &app_report.asset {
+            let mut shared = lock_run_state(&shared);
+            let previous = shared.state.assets.get(&app.name).cloned();
+            if diff_assets {
+                match &previous {
+                    Some(previous) if previous != asset => {
+                        warn!("{}: asset changed: {} -> {}", app.name, previous, asset);
+                        app_report
+                            .warnings
+                            .push(format!("asset changed: {} -> {}", previous, asset));
+                    }
+                    Some(_) => info!("{}: asset unchanged: {}", app.name, asset),
+                    None => info!(
+                        "{}: no previously installed asset recorded; selected {}",
+                        app.name, asset
+                    ),
+                }
+            }
+            if app_report.action == Action::Installed && previous.as_ref() != Some(asset) {
+                shared
+                    .installed_assets
+                    .push((app.name.clone(), asset.clone()));
+            }
+        }
+        app_report
+    };
+
+    let jobs = jobs.max(1).min(applications.len().max(1));
+    let total = applications.len();
+    let stop = AtomicBool::new(false);
+    if jobs == 1 {
+        for app in applications {
+            let app_report = run_app(app);
+            let failed = app_report.action == Action::Failed;
+            report.push(app_report);
+            if fail_fast && failed {
+                stop.store(true, Ordering::SeqCst);
+                break;
+            }
+        }
+    } else {
+        info!("Processing applications with {} workers", jobs);
+        // Workers take applications in priority order; reports keep that order.
+        let next = AtomicUsize::new(0);
+        let results: Vec<Mutex<Option<AppReport>>> =
+            applications.iter().map(|_| Mutex::new(None)).collect();
+        std::thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| loop {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(app) = applications.get(i) else {
+                        break;
+                    };
+                    let app_report = run_app(app);
+                    if fail_fast && app_report.action == Action::Failed {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    *results[i].lock().unwrap_or_else(|e| e.into_inner()) = Some(app_report);
+                });
+            }
+        });
+        for result in results {
+            if let Some(app_report) = result.into_inner().unwrap_or_else(|e| e.into_inner()) {
+                report.push(app_report);
+            }
+        }
+    }
+    if stop.load(Ordering::SeqCst) {
+        warn!(
+            "Stopped after a failure (--fail-fast); {} of {} application(s) not processed",
+            total - report.applications.len(),
+            total
+        );
+    }
+    let RunState {
+        state,
+        state_changed,
+        installed_assets,
+        installers,
+        checks,
+        downloads,
+    } = shared.into_inner().unwrap_or_else(|e| e.into_inner());
+
+    if !verify_only {
+        let history_path = history::path(config.settings.history_file.as_deref(), &config_path);
+        if let Err(e) = history::append(
+            &history_path,
+            history_key.as_deref(),
+            state::now(),
+            dry_run,
+            &report.applications,
+        ) {
+            error!("Failed to record history: {:#}", e);
+        }
+    }
+
+    let state_changed = state_changed
+        || !installed_assets.is_empty()
+        || !installers.is_empty()
+        || !checks.is_empty()
+        || !downloads.is_empty();
+    if state_changed && !verify_only {
+        let reset = state.github_rate_limit_reset;
+        let saved = State::update(&state_path, |saved| {
+            saved.github_rate_limit_reset = saved.github_rate_limit_reset.max(reset);
+            saved.assets.extend(installed_assets);
+            for (app, installer) in installers {
+                match installer {
+                    Some(installer) => saved.installers.insert(app, installer),
+                    None => saved.installers.remove(&app),
+                };
+            }
+            saved.checks.extend(checks);
+            for (app, download) in downloads {
+                match download {
+                    Some(download) => saved.downloads.insert(app, download),
+                    None => saved.downloads.remove(&app),
+                };
+            }
+        });
+        if let Err(e) = saved {
+            warn!("Failed to save state: {:#}", e);
+        }
+    }
+
+    if config.settings.gc.after_run && !verify_only {
+        match gc::sweep(&config, Some(run_started), None) {
+            Ok(summary) => report.disk.reclaimed += summary.bytes,
+            Err(e) => error!("Cleanup failed: {:#}", e),
+        }
+    }
+
+    info!("Disk usage: {}", report.disk);
+
+    if let Some(mode) = config.settings.needrestart {
+        let installed_debs = config.applications.iter().any(|app| {
+            installer::resolve_type(&app.installer.r#type).is_ok_and(|t| t == "deb")
+                && report
+                    .applications
+                    .iter()
+                    .any(|r| r.name == app.name && r.action == Action::Installed)
+        });
+        if installed_debs {
+            if let Err(e) = installer::deb::run_needrestart(mode) {
+                error!("needrestart failed: {:#}", e);
+            }
+        }
+    }
+
+    let needs_reboot: Vec<&str> = report
+        .applications
+        .iter()
+        .filter(|r| r.action == Action::Installed)
+        .filter(|r| {
+            config
+                .applications
+                .iter()
+                .any(|app| app.name == r.name && app.reboot_required == Some(true))
+        })
+        .map(|r| r.name.as_str())
+        .collect();
+    let installed_any = report.count(Action::Installed) > 0;
+    if !needs_reboot.is_empty() || (installed_any && Path::new(REBOOT_REQUIRED_FLAG).exists()) {
+        report.reboot_required = true;
+        if needs_reboot.is_empty() {
+            warn!("Reboot required ({} exists)", REBOOT_REQUIRED_FLAG);
+        } else {
+            warn!("Reboot required after updating {}", needs_reboot.join(", "));
+        }
+        if let Some(at) = &config.settings.reboot_at {
+            if let Err(e) = schedule_reboot(at) {
+                error!("Failed to schedule reboot: {:#}", e);
+            }
+        }
+    }
+
+    // Offline runs stay off the network, and a verification isn't a real run
+    if let Some(collector) = config
+        .settings
+        .collector
+        .as_ref()
+        .filter(|_| !offline && !verify_only)
+    {
+        if let Err(e) = collector::post(collector, &http, &report) {
+            error!("Failed to send run summary to {}: {:#}", collector.url, e);
+        }
+    }
+
+    if let Some(path) = &report_path {
+        let json = report.render(ReportFormat::Json);
+        match lock::write_atomically(path, json.as_bytes()) {
+            Ok(()) => info!("Wrote run report to {}", path.display()),
+            Err(e) => error!("Failed to write run report: {:#}", e),
+        }
+    }
+    // The summary is a result, not a log line, so it goes to stdout regardless
+    // of the log level; a requested report takes its place there
+    match report_format {
+        Some(format) => print!("{}", report.render(format)),
+        None if !quiet => println!("{}", report.summary()),
+        None => {}
+    }
+
+    Ok(report)
+}
+
+/// Flag file left by Debian/Ubuntu packages that need a reboot.
+const REBOOT_REQUIRED_FLAG: &str = "/var/run/reboot-required";
+
+/// Schedule a reboot with `shutdown -r <at>`.
+fn schedule_reboot(at: &str) -> Result<()> {
+    info!("Scheduling reboot: shutdown -r {}", at);
+    let status = installer::run_as_root(&["shutdown", "-r", at], || "scheduling reboot")?;
+    if !status.success() {
+        return Err(anyhow!("shutdown -r {} failed with status {}", at, status));
+    }
+    Ok(())
+}
+
+fn show_config_command(
+    config: Option<PathBuf>,
+    profile: Option<&str>,
+    overrides: &[String],
+    selection: Selection,
+) -> Result<()> {
+    let (mut config, config_path) = load_config(config, profile, overrides)?;
+    config.applications.retain(|app| selection.selects(app));
+    info!(
+        "Configuration from {} successfully parsed:",
+        config_path.display()
+    );
+
+    // Pretty-print the config to stdout (still logging to stderr)
+    println!(
+        "{}",
+        serde_yaml::to_string(&config).unwrap_or_else(|_| "<failed to serialize config>".into())
+    );
+    Ok(())
+}
+
+fn validate_command(
+    config: Option<PathBuf>,
+    profile: Option<&str>,
+    overrides: &[String],
+) -> Result<()> {
+    let (config, config_path) = load_config(config, profile, overrides)?;
+    let http = HttpClient::new(&config.http)?;
+
+    let mut failed = 0;
+    for app in &config.applications {
+        // Only the factories: nothing is fetched or installed
+        let errors: Vec<anyhow::Error> = [
+            create_fetcher(&app.fetcher, app, &http).err(),
+            create_installer(&app.installer, app).err(),
+            app.check_interval().err(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        for e in &errors {
+            println!("{}: {:#}", app.name, e);
+        }
+        if !errors.is_empty() {
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} application(s) in {} failed validation",
+            failed,
+            config.applications.len(),
+            config_path.display()
+        ));
+    }
+    println!("{} applications OK", config.applications.len());
+    Ok(())
+}
+
+fn gc_command(
+    config: Option<PathBuf>,
+    profile: Option<&str>,
+    overrides: &[String],
+    older_than: Option<&str>,
+    lock_file: Option<&Path>,
+) -> Result<()> {
+    let (config, _) = load_config(config, profile, overrides)?;
+    let older_than = older_than
+        .map(|age| {
+            humantime::parse_duration(age)
+                .map_err(|e| anyhow!("Invalid --older-than '{}': {}", age, e))
+        })
+        .transpose()?;
+
+    // Files written from now on, or since a run in progress started, are kept
+    let mut protect_since = std::time::SystemTime::now();
+    if let Some(started) = lock::running_since(&lock::run_lock_path(lock_file))? {
+        info!("A run is in progress; keeping the files it wrote");
+        protect_since = protect_since.min(started);
+    }
+    let summary = gc::sweep(&config, Some(protect_since), older_than)?;
+    println!(
+        "Removed {} item(s), freed {}",
+        summary.removed,
+        report::format_bytes(summary.bytes)
+    );
+    Ok(())
+}
+
+fn import_command(config: Option<PathBuf>, from: &Path, dry_run: bool) -> Result<()> {
+    let config_path = config.unwrap_or_else(|| PathBuf::from("autopkg.yml"));
+    let entries = import::read_manifest(from)?;
+    let summary = import::import(&config_path, &entries, dry_run)?;
+
+    for (name, reason) in &summary.invalid {
+        println!("invalid    {}: {}", name, reason);
+    }
+    for name in &summary.duplicates {
+        println!("duplicate  {}", name);
+    }
+    for name in &summary.imported {
+        println!("imported   {}", name);
+    }
+    println!(
+        "{}{} imported, {} duplicate(s) skipped, {} invalid",
+        if dry_run { "(dry run) " } else { "" },
+        summary.imported.len(),
+        summary.duplicates.len(),
+        summary.invalid.len()
+    );
+    if summary.invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} manifest entries are invalid",
+            summary.invalid.len()
+        ))
+    }
+}
+
+fn explain_version_command(a: &Version, b: &Version) {
+    let describe = |label: &str, version: &Version| {
+        let upstream = version.upstream_part();
+        println!(
+            "{}: {} ({} scheme)",
+            label,
+            version,
+            version.scheme().label()
+        );
+        if version.epoch() > 0 {
+            println!("  epoch:      {}", version.epoch());
+        }
+        println!("  upstream:   {}", upstream);
+        let semver = version::parse_semver(&upstream);
+        match &semver {
+            Some(semver) => println!("  semver:     {}", semver),
+            None => {
+                let (numbers, ignored) = version::components(&upstream);
+                println!("  components: {:?} (not semver)", numbers);
+                if !ignored.is_empty() {
+                    println!("  ignored:    {:?} (not numeric)", ignored);
+                }
+            }
+        }
+        (upstream, semver.is_some())
+    };
+    let (a_upstream, a_semver) = describe("a", a);
+    let (b_upstream, b_semver) = describe("b", b);
+
+    if a.scheme() == version::Scheme::Debian && b.scheme() == version::Scheme::Debian {
+        println!(
+            "comparison: full Debian versions as dpkg orders them: epoch, upstream version, then revision"
+        );
+    } else if a.scheme() == b.scheme() && a.epoch() != b.epoch() {
+        println!("epochs differ ({} vs {}) and decide", a.epoch(), b.epoch());
+    } else if a_semver && b_semver {
+        println!(
+            "comparison: semver precedence of the upstream versions; pre-releases sort below their release, build metadata is ignored"
+        );
+    } else {
+        println!(
+            "comparison: numeric dot-separated components of the upstream versions, left to right; missing components count as 0"
+        );
+        if let Some((i, a_value, b_value)) = version::first_difference(&a_upstream, &b_upstream) {
+            println!(
+                "first difference: component {} ({} vs {})",
+                i + 1,
+                a_value,
+                b_value
+            );
+        }
+    }
+    if let Some(level) = a.update_level(b).filter(|_| a.is_older_than(b)) {
+        println!("bump: {} (for auto_update_level)", level.label());
+    }
+    let verdict = if a.is_older_than(b) {
+        "b is newer; with a installed, b would be installed"
+    } else if b.is_older_than(a) {
+        "a is newer; with a installed, b would not be installed"
+    } else {
+        "equal; with a installed, b would not be installed"
+    };
+    println!("verdict: {}", verdict);
+}
+
+fn history_command(
+    config: Option<PathBuf>,
+    profile: Option<&str>,
+    overrides: &[String],
+    verify: bool,
+) -> Result<()> {
+    let (config, config_path) = load_config(config, profile, overrides)?;
+    let path = history::path(config.settings.history_file.as_deref(), &config_path);
+    let records = history::read(&path)?;
+
+    if verify {
+        let key = history::load_key(
+            config.settings.history_key_file.as_deref(),
+            config.settings.history_key_env.as_deref(),
+        )?
+        .ok_or_else(|| anyhow!("--verify needs settings.history_key_file or history_key_env"))?;
+        let head = history::read_head(&path)?;
+        let problems = history::verify(&records, head.as_ref(), &key)?;
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        if !problems.is_empty() {
+            return Err(anyhow!(
+                "History {} failed verification ({} problem(s))",
+                path.display(),
+                problems.len()
+            ));
+        }
+        println!("{} record(s) verified", records.len());
+        return Ok(());
+    }
+
+    for record in &records {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(record.timestamp);
+        println!(
+            "{:>5}  {}  {}  {}{}  {} -> {}{}",
+            record.seq,
+            humantime::format_rfc3339_seconds(time),
+            record.app,
+            record.action.label(),
+            if record.dry_run { " (dry-run)" } else { "" },
+            record.from_version.as_deref().unwrap_or("-"),
+            record.to_version.as_deref().unwrap_or("-"),
+            record
+                .error
+                .as_ref()
+                .map(|e| format!("  {}", e))
+                .unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn status_command(
+    config: Option<PathBuf>,
+    profile: Option<&str>,
+    overrides: &[String],
+    json: bool,
+    refresh: bool,
+) -> Result<()> {
+    let (config, config_path) = load_config(config, profile, overrides)?;
+    apply_settings(&config.settings);
+    let state_path = State::path(config.settings.state_file.as_deref(), &config_path);
+    fetcher::github::set_release_cache(
+        &state_path.with_file_name(fetcher::github::RELEASE_CACHE_FILE),
+        refresh,
+    );
+    fetcher::url::set_version_cache(&state_path, refresh);
+    let http = HttpClient::new(&config.http)?;
+    let state = State::load(&state_path);
+
+    let entries: Vec<status::StatusEntry> = config
+        .applications
+        .iter()
+        .map(|app| {
+            let _log_context = logging::AppContext::enter(&app.name);
+            let recorded_installer = state.installers.get(&app.name).map(String::as_str);
+            status::check(app, recorded_installer, &http)
+        })
+        .collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        print!("{}", status::render_table(&entries));
+    }
+    Ok(())
+}
+
+fn list_assets_command(
+    config: Option<PathBuf>,
+    profile: Option<&str>,
+    overrides: &[String],
+    name: &str,
+) -> Result<()> {
+    let (config, _) = load_config(config, profile, overrides)?;
+    apply_settings(&config.settings);
+    let app = config
+        .applications
+        .iter()
+        .find(|app| app.name == name)
+        .ok_or_else(|| anyhow!("No application named '{}' in config", name))?;
+
+    let fetcher = create_fetcher(&app.fetcher, app, &HttpClient::new(&config.http)?)?;
+    let assets = fetcher.list_assets()?;
+    if assets.is_empty() {
+        println!("Latest release has no assets");
+        return Ok(());
+    }
+
+    let width = assets.iter().map(|a| a.name.len()).max().unwrap_or(0);
+    println!(
+        "{:<width$}  {:>10}  {:<32}  MATCHES",
+        "NAME",
+        "SIZE",
+        "CONTENT-TYPE",
+        width = width
+    );
+    for asset in &assets {
+        println!(
+            "{:<width$}  {:>10}  {:<32}  {}",
+            asset.name,
+            report::format_bytes(asset.size),
+            asset.content_type,
+            if asset.matches { "yes" } else { "no" },
+            width = width
+        );
     }
-
     Ok(())
 }
 
-fn show_config_command(config: Option<PathBuf>) -> Result<()> {
-    let (config, config_path) = load_config(config)?;
-    info!(
-        "Configuration from {} successfully parsed:",
-        config_path.display()
-    );
+/// How `process_application` handles each application of a run.
+#[derive(Clone, Copy)]
+struct ProcessOptions<'a> {
+    quarantine_dir: Option<&'a Path>,
+    dry_run: bool,
+    offline: bool,
+    /// Keep installed downloads for `gc` to sweep (`settings.gc.keep_versions`)
+    keep_downloads: bool,
+    http: &'a HttpClient,
+}
 
-    // Pretty-print the config to stdout (still logging to stderr)
-    println!(
-        "{}",
-        serde_yaml::to_string(&config).unwrap_or_else(|_| "<failed to serialize config>".into())
-    );
+/// Check `app` for an update and install it. `recorded_installer` is the
+/// installer type the state file says installed it, if a `prefer` entry chose
+/// another than the application's own; `kept_download` the download of a
+/// deferred install, which `--offline` installs.
+fn process_application(
+    app: &config::ApplicationConfig,
+    recorded_installer: Option<&str>,
+    kept_download: Option<&Download>,
+    options: &ProcessOptions,
+    report: &mut AppReport,
+) -> Result<()> {
+    let installer = installer::create_reporting_installer(app, recorded_installer)?;
+    let fetcher = create_fetcher(&app.fetcher, app, options.http)?;
+    update_application(
+        app,
+        installer.as_ref(),
+        fetcher.as_ref(),
+        recorded_installer,
+        kept_download,
+        options,
+        report,
+    )?;
+    // Only an application without updates can be stuck on an archived
+    // upstream, so other checks don't spend a request on it
+    if !options.offline && report.action == Action::UpToDate {
+        warn_if_archived(app, fetcher.as_ref(), report);
+    }
     Ok(())
 }
 
-fn process_application(app: &config::ApplicationConfig, dry_run: bool) -> Result<()> {
-    let installer = create_installer(&app.installer, app)?;
-    let fetcher = create_fetcher(&app.fetcher, app)?;
+/// Warn when `app`'s upstream repository is archived, so no further releases
+/// are expected. Failures to check are only logged.
+fn warn_if_archived(
+    app: &config::ApplicationConfig,
+    fetcher: &dyn fetcher::Fetcher,
+    report: &mut AppReport,
+) {
+    match fetcher.source_archived() {
+        Ok(true) => {
+            warn!(
+                "{}: upstream repo archived; no further releases expected, consider removing",
+                app.name
+            );
+            report
+                .warnings
+                .push("repo archived; consider removing".to_string());
+        }
+        Ok(false) => {}
+        Err(e) => warn!("{}: could not check if repo is archived: {:#}", app.name, e),
+    }
+}
+
+/// The update check and install of [`process_application`].
+fn update_application(
+    app: &config::ApplicationConfig,
+    installer: &dyn installer::Installer,
+    fetcher: &dyn fetcher::Fetcher,
+    recorded_installer: Option<&str>,
+    kept_download: Option<&Download>,
+    options: &ProcessOptions,
+    report: &mut AppReport,
+) -> Result<()> {
+    let ProcessOptions {
+        quarantine_dir,
+        dry_run,
+        offline,
+        keep_downloads,
+        http: _,
+    } = *options;
 
     match installer.should_check_for_update()? {
         UpdateCheck::No => {
             info!("{}: update check skipped (pinned or disabled)", app.name);
+            report.action = Action::Skipped;
         }
         UpdateCheck::Yes(current_version) => {
             info!(
                 "{}: current version reported by installer: {}",
                 app.name, current_version
             );
+            report.current_version = Some(current_version.to_string());
+
+            let fetched = if offline {
+                match fetcher.fetch_cached(&current_version) {
+                    Some(result) => result,
+                    None => match newer_kept_download(app, kept_download, &current_version)? {
+                        Some(download) => {
+                            info!(
+                                "{}: offline; using the download kept from an earlier run",
+                                app.name
+                            );
+                            Ok(Some(download))
+                        }
+                        None => {
+                            info!("{}: offline; skipped", app.name);
+                            report.action = Action::Skipped;
+                            return Ok(());
+                        }
+                    },
+                }
+            } else if installer.handles_fetch() {
+                return install_latest_version(
+                    app,
+                    installer,
+                    fetcher,
+                    &current_version,
+                    dry_run,
+                    report,
+                );
+            } else if should_stream(app, installer, fetcher) {
+                return stream_latest_version(
+                    app,
+                    installer,
+                    fetcher,
+                    &current_version,
+                    dry_run,
+                    report,
+                );
+            } else {
+                if (dry_run || app.auto_update_level.is_some())
+                    && checked_before_download(app, fetcher, &current_version, dry_run, report)?
+                {
+                    return Ok(());
+                }
+                fetcher.fetch_if_newer(&current_version)
+            };
 
-            match fetcher.fetch_if_newer(&current_version)? {
+            match fetched.map_err(|e| quarantine::reject_fetched(&app.name, e, quarantine_dir))? {
                 None => {
                     info!("{}: already up-to-date", app.name);
+                    report.action = Action::UpToDate;
                 }
-                Some(downloaded_path) => {
-                    if dry_run {
+                Some(download) => {
+                    // A `prefer` entry may pick another installer for this asset
+                    // than the one that installed the current version; the one
+                    // installing it also checks the installed version afterwards
+                    let asset_type = download
+                        .installer
+                        .clone()
+                        .unwrap_or_else(|| app.installer.r#type.clone());
+                    let chosen =
+                        if asset_type == recorded_installer.unwrap_or(&app.installer.r#type) {
+                            None
+                        } else {
+                            info!(
+                                "{}: using the {} installer for this asset",
+                                app.name, asset_type
+                            );
+                            Some(installer::create_reporting_installer(
+                                app,
+                                Some(&asset_type),
+                            )?)
+                        };
+                    let asset_installer = chosen.as_deref().unwrap_or(installer);
+                    report.installer = (asset_type != app.installer.r#type).then_some(asset_type);
+                    let downloaded_path = download.path;
+                    report.new_version = download.version.clone();
+                    report.asset = Some(download.asset.clone());
+                    report.downloaded_path = Some(downloaded_path.clone());
+                    report.bytes_downloaded = file_size(&downloaded_path);
+                    let checked =
+                        asset_installer
+                            .verify_download(&downloaded_path)
+                            .and_then(|()| match &app.pre_install_scan {
+                                Some(scan) => {
+                                    hooks::run_scan(scan, &downloaded_path, app.run_as.as_deref())
+                                }
+                                None => Ok(()),
+                            });
+                    if let Err(e) = checked {
+                        return Err(quarantine::reject(
+                            &app.name,
+                            &downloaded_path,
+                            e,
+                            quarantine_dir,
+                        ));
+                    }
+                    // Without a version (a `url` fetcher without `version_url`)
+                    // there is no bump to judge, nor an expected version to verify
+                    let held = download.version.as_deref().is_some_and(|version| {
+                        hold_for_approval(app, &current_version, version, report)
+                    });
+                    if held {
+                        remove_download(&downloaded_path);
+                        return Ok(());
+                    }
+                    let deferral = if dry_run {
+                        None
+                    } else {
+                        install_deferral(app)?
+                    };
+                    if let Some(reason) = deferral {
+                        warn!("{}: install deferred to a later run: {}", app.name, reason);
+                        report
+                            .warnings
+                            .push(format!("install deferred: {}", reason));
+                        report.action = Action::Deferred;
+                    } else if dry_run {
                         warn!(
                             "{}: update available (downloaded to {}), dry-run enabled; not installing",
                             app.name,
                             downloaded_path.display()
                         );
+                        report.action = Action::Downloaded;
                     } else {
                         info!(
                             "{}: installing update from {}",
                             app.name,
                             downloaded_path.display()
                         );
-                        installer.install(&downloaded_path)?;
+                        let _install = installer::exclusive();
+                        match asset_installer.check_install(&downloaded_path) {
+                            Err(e) if e.is::<installer::AlreadyInstalled>() => {
+                                info!("{}: {}", app.name, e);
+                                report.action = Action::UpToDate;
+                                remove_download(&downloaded_path);
+                                return Ok(());
+                            }
+                            result => result?,
+                        }
+                        run_pre_install_hooks(app)?;
+                        asset_installer.install(&downloaded_path)?;
                         info!("{}: installation completed", app.name);
+                        check_installed_version(
+                            app,
+                            asset_installer,
+                            &current_version,
+                            report,
+                            || asset_installer.install(&downloaded_path),
+                        )?;
+                        if let Some(version) = &download.version {
+                            verify_installed_version(
+                                app,
+                                asset_installer,
+                                version,
+                                &current_version,
+                            )?;
+                        }
+                        run_post_install_hooks(app, report);
+                        report.action = Action::Installed;
+                        report.bytes_installed = report.bytes_downloaded;
+                        if !keep_downloads {
+                            remove_download(&downloaded_path);
+                        }
                     }
                 }
             }
@@ -177,9 +1610,486 @@ fn process_application(app: &config::ApplicationConfig, dry_run: bool) -> Result
 
     Ok(())
 }
-fn self_install_command(install_dir: PathBuf, config_path: PathBuf) -> Result<()> {
+
+/// Delete an installed download from the download directory. Files elsewhere
+/// (such as packages built in a git checkout) are left alone.
+fn remove_download(path: &Path) {
+    let Ok(download_dir) = fetcher::download_dir() else {
+        return;
+    };
+    if path.parent() != Some(download_dir.as_path()) {
+        return;
+    }
+    match fs::remove_file(path) {
+        Ok(()) => info!("Removed installed download {}", path.display()),
+        Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+    }
+}
+
+/// Run the application's `pre_install` hooks; a failing hook aborts its install.
+fn run_pre_install_hooks(app: &config::ApplicationConfig) -> Result<()> {
+    hooks::run_install_hooks("pre_install", &app.pre_install, app.run_as.as_deref())
+}
+
+/// Run the application's `post_install` hooks. The update is already installed
+/// by then, so a failing hook is logged and reported rather than failing the
+/// application.
+fn run_post_install_hooks(app: &config::ApplicationConfig, report: &mut AppReport) {
+    if let Err(e) =
+        hooks::run_install_hooks("post_install", &app.post_install, app.run_as.as_deref())
+    {
+        error!("{}: {:#}", app.name, e);
+        report.warnings.push(format!("{:#}", e));
+    }
+}
+
+/// With `auto_update_level` set, hold back an update that is a bigger bump than
+/// allowed: it is reported as needing approval instead of being installed.
+/// A first install (nothing installed yet) is never held back.
+fn hold_for_approval(
+    app: &config::ApplicationConfig,
+    current_version: &Version,
+    latest_version: &str,
+    report: &mut AppReport,
+) -> bool {
+    let Some(level) = version::approval_needed(app, current_version, latest_version) else {
+        return false;
+    };
+    warn!(
+        "{}: update available ({} -> {}) but requires approval ({} bump, auto_update_level is {})",
+        app.name,
+        current_version,
+        latest_version,
+        level.label(),
+        app.auto_update_level.map_or("-", |allowed| allowed.label())
+    );
+    report.warnings.push(format!(
+        "update available but requires approval ({} bump)",
+        level.label()
+    ));
+    report.new_version = Some(latest_version.to_string());
+    report.action = Action::NeedsApproval;
+    true
+}
+
+/// Compare the latest version a fetcher reported without downloading with the
+/// installed one. Returns it if it should be installed; otherwise the report
+/// says why not (up to date, or held for approval).
+fn resolve_update(
+    app: &config::ApplicationConfig,
+    resolved: Option<Resolved>,
+    current_version: &Version,
+    report: &mut AppReport,
+) -> Result<Option<Resolved>> {
+    let Some(resolved) = resolved else {
+        info!("{}: no version available from fetcher", app.name);
+        report.action = Action::UpToDate;
+        return Ok(None);
+    };
+    if !version::should_update(app, current_version, &resolved.version)? {
+        info!("{}: already up-to-date", app.name);
+        report.action = Action::UpToDate;
+        return Ok(None);
+    }
+    report.new_version = Some(resolved.version.clone());
+    report.asset = resolved
+        .url
+        .as_deref()
+        .and_then(fetcher::url_file_name)
+        .map(str::to_string);
+    if hold_for_approval(app, current_version, &resolved.version, report) {
+        return Ok(None);
+    }
+    Ok(Some(resolved))
+}
+
+/// Report an update found on a dry run, which downloads nothing.
+fn report_would_update(
+    app: &config::ApplicationConfig,
+    current_version: &Version,
+    resolved: &Resolved,
+    report: &mut AppReport,
+) {
+    warn!(
+        "{}: update available ({} -> {}){}, dry-run enabled; not downloading",
+        app.name,
+        current_version,
+        resolved.version,
+        resolved
+            .url
+            .as_deref()
+            .map(|url| format!(" at {}", url))
+            .unwrap_or_default()
+    );
+    report.action = Action::WouldUpdate;
+}
+
+/// On a dry run or with `auto_update_level`, decide from the fetcher's
+/// metadata whether there is an update to download at all; returns whether the
+/// application is done. Fetchers that can't tell without downloading download
+/// as usual, and the download is checked instead.
+fn checked_before_download(
+    app: &config::ApplicationConfig,
+    fetcher: &dyn fetcher::Fetcher,
+    current_version: &Version,
+    dry_run: bool,
+    report: &mut AppReport,
+) -> Result<bool> {
+    let resolved = match fetcher.resolve_latest() {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            debug!(
+                "{}: latest version unknown before downloading: {:#}",
+                app.name, e
+            );
+            return Ok(false);
+        }
+    };
+    let Some(resolved) = resolve_update(app, resolved, current_version, report)? else {
+        return Ok(true);
+    };
+    if dry_run {
+        report_would_update(app, current_version, &resolved, report);
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// The kept download of a deferred install, if its file is still there and it
+/// would replace `current_version`. One without a version is left for the
+/// installer to compare.
+fn newer_kept_download(
+    app: &config::ApplicationConfig,
+    kept: Option<&Download>,
+    current_version: &Version,
+) -> Result<Option<Download>> {
+    let Some(kept) = kept.filter(|kept| kept.path.is_file()) else {
+        return Ok(None);
+    };
+    let requirement = VersionRequirement::from_app(app)?;
+    let newer = kept
+        .version
+        .as_deref()
+        .is_none_or(|version| should_replace(current_version, version, requirement.as_ref()));
+    Ok(newer.then(|| kept.clone()))
+}
+
+/// Check the application's `min_free_mem` / `max_load` preconditions, returning
+/// why the install should wait if one is unmet. Metrics that can't be read
+/// don't block the install.
+fn install_deferral(app: &config::ApplicationConfig) -> Result<Option<String>> {
+    if let Some(min) = &app.min_free_mem {
+        let min_bytes = host::parse_size(min).context("Invalid min_free_mem")?;
+        match host::available_memory() {
+            Some(available) if available < min_bytes => {
+                return Ok(Some(format!(
+                    "{} of memory available, min_free_mem is {}",
+                    report::format_bytes(available),
+                    min
+                )));
+            }
+            Some(_) => {}
+            None => warn!(
+                "{}: could not read available memory; ignoring min_free_mem",
+                app.name
+            ),
+        }
+    }
+    if let Some(max) = app.max_load {
+        match host::load_average() {
+            Some(load) if load > max => {
+                return Ok(Some(format!(
+                    "load average is {:.2}, max_load is {}",
+                    load, max
+                )));
+            }
+            Some(_) => {}
+            None => warn!(
+                "{}: could not read the load average; ignoring max_load",
+                app.name
+            ),
+        }
+    }
+    Ok(None)
+}
+
+/// Size of a file in bytes, or 0 if it can't be read.
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Update flow for installers that fetch by themselves: ask the fetcher for the
+/// latest version and hand that version to the installer.
+fn install_latest_version(
+    app: &config::ApplicationConfig,
+    installer: &dyn installer::Installer,
+    fetcher: &dyn fetcher::Fetcher,
+    current_version: &Version,
+    dry_run: bool,
+    report: &mut AppReport,
+) -> Result<()> {
+    let Some(resolved) = resolve_update(app, fetcher.resolve_latest()?, current_version, report)?
+    else {
+        return Ok(());
+    };
+    let latest_version = &resolved.version;
+
+    let deferral = if dry_run {
+        None
+    } else {
+        install_deferral(app)?
+    };
+    if let Some(reason) = deferral {
+        warn!("{}: install deferred to a later run: {}", app.name, reason);
+        report
+            .warnings
+            .push(format!("install deferred: {}", reason));
+        report.action = Action::Deferred;
+    } else if dry_run {
+        report_would_update(app, current_version, &resolved, report);
+    } else {
+        match &resolved.url {
+            Some(url) => info!(
+                "{}: installing version {} from {}",
+                app.name, latest_version, url
+            ),
+            None => info!("{}: installing version {}", app.name, latest_version),
+        }
+        let _install = installer::exclusive();
+        run_pre_install_hooks(app)?;
+        installer.install_version(&resolved)?;
+        info!("{}: installation completed", app.name);
+        check_installed_version(app, installer, current_version, report, || {
+            installer.install_version(&resolved)
+        })?;
+        verify_installed_version(app, installer, latest_version, current_version)?;
+        run_post_install_hooks(app, report);
+        report.action = Action::Installed;
+    }
+
+    Ok(())
+}
+
+/// Whether to pipe the download straight into the installer (`stream`), which
+/// needs a fetcher and installer that support it. Otherwise, and when a step
+/// needs the file itself, the download is saved to a file as usual.
+fn should_stream(
+    app: &config::ApplicationConfig,
+    installer: &dyn installer::Installer,
+    fetcher: &dyn fetcher::Fetcher,
+) -> bool {
+    if app.stream != Some(true) {
+        return false;
+    }
+    let fallback = if !installer.supports_stream() {
+        Some("the installer cannot install from a stream")
+    } else if !fetcher.supports_stream() {
+        Some("the fetcher cannot stream downloads")
+    } else if app.pre_install_scan.is_some() {
+        Some("pre_install_scan needs the downloaded file")
+    } else if !app.fetcher.prefer.is_empty() {
+        Some("prefer may choose another installer for the asset")
+    } else {
+        None
+    };
+    match fallback {
+        Some(reason) => {
+            info!(
+                "{}: not streaming ({}); downloading to a file",
+                app.name, reason
+            );
+            false
+        }
+        None => true,
+    }
+}
+
+/// Update flow with `stream`: ask the fetcher for the latest version and, if it
+/// is newer, pipe its asset into the installer without a temporary file.
+fn stream_latest_version(
+    app: &config::ApplicationConfig,
+    installer: &dyn installer::Installer,
+    fetcher: &dyn fetcher::Fetcher,
+    current_version: &Version,
+    dry_run: bool,
+    report: &mut AppReport,
+) -> Result<()> {
+    let Some(resolved) = resolve_update(app, fetcher.resolve_latest()?, current_version, report)?
+    else {
+        return Ok(());
+    };
+    let Some(url) = resolved.url.clone() else {
+        warn!("{}: no asset matching the pattern found", app.name);
+        report.new_version = None;
+        report.action = Action::UpToDate;
+        return Ok(());
+    };
+    let latest_version = &resolved.version;
+
+    let deferral = if dry_run {
+        None
+    } else {
+        install_deferral(app)?
+    };
+    if let Some(reason) = deferral {
+        warn!("{}: install deferred to a later run: {}", app.name, reason);
+        report
+            .warnings
+            .push(format!("install deferred: {}", reason));
+        report.action = Action::Deferred;
+        return Ok(());
+    }
+    if dry_run {
+        report_would_update(app, current_version, &resolved, report);
+        return Ok(());
+    }
+
+    let install = || -> Result<u64> {
+        let mut reader = CountingReader::new(fetcher.open_stream(&url)?);
+        installer.install_stream(&mut reader)?;
+        Ok(reader.count)
+    };
+    info!(
+        "{}: installing version {} streamed from {}",
+        app.name, latest_version, url
+    );
+    let _install = installer::exclusive();
+    run_pre_install_hooks(app)?;
+    let bytes = install()?;
+    info!("{}: installation completed", app.name);
+    report.bytes_downloaded = bytes;
+    report.bytes_installed = bytes;
+    check_installed_version(app, installer, current_version, report, || {
+        install().map(|_| ())
+    })?;
+    verify_installed_version(app, installer, latest_version, current_version)?;
+    run_post_install_hooks(app, report);
+    report.action = Action::Installed;
+    Ok(())
+}
+
+/// Reader that counts the bytes read through it.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// With `post_install_mismatch` set, ask the installer for the version again
+/// after an install and apply the policy if it still reports `previous_version`.
+fn check_installed_version(
+    app: &config::ApplicationConfig,
+    installer: &dyn installer::Installer,
+    previous_version: &Version,
+    report: &mut AppReport,
+    reinstall: impl Fn() -> Result<()>,
+) -> Result<()> {
+    let Some(policy) = app.post_install_mismatch else {
+        return Ok(());
+    };
+    let unchanged = || -> Result<bool> {
+        Ok(match installer.should_check_for_update()? {
+            UpdateCheck::Yes(version) => version == *previous_version,
+            UpdateCheck::No => false,
+        })
+    };
+
+    if !unchanged()? {
+        info!("{}: installed version changed as expected", app.name);
+        return Ok(());
+    }
+
+    let message = format!(
+        "install succeeded but installed version is still {}",
+        previous_version
+    );
+    match policy {
+        PostInstallMismatch::Warn => {
+            warn!("{}: {}", app.name, message);
+            report.warnings.push(message);
+            Ok(())
+        }
+        PostInstallMismatch::Error => Err(anyhow!(message)),
+        PostInstallMismatch::Retry => {
+            warn!("{}: {}; retrying install once", app.name, message);
+            reinstall()?;
+            if unchanged()? {
+                Err(anyhow!("{} (after retrying)", message))
+            } else {
+                info!("{}: installed version changed after retry", app.name);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// With `verify_installed_version` set, fail unless the installer now reports
+/// `expected_version`, rolling back to `previous_version` if configured.
+fn verify_installed_version(
+    app: &config::ApplicationConfig,
+    installer: &dyn installer::Installer,
+    expected_version: &str,
+    previous_version: &Version,
+) -> Result<()> {
+    if !app.verify_installed_version.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let installed = match installer.should_check_for_update()? {
+        UpdateCheck::Yes(version) => version,
+        UpdateCheck::No => return Ok(()),
+    };
+    if version::matches_version(installed.as_str(), expected_version) {
+        info!(
+            "{}: installed version {} matches expected {}",
+            app.name, installed, expected_version
+        );
+        return Ok(());
+    }
+
+    let message = format!(
+        "installed version is {} but {} was expected",
+        installed, expected_version
+    );
+    if app.rollback_on_mismatch.unwrap_or(false) {
+        warn!(
+            "{}: {}; rolling back to {}",
+            app.name, message, previous_version
+        );
+        if let Err(e) = installer.rollback(previous_version) {
+            return Err(anyhow!("{}; rollback failed: {:#}", message, e));
+        }
+        return Err(anyhow!("{}; rolled back to {}", message, previous_version));
+    }
+    Err(anyhow!(message))
+}
+
+fn self_install_command(
+    install_dir: PathBuf,
+    config_path: PathBuf,
+    verify_config: bool,
+    timer_interval: Option<&str>,
+    randomized_delay: Option<&str>,
+) -> Result<()> {
     info!("Starting self-install process");
 
+    // Check the timer options before anything is installed
+    let timer = timer_unit(timer_interval, randomized_delay)?;
+    let replace_timers = timer_interval.is_some() || randomized_delay.is_some();
+
     // 1. Install binary
     install_binary(&install_dir)?;
 
@@ -187,15 +2097,26 @@ fn self_install_command(install_dir: PathBuf, config_path: PathBuf) -> Result<()
     install_config_file(&config_path)?;
 
     // 3. Install systemd units
-    install_systemd_units()?;
+    let units = systemd_units(&config_path, &timer);
+    let replaced = install_systemd_units(&units, replace_timers)?;
+    verify_systemd_units(&units)?;
 
-    // 4. Reload systemd and enable timer
-    enable_systemd_timer()?;
+    // 4. Reload systemd and enable timers
+    let timers: Vec<&str> = units
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| name.ends_with(".timer"))
+        .collect();
+    enable_systemd_timers(&timers)?;
+    restart_systemd_timers(&replaced)?;
 
     info!("Self-install completed successfully!");
     info!("Binary installed to: {}/autopkg", install_dir.display());
     info!("Config file at: {}", config_path.display());
-    info!("systemd units installed and timer enabled");
+    info!(
+        "systemd units installed and timer(s) enabled: {}",
+        timers.join(", ")
+    );
     info!("");
     info!("You can now:");
     info!("  - Edit the config file: {}", config_path.display());
@@ -205,6 +2126,160 @@ fn self_install_command(install_dir: PathBuf, config_path: PathBuf) -> Result<()
         config_path.display()
     );
 
+    if verify_config {
+        verify_installed_config(&config_path)?;
+    }
+
+    Ok(())
+}
+
+fn self_uninstall_command(install_dir: PathBuf, config_path: PathBuf, purge: bool) -> Result<()> {
+    info!("Starting self-uninstall process");
+
+    // The same units self-install would install, including per-schedule ones
+    // (read from the config, so before it is purged)
+    let systemd_dir = Path::new("/etc/systemd/system");
+    let units = systemd_units(&config_path, SYSTEMD_TIMER);
+
+    // 1. Stop and disable timers
+    let timers: Vec<&str> = units
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| name.ends_with(".timer"))
+        .collect();
+    disable_systemd_timers(&timers);
+
+    // 2. Remove systemd units and reload systemd
+    for (name, _) in &units {
+        remove_installed_file(&systemd_dir.join(name), "Unit file")?;
+    }
+    if which::which("systemctl").is_ok() {
+        info!("Reloading systemd daemon");
+        match Command::new("systemctl").arg("daemon-reload").output() {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => warn!(
+                "Failed to reload systemd daemon: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => warn!("Failed to execute 'systemctl daemon-reload': {}", e),
+        }
+    }
+
+    // 3. Remove binary
+    remove_installed_file(&install_dir.join("autopkg"), "Binary")?;
+
+    // 4. Remove config file
+    if purge {
+        remove_installed_file(&config_path, "Config file")?;
+        // Only removed if nothing else was kept in it
+        if let Some(parent) = config_path.parent() {
+            if fs::remove_dir(parent).is_ok() {
+                info!("Removed empty config directory {}", parent.display());
+            }
+        }
+    } else if config_path.exists() {
+        info!(
+            "Config file left in place at {} (use --purge to remove it)",
+            config_path.display()
+        );
+    }
+
+    info!("Self-uninstall completed successfully!");
+    Ok(())
+}
+
+/// Stop and disable `timers`. Failures are only warnings, since a timer that
+/// isn't installed or loaded has nothing to stop.
+fn disable_systemd_timers(timers: &[&str]) {
+    if which::which("systemctl").is_err() {
+        warn!("systemctl command not found; not stopping timers");
+        return;
+    }
+
+    for timer in timers {
+        info!("Stopping and disabling {}", timer);
+        let output = Command::new("systemctl")
+            .arg("disable")
+            .arg("--now")
+            .arg(timer)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                info!("{} stopped and disabled", timer)
+            }
+            Ok(output) => warn!(
+                "Failed to stop and disable {}: {}",
+                timer,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => warn!(
+                "Failed to execute 'systemctl disable --now {}': {}",
+                timer, e
+            ),
+        }
+    }
+}
+
+/// Delete a file installed by self-install, warning if it is already gone.
+fn remove_installed_file(path: &Path, what: &str) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            info!("{} removed: {}", what, path.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("{} {} does not exist, skipping", what, path.display());
+            Ok(())
+        }
+        Err(e) => Err(e).with_context(|| {
+            format!(
+                "Failed to remove {}. Do you have permission? (Try running with sudo)",
+                path.display()
+            )
+        }),
+    }
+}
+
+/// Dry-run the installed config against the network, so configuration problems
+/// show up now rather than when the timer first fires.
+fn verify_installed_config(config_path: &Path) -> Result<()> {
+    info!("Verifying {} with a dry run", config_path.display());
+    let report = run_command(
+        Some(config_path.to_path_buf()),
+        None,
+        &[],
+        RunOptions {
+            dry_run: true,
+            offline: false,
+            diff_assets: false,
+            lock_file: None,
+            fail_fast: false,
+            force: true,
+            refresh: false,
+            jobs: 1,
+            quiet: true,
+            report_path: None,
+            verify_only: true,
+        },
+        Selection {
+            only: Vec::new(),
+            schedule: None,
+            unscheduled: false,
+            group: None,
+            tag: None,
+        },
+        Some(ReportFormat::Text),
+    )?;
+
+    let failed = report.count(Action::Failed);
+    if failed > 0 {
+        return Err(anyhow!(
+            "Config verification failed for {} application(s); autopkg is installed, fix {} and run `autopkg run --dry-run` to check again",
+            failed,
+            config_path.display()
+        ));
+    }
+    info!("Config verified: no application failed");
     Ok(())
 }
 
@@ -236,28 +2311,14 @@ fn install_binary(install_dir: &Path) -> Result<()> {
         return Ok(());
     }
 
-    // Copy the binary
-    fs::copy(&current_exe, &target_path).with_context(|| {
+    // Copy the binary into place atomically with executable permissions
+    installer::install_file_atomically(&current_exe, &target_path, 0o755).with_context(|| {
         format!(
-            "Failed to copy binary to {}. Do you have permission to write to this directory?",
+            "Failed to install binary to {}. Do you have permission to write to this directory?",
             target_path.display()
         )
     })?;
 
-    // Set executable permissions on Unix
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&target_path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&target_path, perms).with_context(|| {
-            format!(
-                "Failed to set executable permissions on {}",
-                target_path.display()
-            )
-        })?;
-    }
-
     info!("Binary installed successfully to {}", target_path.display());
     Ok(())
 }
@@ -301,7 +2362,176 @@ fn install_config_file(config_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn install_systemd_units() -> Result<()> {
+/// The `autopkg.timer` unit, with `OnCalendar=` set to `interval` and
+/// `RandomizedDelaySec=` to `randomized_delay` when given. `interval` is
+/// checked with `systemd-analyze calendar` if available.
+fn timer_unit(interval: Option<&str>, randomized_delay: Option<&str>) -> Result<String> {
+    if let Some(interval) = interval {
+        validate_on_calendar(interval)?;
+    }
+    let delay = randomized_delay
+        .map(|delay| {
+            humantime::parse_duration(delay).map_err(|e| {
+                anyhow!(
+                    "Invalid --randomized-delay '{}': {} (expected a duration such as 30m or 2h)",
+                    delay,
+                    e
+                )
+            })
+        })
+        .transpose()?;
+
+    let timer = SYSTEMD_TIMER
+        .lines()
+        .map(|line| match (interval, delay) {
+            (Some(interval), _) if line.starts_with("OnCalendar=") => {
+                format!("OnCalendar={}", interval)
+            }
+            (Some(interval), _) if line.starts_with("Description=") => {
+                format!("Description=Run autopkg package updater ({})", interval)
+            }
+            (_, Some(delay)) if line.starts_with("RandomizedDelaySec=") => {
+                format!("RandomizedDelaySec={}", delay.as_secs())
+            }
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(timer + "\n")
+}
+
+/// Fail early on an `OnCalendar=` expression systemd wouldn't accept.
+fn validate_on_calendar(interval: &str) -> Result<()> {
+    let help = "expected an OnCalendar expression such as `daily`, `Mon *-*-* 06:00:00` or `*-*-* 00/6:00:00` (see `man systemd.time`)";
+    if interval.trim().is_empty() || interval.contains('\n') {
+        return Err(anyhow!("Invalid --timer-interval '{}': {}", interval, help));
+    }
+    if which::which("systemd-analyze").is_err() {
+        warn!(
+            "systemd-analyze not found; not checking --timer-interval '{}'",
+            interval
+        );
+        return Ok(());
+    }
+
+    let output = Command::new("systemd-analyze")
+        .arg("calendar")
+        .arg(interval)
+        .output()
+        .context("Failed to execute 'systemd-analyze calendar'")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Invalid --timer-interval '{}': {}; {}",
+            interval,
+            String::from_utf8_lossy(&output.stderr).trim(),
+            help
+        ));
+    }
+    info!("Timer interval '{}' is valid", interval);
+    Ok(())
+}
+
+/// Unit files to install as `(file name, contents)` pairs: the default
+/// `autopkg.service` and `timer`, plus a service/timer pair for every distinct
+/// application `schedule` in the config, derived from the same templates. When
+/// there are schedules, the default service runs `--unscheduled`, so scheduled
+/// applications are only checked by their own timer.
+fn systemd_units(config_path: &Path, timer: &str) -> Vec<(String, String)> {
+    let schedules = match load_config(Some(config_path.to_path_buf()), None, &[]) {
+        Ok((config, _)) => config.schedules(),
+        Err(e) => {
+            warn!(
+                "Could not read schedules from {}: {:#}; installing the default timer only",
+                config_path.display(),
+                e
+            );
+            Vec::new()
+        }
+    };
+
+    let default_service = if schedules.is_empty() {
+        SYSTEMD_SERVICE.to_string()
+    } else {
+        SYSTEMD_SERVICE
+            .lines()
+            .map(|line| {
+                if line.starts_with("ExecStart=") {
+                    format!("{} --unscheduled", line)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "\n"
+    };
+    let mut units = vec![
+        ("autopkg.service".to_string(), default_service),
+        ("autopkg.timer".to_string(), timer.to_string()),
+    ];
+
+    for schedule in schedules {
+        let unit_name = schedule_unit_name(&schedule);
+        info!("Adding {} units for schedule '{}'", unit_name, schedule);
+
+        let service = SYSTEMD_SERVICE
+            .lines()
+            .map(|line| {
+                if line.starts_with("ExecStart=") {
+                    format!("{} --schedule \"{}\"", line, schedule)
+                } else if line.starts_with("Description=") {
+                    format!("{} ({})", line, schedule)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let timer = timer
+            .lines()
+            .map(|line| {
+                if line.starts_with("OnCalendar=") {
+                    format!("OnCalendar={}", schedule)
+                } else if line.starts_with("Description=") {
+                    format!("Description=Run autopkg package updater ({})", schedule)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        units.push((format!("{}.service", unit_name), service + "\n"));
+        units.push((format!("{}.timer", unit_name), timer + "\n"));
+    }
+
+    units
+}
+
+/// Unit name for a schedule, e.g. `hourly` -> `autopkg-hourly`. Schedules with
+/// characters other than letters, digits and `-` get a hash of the schedule
+/// appended (`*-*-* 04:00` -> `autopkg-04-00-1a2b3c4d`), so two schedules that
+/// differ only in those characters don't share units.
+fn schedule_unit_name(schedule: &str) -> String {
+    let sanitized: String = schedule
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let name = format!("autopkg-{}", sanitized.trim_matches('-'));
+    if sanitized == schedule && !schedule.starts_with('-') && !schedule.ends_with('-') {
+        return name;
+    }
+    let digest = Sha256::digest(schedule.as_bytes());
+    format!(
+        "{}-{}",
+        name.trim_end_matches('-'),
+        &hex::encode(digest)[..8]
+    )
+}
+
+/// Write the unit files that don't exist yet, and with `replace_timers` also
+/// timers whose contents changed. Returns the names of the replaced timers.
+fn install_systemd_units(units: &[(String, String)], replace_timers: bool) -> Result<Vec<String>> {
     let systemd_dir = Path::new("/etc/systemd/system");
 
     info!("Installing systemd units to {}", systemd_dir.display());
@@ -314,49 +2544,75 @@ fn install_systemd_units() -> Result<()> {
         ));
     }
 
-    // Install service file
-    let service_path = systemd_dir.join("autopkg.service");
-    if service_path.exists() {
-        info!(
-            "Service file already exists at {}, skipping",
-            service_path.display()
-        );
-    } else {
-        fs::write(&service_path, SYSTEMD_SERVICE)
-            .with_context(|| format!(
-                "Failed to write service file to {}. Do you have permission? (Try running with sudo)",
-                service_path.display()
-            ))?;
-        info!(
-            "Service file created successfully at {}",
-            service_path.display()
-        );
-    }
+    let mut replaced = Vec::new();
+    for (name, contents) in units {
+        let unit_path = systemd_dir.join(name);
+        if unit_path.exists() {
+            let replace = replace_timers
+                && name.ends_with(".timer")
+                && fs::read_to_string(&unit_path).ok().as_ref() != Some(contents);
+            if !replace {
+                info!(
+                    "Unit file already exists at {}, skipping",
+                    unit_path.display()
+                );
+                continue;
+            }
+            info!(
+                "Replacing {} with the new --timer-interval/--randomized-delay",
+                unit_path.display()
+            );
+            replaced.push(name.clone());
+        }
 
-    // Install timer file
-    let timer_path = systemd_dir.join("autopkg.timer");
-    if timer_path.exists() {
-        info!(
-            "Timer file already exists at {}, skipping",
-            timer_path.display()
-        );
-    } else {
-        fs::write(&timer_path, SYSTEMD_TIMER).with_context(|| {
+        fs::write(&unit_path, contents).with_context(|| {
             format!(
-                "Failed to write timer file to {}. Do you have permission? (Try running with sudo)",
-                timer_path.display()
+                "Failed to write unit file to {}. Do you have permission? (Try running with sudo)",
+                unit_path.display()
             )
         })?;
-        info!(
-            "Timer file created successfully at {}",
-            timer_path.display()
-        );
+        info!("Unit file created successfully at {}", unit_path.display());
+    }
+
+    Ok(replaced)
+}
+
+/// Check the installed units with `systemd-analyze verify`, so a broken unit
+/// fails the install instead of the first timer run.
+fn verify_systemd_units(units: &[(String, String)]) -> Result<()> {
+    if which::which("systemd-analyze").is_err() {
+        warn!("systemd-analyze not found; skipping unit verification");
+        return Ok(());
     }
 
+    let paths: Vec<PathBuf> = units
+        .iter()
+        .map(|(name, _)| Path::new("/etc/systemd/system").join(name))
+        .collect();
+    info!("Verifying systemd units with systemd-analyze");
+    let output = Command::new("systemd-analyze")
+        .arg("verify")
+        .args(&paths)
+        .output()
+        .context("Failed to execute 'systemd-analyze verify'")?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(anyhow!(
+            "systemd unit verification failed: {}",
+            stderr.trim()
+        ));
+    }
+    // Problems found in other units are reported without failing the check
+    for line in stderr.lines().filter(|l| !l.trim().is_empty()) {
+        warn!("systemd-analyze: {}", line);
+    }
+
+    info!("systemd units verified successfully");
     Ok(())
 }
 
-fn enable_systemd_timer() -> Result<()> {
+fn enable_systemd_timers(timers: &[&str]) -> Result<()> {
     info!("Reloading systemd daemon");
 
     // Check if systemctl is available
@@ -379,23 +2635,47 @@ fn enable_systemd_timer() -> Result<()> {
 
     info!("Systemd daemon reloaded successfully");
 
-    // Enable and start the timer
-    info!("Enabling and starting autopkg.timer");
-    let enable_output = Command::new("systemctl")
-        .arg("enable")
-        .arg("--now")
-        .arg("autopkg.timer")
-        .output()
-        .context("Failed to execute 'systemctl enable --now autopkg.timer'")?;
+    // Enable and start the timers
+    for timer in timers {
+        info!("Enabling and starting {}", timer);
+        let enable_output = Command::new("systemctl")
+            .arg("enable")
+            .arg("--now")
+            .arg(timer)
+            .output()
+            .with_context(|| format!("Failed to execute 'systemctl enable --now {}'", timer))?;
 
-    if !enable_output.status.success() {
-        let stderr = String::from_utf8_lossy(&enable_output.stderr);
-        return Err(anyhow!(
-            "Failed to enable and start autopkg.timer: {}. Do you have permission? (Try running with sudo)",
-            stderr
-        ));
+        if !enable_output.status.success() {
+            let stderr = String::from_utf8_lossy(&enable_output.stderr);
+            return Err(anyhow!(
+                "Failed to enable and start {}: {}. Do you have permission? (Try running with sudo)",
+                timer,
+                stderr
+            ));
+        }
+
+        info!("{} enabled and started successfully", timer);
     }
+    Ok(())
+}
 
-    info!("autopkg.timer enabled and started successfully");
+/// Restart timers whose unit file was replaced (after the daemon reload), so an
+/// already running timer uses its new schedule right away.
+fn restart_systemd_timers(timers: &[String]) -> Result<()> {
+    for timer in timers {
+        info!("Restarting {}", timer);
+        let output = Command::new("systemctl")
+            .arg("restart")
+            .arg(timer)
+            .output()
+            .with_context(|| format!("Failed to execute 'systemctl restart {}'", timer))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Failed to restart {}: {}",
+                timer,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
     Ok(())
 }
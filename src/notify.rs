@@ -0,0 +1,104 @@
+use crate::config::NotificationsConfig;
+use crate::fetcher::{self, HttpClient};
+use crate::report::{Action, AppReport};
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::process::Command;
+use std::time::Duration;
+use which::which;
+
+/// How long a webhook may take before it is given up on.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// JSON body POSTed to the webhook.
+#[derive(Debug, Serialize)]
+struct Notification<'a> {
+    /// `installed`, `needs_approval` or `failed`
+    event: &'static str,
+    app: &'a str,
+    old_version: Option<&'a str>,
+    new_version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+impl Notification<'_> {
+    fn summary(&self) -> String {
+        match self.event {
+            "failed" => format!("autopkg: {} update failed", self.app),
+            "needs_approval" => format!("autopkg: {} update needs approval", self.app),
+            _ => format!("autopkg: {} updated", self.app),
+        }
+    }
+
+    fn body(&self) -> String {
+        if let Some(error) = self.error {
+            return error.to_string();
+        }
+        format!(
+            "{} -> {}",
+            self.old_version.unwrap_or("not installed"),
+            self.new_version.unwrap_or("unknown")
+        )
+    }
+}
+
+/// Notify about an application's outcome, if it was installed, has an update
+/// held for approval, or failed (with `notify_on_failure`). Delivery is best-effort: problems are logged as
+/// warnings and never fail the application.
+pub fn notify(config: &NotificationsConfig, http: &HttpClient, report: &AppReport) {
+    let event = match report.action {
+        Action::Installed => "installed",
+        Action::NeedsApproval => "needs_approval",
+        Action::Failed if config.notify_on_failure.unwrap_or(false) => "failed",
+        _ => return,
+    };
+    let notification = Notification {
+        event,
+        app: &report.name,
+        old_version: report.current_version.as_deref(),
+        new_version: report.new_version.as_deref(),
+        error: report.error.as_deref(),
+    };
+
+    if let Some(url) = &config.webhook {
+        if let Err(e) = send_webhook(http, url, &notification) {
+            warn!("{}: webhook notification failed: {:#}", report.name, e);
+        }
+    }
+    if config.desktop.unwrap_or(false) {
+        if let Err(e) = send_desktop(&notification) {
+            warn!("{}: desktop notification failed: {:#}", report.name, e);
+        }
+    }
+}
+
+fn send_webhook(http: &HttpClient, url: &str, notification: &Notification) -> Result<()> {
+    let request = http
+        .client()
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(notification);
+    let resp = fetcher::send(request).with_context(|| format!("Failed to POST to {}", url))?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("{} returned status {}", url, resp.status()));
+    }
+    info!("Sent {} notification to webhook", notification.event);
+    Ok(())
+}
+
+fn send_desktop(notification: &Notification) -> Result<()> {
+    let program = which("notify-send").context("notify-send not found in PATH")?;
+    let status = Command::new(program)
+        .arg("--app-name=autopkg")
+        .arg(notification.summary())
+        .arg(notification.body())
+        .status()
+        .context("Failed to run notify-send")?;
+    if !status.success() {
+        return Err(anyhow!("notify-send failed with status {}", status));
+    }
+    Ok(())
+}
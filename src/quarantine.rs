@@ -0,0 +1,129 @@
+use crate::fetcher::RejectedDownload;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sidecar written next to a quarantined file, explaining why it was rejected.
+#[derive(Debug, Serialize)]
+struct Sidecar<'a> {
+    app: &'a str,
+    /// Where the file was downloaded to
+    original_path: String,
+    /// Unix time of the rejection
+    timestamp: u64,
+    reason: String,
+}
+
+/// Quarantine directory from `settings.quarantine_dir`; relative paths are
+/// resolved against the config file's directory.
+pub fn dir(setting: Option<&str>, config_path: &Path) -> Option<PathBuf> {
+    let setting = Path::new(setting?);
+    if setting.is_absolute() {
+        return Some(setting.to_path_buf());
+    }
+    let base = config_path.parent().unwrap_or(Path::new("."));
+    Some(base.join(setting))
+}
+
+/// Dispose of a download that failed verification: move it to `quarantine_dir`
+/// with a `.reason.json` sidecar if one is configured, otherwise delete it.
+/// Returns `error` with a note saying where the file went.
+pub fn reject(
+    app: &str,
+    file_path: &Path,
+    error: anyhow::Error,
+    quarantine_dir: Option<&Path>,
+) -> anyhow::Error {
+    let Some(dir) = quarantine_dir else {
+        if let Err(e) = fs::remove_file(file_path) {
+            warn!(
+                "Failed to delete rejected file {}: {}",
+                file_path.display(),
+                e
+            );
+            return error;
+        }
+        return error.context(format!("{} deleted", file_path.display()));
+    };
+
+    match move_to_quarantine(app, file_path, &error, dir) {
+        Ok(dest) => {
+            info!(
+                "{}: quarantined {} as {}",
+                app,
+                file_path.display(),
+                dest.display()
+            );
+            error.context(format!("file quarantined as {}", dest.display()))
+        }
+        Err(e) => {
+            warn!(
+                "{}: failed to quarantine {}: {:#}",
+                app,
+                file_path.display(),
+                e
+            );
+            error
+        }
+    }
+}
+
+/// Like [`reject`], for an error from a fetcher: if it is about a download
+/// that failed its checksum or signature check ([`RejectedDownload`]), that
+/// file is quarantined or deleted. Other errors are returned unchanged.
+pub fn reject_fetched(
+    app: &str,
+    error: anyhow::Error,
+    quarantine_dir: Option<&Path>,
+) -> anyhow::Error {
+    let Some(path) = error
+        .downcast_ref::<RejectedDownload>()
+        .map(|rejected| rejected.path.clone())
+    else {
+        return error;
+    };
+    reject(app, &path, error, quarantine_dir)
+}
+
+fn move_to_quarantine(
+    app: &str,
+    file_path: &Path,
+    error: &anyhow::Error,
+    dir: &Path,
+) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let timestamp = crate::state::now();
+    let file_name = file_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "download".to_string());
+    let dest = dir.join(format!("{}-{}", timestamp, file_name));
+
+    // The download directory is often on another filesystem, where rename fails
+    if fs::rename(file_path, &dest).is_err() {
+        fs::copy(file_path, &dest).with_context(|| {
+            format!(
+                "Failed to copy {} to {}",
+                file_path.display(),
+                dest.display()
+            )
+        })?;
+        fs::remove_file(file_path)
+            .with_context(|| format!("Failed to remove {}", file_path.display()))?;
+    }
+
+    let sidecar = Sidecar {
+        app,
+        original_path: file_path.display().to_string(),
+        timestamp,
+        reason: format!("{:#}", error),
+    };
+    let sidecar_path = PathBuf::from(format!("{}.reason.json", dest.display()));
+    fs::write(&sidecar_path, serde_json::to_string_pretty(&sidecar)?)
+        .with_context(|| format!("Failed to write {}", sidecar_path.display()))?;
+    Ok(dest)
+}
@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Output format for the run report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Markdown,
+    Junit,
+}
+
+/// What happened to an application during a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Update check was skipped (e.g., pinned)
+    Skipped,
+    /// Installed version is already the latest
+    UpToDate,
+    /// A newer version was found but not downloaded (dry-run)
+    WouldUpdate,
+    /// A newer version was downloaded but not installed (dry-run, for fetchers
+    /// that only know the version once they have downloaded it)
+    Downloaded,
+    /// A newer version was downloaded and installed
+    Installed,
+    /// A newer version was downloaded, but the install was postponed because the
+    /// host didn't meet the application's preconditions
+    Deferred,
+    /// A newer version was found, but it is a bigger bump than the application's
+    /// `auto_update_level` allows
+    NeedsApproval,
+    /// Processing the application failed
+    Failed,
+}
+
+impl Action {
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Skipped => "skipped",
+            Action::UpToDate => "up-to-date",
+            Action::WouldUpdate => "would-update",
+            Action::Downloaded => "downloaded",
+            Action::Installed => "installed",
+            Action::Deferred => "deferred",
+            Action::NeedsApproval => "needs-approval",
+            Action::Failed => "failed",
+        }
+    }
+}
+
+/// Result of processing a single application.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppReport {
+    pub name: String,
+    pub action: Action,
+    /// Version reported by the installer before any update
+    pub current_version: Option<String>,
+    /// Newer version found by the fetcher, if any
+    pub new_version: Option<String>,
+    /// Path of the downloaded update, if any
+    pub downloaded_path: Option<PathBuf>,
+    /// File name of the asset selected for the update, if any
+    pub asset: Option<String>,
+    /// Installer type that installed the update, when a `prefer` entry chose
+    /// another than the application's own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installer: Option<String>,
+    /// Error message when `action` is `failed`
+    pub error: Option<String>,
+    /// Problems worth attention that didn't stop processing (e.g. archived upstream)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Size of the downloaded update
+    pub bytes_downloaded: u64,
+    /// Size of the update that was installed
+    pub bytes_installed: u64,
+}
+
+impl AppReport {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            action: Action::Skipped,
+            current_version: None,
+            new_version: None,
+            downloaded_path: None,
+            asset: None,
+            installer: None,
+            error: None,
+            warnings: Vec::new(),
+            bytes_downloaded: 0,
+            bytes_installed: 0,
+        }
+    }
+}
+
+/// Summary of a whole `run` invocation.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub dry_run: bool,
+    pub applications: Vec<AppReport>,
+    /// Disk usage totals across all applications
+    pub disk: DiskUsage,
+    /// An installed update needs a reboot to take effect
+    pub reboot_required: bool,
+}
+
+/// Disk impact of a run, in bytes.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DiskUsage {
+    pub downloaded: u64,
+    pub installed: u64,
+    pub reclaimed: u64,
+}
+
+impl std::fmt::Display for DiskUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "downloaded {}, installed {}, reclaimed {}",
+            format_bytes(self.downloaded),
+            format_bytes(self.installed),
+            format_bytes(self.reclaimed)
+        )
+    }
+}
+
+/// Human-readable byte count, e.g. `1.2GB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+impl RunReport {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            applications: Vec::new(),
+            disk: DiskUsage::default(),
+            reboot_required: false,
+        }
+    }
+
+    /// Record an application's result and add its sizes to the disk totals.
+    pub fn push(&mut self, app: AppReport) {
+        self.disk.downloaded += app.bytes_downloaded;
+        self.disk.installed += app.bytes_installed;
+        self.applications.push(app);
+    }
+
+    pub fn count(&self, action: Action) -> usize {
+        self.applications
+            .iter()
+            .filter(|a| a.action == action)
+            .count()
+    }
+
+    /// One-line result summary printed at the end of `run`: how many
+    /// applications were up to date, updated, skipped and failed, with the
+    /// names of all but the up-to-date ones.
+    pub fn summary(&self) -> String {
+        let names = |actions: &[Action]| -> Vec<&str> {
+            self.applications
+                .iter()
+                .filter(|a| actions.contains(&a.action))
+                .map(|a| a.name.as_str())
+                .collect()
+        };
+        let bucket = |label: &str, names: Vec<&str>| {
+            if names.is_empty() {
+                format!("0 {}", label)
+            } else {
+                format!("{} {} ({})", names.len(), label, names.join(", "))
+            }
+        };
+        // A dry run stops before installing, so nothing is updated yet
+        let updated = if self.dry_run {
+            "update(s) available"
+        } else {
+            "updated"
+        };
+        format!(
+            "Summary: {} up to date, {}, {}, {}",
+            self.count(Action::UpToDate),
+            bucket(
+                updated,
+                names(&[Action::Installed, Action::WouldUpdate, Action::Downloaded])
+            ),
+            bucket(
+                "skipped",
+                names(&[Action::Skipped, Action::Deferred, Action::NeedsApproval])
+            ),
+            bucket("failed", names(&[Action::Failed]))
+        )
+    }
+
+    /// Render the report in the requested format.
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.render_text(),
+            ReportFormat::Json => serde_json::to_string_pretty(self)
+                .unwrap_or_else(|_| "<failed to serialize report>".into()),
+            ReportFormat::Markdown => self.render_markdown(),
+            ReportFormat::Junit => self.render_junit(),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let mut out = String::new();
+        for app in &self.applications {
+            let _ = write!(out, "{}: {}", app.name, app.action.label());
+            if let Some(version) = &app.current_version {
+                let _ = write!(out, " (current {})", version);
+            }
+            if let Some(error) = &app.error {
+                let _ = write!(out, " - {}", error);
+            }
+            for warning in &app.warnings {
+                let _ = write!(out, " [warning: {}]", warning);
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(out, "Disk: {}", self.disk);
+        if self.reboot_required {
+            out.push_str("Reboot required\n");
+        }
+        out
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = String::from("| Application | Action | Current version | Details |\n");
+        out.push_str("|---|---|---|---|\n");
+        for app in &self.applications {
+            let mut details = app
+                .error
+                .clone()
+                .or_else(|| {
+                    app.downloaded_path
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                })
+                .unwrap_or_default();
+            for warning in &app.warnings {
+                if !details.is_empty() {
+                    details.push_str("; ");
+                }
+                let _ = write!(details, "warning: {}", warning);
+            }
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                app.name,
+                app.action.label(),
+                app.current_version.as_deref().unwrap_or("-"),
+                details.replace('|', "\\|").replace('\n', " ")
+            );
+        }
+        let _ = writeln!(out, "\nDisk: {}", self.disk);
+        if self.reboot_required {
+            out.push_str("\n**Reboot required**\n");
+        }
+        out
+    }
+
+    fn render_junit(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            out,
+            "<testsuite name=\"autopkg\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">",
+            self.applications.len(),
+            self.count(Action::Failed),
+            self.count(Action::Skipped)
+                + self.count(Action::Deferred)
+                + self.count(Action::NeedsApproval)
+        );
+        for app in &self.applications {
+            let name = xml_escape(&app.name);
+            match app.action {
+                Action::Failed => {
+                    let message = xml_escape(app.error.as_deref().unwrap_or("failed"));
+                    let _ = writeln!(
+                        out,
+                        "  <testcase classname=\"autopkg\" name=\"{}\">\n    <failure message=\"{}\">{}</failure>\n  </testcase>",
+                        name, message, message
+                    );
+                }
+                Action::Skipped | Action::Deferred | Action::NeedsApproval => {
+                    let _ = writeln!(
+                        out,
+                        "  <testcase classname=\"autopkg\" name=\"{}\">\n    <skipped/>\n  </testcase>",
+                        name
+                    );
+                }
+                _ => {
+                    let _ = writeln!(out, "  <testcase classname=\"autopkg\" name=\"{}\"/>", name);
+                }
+            }
+        }
+        out.push_str("</testsuite>\n");
+        out
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
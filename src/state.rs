@@ -0,0 +1,131 @@
+use crate::lock;
+use crate::types::Download;
+
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File name of the state file, next to the config unless `settings.state_file` is set.
+pub const DEFAULT_STATE_FILE: &str = "autopkg-state.json";
+
+/// Data persisted between runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Unix time until which the GitHub API is rate-limited; GitHub checks are
+    /// skipped until then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_rate_limit_reset: Option<u64>,
+
+    /// File name of the asset last installed, per application, for `run --diff-assets`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assets: BTreeMap<String, String>,
+
+    /// Last successful update check, per application, for `check_interval`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, LastCheck>,
+
+    /// Installer type that installed an application, where a `prefer` entry
+    /// chose another than the application's own; it reports the installed version
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub installers: BTreeMap<String, String>,
+
+    /// Download whose install was deferred, per application, so `run --offline`
+    /// can install it without fetching it again
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub downloads: BTreeMap<String, Download>,
+
+    /// Last `version_url` response, per URL, which the `url` fetcher
+    /// revalidates with conditional requests
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub url_versions: BTreeMap<String, CachedVersion>,
+}
+
+/// When an application was last checked, and what the newest version was then.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastCheck {
+    /// Unix time of the check
+    pub checked_at: u64,
+    /// Newest version the fetcher reported, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_version: Option<String>,
+}
+
+/// A `version_url` response with its `ETag` / `Last-Modified` validators.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedVersion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+impl State {
+    /// Location of the state file: `state_file` if set, else next to the config file.
+    pub fn path(state_file: Option<&str>, config_path: &Path) -> PathBuf {
+        match state_file {
+            Some(path) => PathBuf::from(path),
+            None => config_path.with_file_name(DEFAULT_STATE_FILE),
+        }
+    }
+
+    /// Read the state file. A missing file is an empty state; an unreadable one
+    /// is logged and treated as empty so a corrupt file never blocks updates.
+    pub fn load(path: &Path) -> Self {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("State: failed to read {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            warn!(
+                "State: ignoring invalid state file {}: {}",
+                path.display(),
+                e
+            );
+            Self::default()
+        })
+    }
+
+    /// Apply `change` to the state file under its lock: the file is re-read, so
+    /// changes saved by another process in the meantime are kept, and written
+    /// back atomically.
+    pub fn update(path: &Path, change: impl FnOnce(&mut State)) -> Result<()> {
+        let _lock = lock::lock(path)?;
+        let mut state = Self::load(path);
+        change(&mut state);
+        let json = serde_json::to_string_pretty(&state)?;
+        lock::write_atomically(path, json.as_bytes())
+            .with_context(|| format!("Failed to save state file {}", path.display()))
+    }
+
+    /// Seconds since `app` was last checked; `None` if it never was (or the
+    /// recorded time is in the future, e.g. after the clock was set back).
+    pub fn last_checked_ago(&self, app: &str) -> Option<u64> {
+        self.checks
+            .get(app)
+            .and_then(|check| now().checked_sub(check.checked_at))
+    }
+
+    /// Seconds left until the recorded GitHub rate limit resets, if it hasn't yet.
+    pub fn github_backoff_remaining(&self) -> Option<u64> {
+        self.github_rate_limit_reset
+            .and_then(|reset| reset.checked_sub(now()))
+            .filter(|&secs| secs > 0)
+    }
+}
+
+/// Current Unix time in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
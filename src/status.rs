@@ -0,0 +1,127 @@
+use crate::config::ApplicationConfig;
+use crate::fetcher::{create_fetcher, HttpClient};
+use crate::installer::create_reporting_installer;
+use crate::types::UpdateCheck;
+use crate::version::{approval_needed, should_update, Version};
+
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Installed and latest version of one application, for `status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusEntry {
+    pub name: String,
+    /// Version reported by the installer; `None` if pinned or on error
+    pub installed: Option<String>,
+    /// Latest version reported by the fetcher; `None` if pinned or on error
+    pub latest: Option<String>,
+    /// Whether `latest` is newer than `installed`; `None` if unknown
+    pub update_available: Option<bool>,
+    /// Whether the update is bigger than `auto_update_level` allows, so `run`
+    /// holds it until it is approved
+    pub needs_approval: bool,
+    pub pinned: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Look up an application's installed and latest versions without downloading
+/// or installing anything. `recorded_installer` is the installer type the
+/// state file says installed it. Failures are recorded in the entry.
+pub fn check(
+    app: &ApplicationConfig,
+    recorded_installer: Option<&str>,
+    http: &HttpClient,
+) -> StatusEntry {
+    let mut entry = StatusEntry {
+        name: app.name.clone(),
+        installed: None,
+        latest: None,
+        update_available: None,
+        needs_approval: false,
+        pinned: false,
+        error: None,
+    };
+    if let Err(e) = fill(app, recorded_installer, http, &mut entry) {
+        entry.error = Some(format!("{:#}", e));
+    }
+    entry
+}
+
+fn fill(
+    app: &ApplicationConfig,
+    recorded_installer: Option<&str>,
+    http: &HttpClient,
+    entry: &mut StatusEntry,
+) -> Result<()> {
+    let installer = create_reporting_installer(app, recorded_installer)?;
+    let fetcher = create_fetcher(&app.fetcher, app, http)?;
+
+    let current = match installer.should_check_for_update()? {
+        UpdateCheck::No => {
+            entry.pinned = true;
+            return Ok(());
+        }
+        UpdateCheck::Yes(current) => current,
+    };
+    if current != Version::not_installed() {
+        entry.installed = Some(current.to_string());
+    }
+
+    let Some(latest) = fetcher.resolve_latest()?.map(|resolved| resolved.version) else {
+        return Ok(());
+    };
+    let update_available = should_update(app, &current, &latest)?;
+    entry.update_available = Some(update_available);
+    entry.needs_approval = update_available && approval_needed(app, &current, &latest).is_some();
+    entry.latest = Some(latest);
+    Ok(())
+}
+
+/// Render entries as a table: name, installed, latest, update available.
+pub fn render_table(entries: &[StatusEntry]) -> String {
+    let rows: Vec<[String; 4]> = entries
+        .iter()
+        .map(|e| {
+            let update = if e.pinned {
+                "pinned".to_string()
+            } else if let Some(error) = &e.error {
+                format!("error: {}", error)
+            } else {
+                match e.update_available {
+                    Some(true) if e.needs_approval => "yes (needs approval)".to_string(),
+                    Some(true) => "yes".to_string(),
+                    Some(false) => "no".to_string(),
+                    None => "unknown".to_string(),
+                }
+            };
+            [
+                e.name.clone(),
+                e.installed.clone().unwrap_or_else(|| "-".to_string()),
+                e.latest.clone().unwrap_or_else(|| "-".to_string()),
+                update,
+            ]
+        })
+        .collect();
+
+    let header = ["NAME", "INSTALLED", "LATEST", "UPDATE"];
+    let width = |i: usize| {
+        rows.iter()
+            .map(|r| r[i].len())
+            .chain([header[i].len()])
+            .max()
+            .unwrap_or(0)
+    };
+    let (w0, w1, w2) = (width(0), width(1), width(2));
+
+    let mut out = String::new();
+    for row in std::iter::once(header.map(str::to_string)).chain(rows) {
+        let _ = writeln!(
+            out,
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            row[0], row[1], row[2], row[3]
+        );
+    }
+    out
+}
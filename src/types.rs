@@ -1,3 +1,5 @@
+use crate::version::Version;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// Whether and how to check for updates.
@@ -6,8 +8,42 @@ pub enum UpdateCheck {
     /// Don't check (e.g., pinned version)
     No,
     /// Check for updates, with the current installed version
-    Yes(String),
+    Yes(Version),
+}
+
+/// A newer version downloaded (or built) by a fetcher.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Download {
+    /// Local path of the file to install
+    pub path: PathBuf,
+    /// Version the fetcher resolved for this file; `None` if the source doesn't
+    /// say (a `url` fetcher without `version_url`)
+    pub version: Option<String>,
+    /// File name of the selected asset (or built artifact), as published upstream
+    pub asset: String,
+    /// Installer type to use instead of the application's, from the `prefer`
+    /// entry that selected the file
+    pub installer: Option<String>,
+}
+
+/// Latest version found by a fetcher without downloading it, for installers
+/// that fetch by themselves.
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub version: String,
+    /// Download URL of the matching asset, if the source has one
+    pub url: Option<String>,
 }
 
 /// Common result type for components.
-pub type FetchResult = anyhow::Result<Option<PathBuf>>;
+pub type FetchResult = anyhow::Result<Option<Download>>;
+
+/// Description of a release asset, for diagnostics.
+#[derive(Debug, Clone)]
+pub struct AssetInfo {
+    pub name: String,
+    pub size: u64,
+    pub content_type: String,
+    /// Whether the fetcher's configured pattern selects this asset
+    pub matches: bool,
+}
@@ -0,0 +1,568 @@
+use crate::config::{ApplicationConfig, FetcherConfig, UpdateLevel};
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use regex::Regex;
+use std::cmp::Ordering;
+
+/// Naive version extraction from a tag like "v1.2.3" or "1.2.3".
+pub fn normalize_version(tag: &str) -> String {
+    let re = Regex::new(r"v?(?P<version>[0-9][0-9A-Za-z\.\-\+]*)").unwrap();
+    if let Some(caps) = re.captures(tag) {
+        caps["version"].to_string()
+    } else {
+        tag.to_string()
+    }
+}
+
+/// Numeric components of a normalized version, as compared by [`is_newer`],
+/// and the dot-separated parts that aren't numbers (which comparison ignores).
+pub fn components(version: &str) -> (Vec<u64>, Vec<String>) {
+    let mut numbers = Vec::new();
+    let mut ignored = Vec::new();
+    for part in version.split('.') {
+        match part.parse::<u64>() {
+            Ok(n) => numbers.push(n),
+            Err(_) => ignored.push(part.to_string()),
+        }
+    }
+    (numbers, ignored)
+}
+
+/// A normalized version as semver, with a missing minor or patch counting as 0
+/// (`1.2` is `1.2.0`); `None` if it isn't semver.
+pub fn parse_semver(version: &str) -> Option<semver::Version> {
+    let core_len = version.find(['-', '+']).unwrap_or(version.len());
+    let (core, rest) = version.split_at(core_len);
+    let padded = match core.matches('.').count() {
+        0 => format!("{}.0.0{}", core, rest),
+        1 => format!("{}.0{}", core, rest),
+        _ => version.to_string(),
+    };
+    semver::Version::parse(&padded).ok()
+}
+
+/// Compare two normalized versions.
+///
+/// Versions that are both semver are compared by semver precedence: a
+/// pre-release sorts below its release (`1.2.0-rc1` < `1.2.0`) and build
+/// metadata is ignored. Anything else falls back to [`first_difference`].
+pub fn compare(local: &str, remote: &str) -> Ordering {
+    if let (Some(l), Some(r)) = (parse_semver(local), parse_semver(remote)) {
+        return (l.major, l.minor, l.patch, &l.pre).cmp(&(r.major, r.minor, r.patch, &r.pre));
+    }
+
+    let lossy = !components(local).1.is_empty() || !components(remote).1.is_empty();
+    if lossy {
+        warn!(
+            "Versions {} and {} aren't both semver; comparing their numeric components only",
+            local, remote
+        );
+    } else {
+        debug!(
+            "Versions {} and {} aren't both semver; comparing numeric components",
+            local, remote
+        );
+    }
+    first_difference(local, remote).map_or(Ordering::Equal, |(_, l, r)| l.cmp(&r))
+}
+
+/// Returns true if `remote` is newer than `local` (see [`compare`]).
+pub fn is_newer(local: &str, remote: &str) -> bool {
+    compare(local, remote) == Ordering::Less
+}
+
+/// The first component (index, local value, remote value) at which two versions
+/// differ, with missing components counting as 0; `None` if they compare equal.
+pub fn first_difference(local: &str, remote: &str) -> Option<(usize, u64, u64)> {
+    let (mut local_parts, _) = components(local);
+    let (mut remote_parts, _) = components(remote);
+
+    let max_len = local_parts.len().max(remote_parts.len());
+    local_parts.resize(max_len, 0);
+    remote_parts.resize(max_len, 0);
+
+    local_parts
+        .into_iter()
+        .zip(remote_parts)
+        .enumerate()
+        .find(|(_, (l, r))| l != r)
+        .map(|(i, (l, r))| (i, l, r))
+}
+
+/// Compare two Debian package versions as `dpkg --compare-versions` does: the
+/// epoch, then the upstream version, then the revision, each part compared in
+/// runs of non-digits (letters before other characters, `~` before anything,
+/// even the end) and numbers.
+pub fn compare_debian(a: &str, b: &str) -> Ordering {
+    fn split(version: &str) -> (u64, &str, &str) {
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => {
+                (epoch.parse().unwrap_or(0), rest)
+            }
+            _ => (0, version),
+        };
+        let (upstream, revision) = rest.rsplit_once('-').unwrap_or((rest, ""));
+        (epoch, upstream, revision)
+    }
+    fn order(c: Option<u8>) -> i32 {
+        match c {
+            None => 0,
+            Some(b'~') => -1,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => i32::from(c),
+            Some(c) => i32::from(c) + 256,
+        }
+    }
+    fn compare_part(a: &[u8], b: &[u8]) -> Ordering {
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            while a.get(i).is_some_and(|c| !c.is_ascii_digit())
+                || b.get(j).is_some_and(|c| !c.is_ascii_digit())
+            {
+                let (ac, bc) = (order(a.get(i).copied()), order(b.get(j).copied()));
+                if ac != bc {
+                    return ac.cmp(&bc);
+                }
+                i += 1;
+                j += 1;
+            }
+            while a.get(i) == Some(&b'0') {
+                i += 1;
+            }
+            while b.get(j) == Some(&b'0') {
+                j += 1;
+            }
+            let mut first_diff = Ordering::Equal;
+            while a.get(i).is_some_and(u8::is_ascii_digit)
+                && b.get(j).is_some_and(u8::is_ascii_digit)
+            {
+                if first_diff == Ordering::Equal {
+                    first_diff = a[i].cmp(&b[j]);
+                }
+                i += 1;
+                j += 1;
+            }
+            if a.get(i).is_some_and(u8::is_ascii_digit) {
+                return Ordering::Greater;
+            }
+            if b.get(j).is_some_and(u8::is_ascii_digit) {
+                return Ordering::Less;
+            }
+            if first_diff != Ordering::Equal {
+                return first_diff;
+            }
+        }
+        Ordering::Equal
+    }
+
+    let (a_epoch, a_upstream, a_revision) = split(a);
+    let (b_epoch, b_upstream, b_revision) = split(b);
+    a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| compare_part(a_upstream.as_bytes(), b_upstream.as_bytes()))
+        .then_with(|| compare_part(a_revision.as_bytes(), b_revision.as_bytes()))
+}
+
+/// Structure of a version string, which decides how it is compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scheme {
+    /// Upstream-style version, such as a release tag (`v1.2.3`) or the version
+    /// an application reports itself
+    Upstream,
+    /// Debian package version: `[epoch:]upstream[-revision]`
+    Debian,
+    /// RPM package version (`%{VERSION}-%{RELEASE}`), which may carry `~`/`^` markers
+    Rpm,
+}
+
+impl Scheme {
+    pub fn label(self) -> &'static str {
+        match self {
+            Scheme::Upstream => "upstream",
+            Scheme::Debian => "debian",
+            Scheme::Rpm => "rpm",
+        }
+    }
+}
+
+/// A version string together with its [`Scheme`].
+///
+/// Versions of different schemes are compared by their upstream part (see
+/// [`Version::upstream_part`]), so an installed `1:1.2.3-1ubuntu1` package and
+/// a `v1.2.4` tag compare as `1.2.3` and `1.2.4`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    raw: String,
+    scheme: Scheme,
+}
+
+impl Version {
+    pub fn new(raw: impl Into<String>, scheme: Scheme) -> Self {
+        Self {
+            raw: raw.into(),
+            scheme,
+        }
+    }
+
+    /// An upstream-style version, e.g. from a release tag.
+    pub fn upstream(raw: impl Into<String>) -> Self {
+        Self::new(raw, Scheme::Upstream)
+    }
+
+    /// Version reported for applications that are not installed, older than
+    /// any real version.
+    pub fn not_installed() -> Self {
+        Self::upstream("0.0.0")
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        self.scheme
+    }
+
+    /// Debian epoch (the number before `:`), 0 if there is none or for other schemes.
+    pub fn epoch(&self) -> u64 {
+        if self.scheme != Scheme::Debian {
+            return 0;
+        }
+        self.raw
+            .split_once(':')
+            .and_then(|(epoch, _)| epoch.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// The upstream version, normalized like a release tag: Debian versions lose
+    /// their epoch, revision and `+` suffix, RPM versions their release and
+    /// `^`/`+` suffixes.
+    /// A `~` pre-release marker becomes a semver pre-release, so `1.2.3~rc1`
+    /// is `1.2.3-rc1` and sorts below `1.2.3`.
+    pub fn upstream_part(&self) -> String {
+        let raw = self.raw.as_str();
+        let upstream = match self.scheme {
+            Scheme::Upstream => raw,
+            Scheme::Debian => {
+                let without_epoch = match raw.split_once(':') {
+                    Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => rest,
+                    _ => raw,
+                };
+                let without_revision = without_epoch
+                    .rsplit_once('-')
+                    .map_or(without_epoch, |(upstream, _)| upstream);
+                without_revision
+                    .split('+')
+                    .next()
+                    .unwrap_or(without_revision)
+            }
+            Scheme::Rpm => {
+                // `%{VERSION}` can't contain `-`, so the release follows the last one
+                let without_release = raw.rsplit_once('-').map_or(raw, |(version, _)| version);
+                without_release
+                    .split(['^', '+'])
+                    .next()
+                    .unwrap_or(without_release)
+            }
+        };
+        normalize_version(&upstream.replace('~', "-"))
+    }
+
+    /// Whether `other` is newer than this version.
+    ///
+    /// Two Debian versions are compared in full, as dpkg does (see
+    /// [`compare_debian`]), so a new revision is newer. Across schemes only
+    /// upstream parts can be compared; a non-zero epoch has no counterpart there,
+    /// so that comparison is logged as unreliable.
+    pub fn is_older_than(&self, other: &Version) -> bool {
+        if self.scheme == Scheme::Debian && other.scheme == Scheme::Debian {
+            return compare_debian(&self.raw, &other.raw) == Ordering::Less;
+        }
+        if self.scheme == other.scheme && self.epoch() != other.epoch() {
+            return self.epoch() < other.epoch();
+        }
+        if self.scheme != other.scheme && (self.epoch() > 0 || other.epoch() > 0) {
+            warn!(
+                "Comparing {} version {} with {} version {}: the epoch is ignored, which may give the wrong result",
+                self.scheme.label(),
+                self.raw,
+                other.scheme.label(),
+                other.raw
+            );
+        }
+        is_newer(&self.upstream_part(), &other.upstream_part())
+    }
+
+    /// Size of the bump from this version to `other`, by the first differing
+    /// upstream component (major.minor.patch); a change of pre-release only is
+    /// a patch bump. `None` if they are the same.
+    pub fn update_level(&self, other: &Version) -> Option<UpdateLevel> {
+        if self.scheme == other.scheme && self.epoch() != other.epoch() {
+            return Some(UpdateLevel::Major);
+        }
+        let (local, remote) = (self.upstream_part(), other.upstream_part());
+        let index = match (parse_semver(&local), parse_semver(&remote)) {
+            (Some(l), Some(r)) => [
+                l.major != r.major,
+                l.minor != r.minor,
+                l.patch != r.patch || l.pre != r.pre,
+            ]
+            .iter()
+            .position(|&differs| differs),
+            _ => first_difference(&local, &remote).map(|(i, _, _)| i),
+        };
+        index.map(|i| match i {
+            0 => UpdateLevel::Major,
+            1 => UpdateLevel::Minor,
+            _ => UpdateLevel::Patch,
+        })
+    }
+}
+
+/// The bump from `current` to `latest` if it is bigger than `app`'s
+/// `auto_update_level` allows, so the update needs approval. A first install
+/// (nothing installed yet) never does.
+pub fn approval_needed(
+    app: &ApplicationConfig,
+    current: &Version,
+    latest: &str,
+) -> Option<UpdateLevel> {
+    let allowed = app.auto_update_level?;
+    if *current == Version::not_installed() {
+        return None;
+    }
+    current
+        .update_level(&Version::new(latest, fetched_scheme(app)))
+        .filter(|&level| level > allowed)
+}
+
+/// Scheme of the versions `app`'s fetcher reports: Debian package versions for
+/// the `apt` fetcher, upstream versions otherwise.
+pub fn fetched_scheme(app: &ApplicationConfig) -> Scheme {
+    match app.fetcher.r#type.as_str() {
+        "apt" => Scheme::Debian,
+        _ => Scheme::Upstream,
+    }
+}
+
+/// Whether `app` should be updated from `current` to the `latest` version its
+/// fetcher reports. A version pinned by `stable_version_url` is installed
+/// whenever it differs, downgrades included, so hosts converge to it; otherwise
+/// [`should_replace_version`] decides, in the fetcher's [`Scheme`].
+pub fn should_update(app: &ApplicationConfig, current: &Version, latest: &str) -> Result<bool> {
+    if app.fetcher.stable_version_url.is_some() {
+        return Ok(current.as_str() != latest);
+    }
+    let requirement = VersionRequirement::from_app(app)?;
+    Ok(should_replace_version(
+        current,
+        &Version::new(latest, fetched_scheme(app)),
+        requirement.as_ref(),
+    ))
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// An application's `version` requirement in semver syntax (`=1.4.3`, `~2.1`,
+/// `>=3, <4`), limiting which releases it is updated to.
+#[derive(Debug, Clone)]
+pub struct VersionRequirement(semver::VersionReq);
+
+impl VersionRequirement {
+    /// The requirement set by `app`'s `version`, if any.
+    pub fn from_app(app: &ApplicationConfig) -> Result<Option<Self>> {
+        app.version
+            .as_deref()
+            .map(|req| {
+                semver::VersionReq::parse(req)
+                    .map(Self)
+                    .with_context(|| format!("Invalid version requirement: {}", req))
+            })
+            .transpose()
+    }
+
+    /// Whether the normalized `version` satisfies the requirement; versions that
+    /// aren't semver never do.
+    pub fn matches(&self, version: &str) -> bool {
+        parse_semver(version).is_some_and(|v| self.0.matches(&v))
+    }
+}
+
+impl std::fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Whether `current` should be replaced with the normalized `candidate`: when
+/// the candidate is newer or, under a `version` requirement it satisfies, when
+/// `current` doesn't, so that a pin also moves back to an allowed version.
+pub fn should_replace(
+    current: &Version,
+    candidate: &str,
+    requirement: Option<&VersionRequirement>,
+) -> bool {
+    should_replace_version(current, &Version::upstream(candidate), requirement)
+}
+
+/// [`should_replace`] for a candidate of any [`Scheme`]; the requirement is
+/// checked against the upstream part of a packaged candidate.
+pub fn should_replace_version(
+    current: &Version,
+    candidate: &Version,
+    requirement: Option<&VersionRequirement>,
+) -> bool {
+    if let Some(requirement) = requirement {
+        let installed = current.upstream_part();
+        let candidate_upstream = match candidate.scheme() {
+            Scheme::Upstream => candidate.as_str().to_string(),
+            _ => candidate.upstream_part(),
+        };
+        if parse_semver(&installed).is_some()
+            && !requirement.matches(&installed)
+            && requirement.matches(&candidate_upstream)
+        {
+            return true;
+        }
+    }
+    current.is_older_than(candidate)
+}
+
+/// Whether an installed version (as reported by an installer) is `expected`.
+///
+/// Packaging decorations are tolerated: a Debian epoch (`1:`) and a revision or
+/// build suffix after `-` or `+` (`1.2.3-1ubuntu1` is `1.2.3`). A `~`
+/// pre-release marker is the same as `-` (`1.2.3~rc1-1` is `1.2.3-rc1`).
+pub fn matches_version(installed: &str, expected: &str) -> bool {
+    let installed = match installed.split_once(':') {
+        Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => installed,
+    };
+    let installed = normalize_version(&installed.replace('~', "-"));
+    let expected = normalize_version(expected);
+    installed == expected
+        || installed
+            .strip_prefix(expected.as_str())
+            .is_some_and(|rest| rest.starts_with(['-', '+']))
+}
+
+/// How release tags are turned into versions: an optional literal prefix and
+/// suffix (e.g. `release/` in `release/1.2.3`) are stripped before normalizing.
+#[derive(Debug, Clone, Default)]
+pub struct TagFormat {
+    prefix: Option<String>,
+    suffix: Option<String>,
+}
+
+impl TagFormat {
+    pub fn from_config(config: &FetcherConfig) -> Self {
+        Self {
+            prefix: config.version_prefix.clone(),
+            suffix: config.version_suffix.clone(),
+        }
+    }
+
+    /// Version extracted from `tag`.
+    pub fn version(&self, tag: &str) -> String {
+        let mut tag = tag;
+        if let Some(prefix) = &self.prefix {
+            tag = tag.strip_prefix(prefix.as_str()).unwrap_or(tag);
+        }
+        if let Some(suffix) = &self.suffix {
+            tag = tag.strip_suffix(suffix.as_str()).unwrap_or(tag);
+        }
+        normalize_version(tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deb(raw: &str) -> Version {
+        Version::new(raw, Scheme::Debian)
+    }
+
+    #[test]
+    fn debian_upstream_part_drops_epoch_revision_and_suffix() {
+        assert_eq!(deb("1:1.2.0+dfsg-1ubuntu1").upstream_part(), "1.2.0");
+        assert_eq!(deb("2.1.5-1").upstream_part(), "2.1.5");
+        assert_eq!(deb("1.2.3~rc1-1").upstream_part(), "1.2.3-rc1");
+        assert_eq!(
+            Version::new("1.2.3~beta2^20240101", Scheme::Rpm).upstream_part(),
+            "1.2.3-beta2"
+        );
+        assert_eq!(
+            Version::new("1.2.3-4.el9", Scheme::Rpm).upstream_part(),
+            "1.2.3"
+        );
+        assert!(
+            !Version::new("1.2.3-4.el9", Scheme::Rpm).is_older_than(&Version::upstream("1.2.3"))
+        );
+    }
+
+    #[test]
+    fn epoch_is_compared_first() {
+        assert!(deb("9.0-1").is_older_than(&deb("1:1.0-1")));
+        assert!(!deb("1:1.0-1").is_older_than(&deb("9.0-1")));
+        assert_eq!(deb("1.0-1").epoch(), 0);
+        assert_eq!(deb("2:1.0-1").epoch(), 2);
+        assert_eq!(
+            deb("1:1.0-1").update_level(&deb("2:1.0-1")),
+            Some(UpdateLevel::Major)
+        );
+    }
+
+    #[test]
+    fn revision_is_ignored_across_schemes() {
+        assert!(!deb("1.2.0-1ubuntu1").is_older_than(&Version::upstream("v1.2.0")));
+        assert!(deb("1.2.0-3").is_older_than(&Version::upstream("1.2.1")));
+    }
+
+    #[test]
+    fn tilde_sorts_below_its_release() {
+        assert!(deb("1.2.3~rc1-1").is_older_than(&Version::upstream("1.2.3")));
+        assert!(deb("1.2.3~rc1-1").is_older_than(&deb("1.2.3-1")));
+        assert!(deb("1.2.3~rc1").is_older_than(&deb("1.2.3~rc2")));
+        assert!(!deb("1.2.3-1").is_older_than(&Version::upstream("1.2.3-rc1")));
+        assert_eq!(
+            deb("1.2.3~rc1-1").update_level(&Version::upstream("1.2.3")),
+            Some(UpdateLevel::Patch)
+        );
+        assert!(matches_version("1.2.3~rc1-1", "1.2.3-rc1"));
+    }
+
+    #[test]
+    fn debian_versions_compare_like_dpkg() {
+        assert!(deb("1.2.0-1").is_older_than(&deb("1.2.0-2")));
+        assert!(deb("1.2.0-2ubuntu9").is_older_than(&deb("1.2.0-2ubuntu10")));
+        assert!(deb("1.2.0~rc1-1").is_older_than(&deb("1.2.0-1")));
+        assert!(deb("1.2.0-1").is_older_than(&deb("1.2.0+dfsg-1")));
+        assert!(deb("1.2.0").is_older_than(&deb("1.2.0-0.1")));
+        assert_eq!(compare_debian("1.02-1", "1.2-1"), Ordering::Equal);
+        assert_eq!(compare_debian("1.2a-1", "1.2-1"), Ordering::Greater);
+    }
+
+    #[test]
+    fn apt_revision_bump_is_an_update() {
+        let app: ApplicationConfig = serde_yaml::from_str(
+            "name: nginx\nversion: '>=1.24'\nfetcher:\n  type: apt\ninstaller: apt\n",
+        )
+        .unwrap();
+        let installed = deb("1.24.0-2ubuntu1");
+        assert!(should_update(&app, &installed, "1.24.0-2ubuntu7").unwrap());
+        assert!(!should_update(&app, &installed, "1.24.0-2ubuntu1").unwrap());
+        assert!(!should_update(&app, &deb("1.24.0-2ubuntu7"), "1.24.0-2ubuntu1").unwrap());
+        assert!(should_update(&app, &deb("1.22.1-9"), "1.24.0-2ubuntu7").unwrap());
+    }
+
+    #[test]
+    fn semver_precedence() {
+        assert!(Version::upstream("1.2.0-rc1").is_older_than(&Version::upstream("1.2.0")));
+        assert!(Version::upstream("1.2.0-rc.2").is_older_than(&Version::upstream("1.2.0-rc.10")));
+    }
+}